@@ -1,9 +1,10 @@
 use tauri::AppHandle;
-use rusqlite::{params, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension};
 use uuid::Uuid;
 use crate::db::{open_db, now_ts};
 use crate::state::{auth_sessions, auth_resolve_account_id, require_admin};
 use crate::models::*;
+use crate::money::Money;
 
 fn sha256_hex(s: &str) -> String {
     use sha2::{Digest, Sha256};
@@ -196,7 +197,7 @@ pub fn auth_accounts_list(app: AppHandle, token: String) -> Result<Vec<AuthAccou
                     is_active: r.get::<_, i64>(6)? != 0,
                     proxy_host: r.get(7)?,
                     is_hidden: r.get::<_, i64>(8).unwrap_or(0) != 0,
-                    salary_base: r.get::<_, f64>(9).unwrap_or(0.0),
+                    salary_base: r.get::<_, Money>(9).unwrap_or_default(),
                     profile,
                 })
             })
@@ -221,7 +222,7 @@ pub fn auth_accounts_list(app: AppHandle, token: String) -> Result<Vec<AuthAccou
                     is_active: r.get::<_, i64>(6)? != 0,
                     proxy_host: r.get(7)?,
                     is_hidden: r.get::<_, i64>(8).unwrap_or(0) != 0,
-                    salary_base: r.get::<_, f64>(9).unwrap_or(0.0),
+                    salary_base: r.get::<_, Money>(9).unwrap_or_default(),
                     profile,
                 })
             })
@@ -687,6 +688,342 @@ pub fn api_list(_app: AppHandle, token: String) -> Result<Vec<ApiRow>, String> {
             path: "/products".to_string(),
             status: "active".to_string(),
         },
+        ApiRow {
+            id: "product_lookup_by_code".to_string(),
+            name: "条码查询".to_string(),
+            method: "GET".to_string(),
+            path: "/products/lookup".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "products_export".to_string(),
+            name: "商品导出".to_string(),
+            method: "GET".to_string(),
+            path: "/products/export".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "products_import".to_string(),
+            name: "商品导入".to_string(),
+            method: "POST".to_string(),
+            path: "/products/import".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "seat_menu".to_string(),
+            name: "座位点单菜单".to_string(),
+            method: "GET".to_string(),
+            path: "/seat/:code/menu".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "seat_orders".to_string(),
+            name: "座位下单".to_string(),
+            method: "POST".to_string(),
+            path: "/seat/:code/orders".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "receipt".to_string(),
+            name: "小票".to_string(),
+            method: "GET".to_string(),
+            path: "/sales/:id/receipt".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "payments_list".to_string(),
+            name: "订单收款明细".to_string(),
+            method: "GET".to_string(),
+            path: "/sales/:id/payments".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "sales_item_modifiers_list".to_string(),
+            name: "订单规格明细".to_string(),
+            method: "GET".to_string(),
+            path: "/sales/:id/modifiers".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "receipt_print".to_string(),
+            name: "打印小票".to_string(),
+            method: "POST".to_string(),
+            path: "/sales/:id/print".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "receipt_link".to_string(),
+            name: "电子小票链接".to_string(),
+            method: "GET".to_string(),
+            path: "/sales/:id/receipt_link".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "receipt_qr".to_string(),
+            name: "电子小票二维码".to_string(),
+            method: "GET".to_string(),
+            path: "/sales/:id/receipt_qr.svg".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "carts_list".to_string(),
+            name: "挂单列表".to_string(),
+            method: "GET".to_string(),
+            path: "/carts".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_create".to_string(),
+            name: "新建购物车".to_string(),
+            method: "POST".to_string(),
+            path: "/carts".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_get".to_string(),
+            name: "购物车详情".to_string(),
+            method: "GET".to_string(),
+            path: "/carts/:id".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_discard".to_string(),
+            name: "作废购物车".to_string(),
+            method: "DELETE".to_string(),
+            path: "/carts/:id".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_item_add".to_string(),
+            name: "购物车加品".to_string(),
+            method: "POST".to_string(),
+            path: "/carts/:id/items".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_item_remove".to_string(),
+            name: "购物车删品".to_string(),
+            method: "DELETE".to_string(),
+            path: "/carts/:id/items/:item_id".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_park".to_string(),
+            name: "挂单".to_string(),
+            method: "POST".to_string(),
+            path: "/carts/:id/park".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_resume".to_string(),
+            name: "取单".to_string(),
+            method: "POST".to_string(),
+            path: "/carts/:id/resume".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "cart_checkout".to_string(),
+            name: "购物车结账".to_string(),
+            method: "POST".to_string(),
+            path: "/carts/:id/checkout".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "displays_list".to_string(),
+            name: "顾客屏列表".to_string(),
+            method: "GET".to_string(),
+            path: "/displays".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "display_pair_start".to_string(),
+            name: "顾客屏配对码".to_string(),
+            method: "POST".to_string(),
+            path: "/displays".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "display_unpair".to_string(),
+            name: "解除顾客屏配对".to_string(),
+            method: "DELETE".to_string(),
+            path: "/displays/:id".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "display_pair".to_string(),
+            name: "顾客屏配对".to_string(),
+            method: "POST".to_string(),
+            path: "/display/pair".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "display_cart_push".to_string(),
+            name: "收银台购物车推送到顾客屏".to_string(),
+            method: "POST".to_string(),
+            path: "/display/cart".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "display_stream".to_string(),
+            name: "顾客屏推送(SSE)".to_string(),
+            method: "GET".to_string(),
+            path: "/display/stream".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "members_list".to_string(),
+            name: "会员列表".to_string(),
+            method: "GET".to_string(),
+            path: "/members".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_upsert".to_string(),
+            name: "保存会员".to_string(),
+            method: "POST".to_string(),
+            path: "/members".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_lookup".to_string(),
+            name: "手机号查会员".to_string(),
+            method: "GET".to_string(),
+            path: "/members/lookup".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_get".to_string(),
+            name: "会员详情".to_string(),
+            method: "GET".to_string(),
+            path: "/members/:id".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_wallet_entries".to_string(),
+            name: "储值流水".to_string(),
+            method: "GET".to_string(),
+            path: "/members/:id/wallet".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_topup".to_string(),
+            name: "会员充值".to_string(),
+            method: "POST".to_string(),
+            path: "/members/:id/topup".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_adjust".to_string(),
+            name: "储值调整".to_string(),
+            method: "POST".to_string(),
+            path: "/members/:id/adjust".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_points_entries".to_string(),
+            name: "积分流水".to_string(),
+            method: "GET".to_string(),
+            path: "/members/:id/points".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "member_points_adjust".to_string(),
+            name: "积分调整".to_string(),
+            method: "POST".to_string(),
+            path: "/members/:id/points/adjust".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "loyalty_settings_get".to_string(),
+            name: "积分规则".to_string(),
+            method: "GET".to_string(),
+            path: "/loyalty/settings".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "redeemable_products".to_string(),
+            name: "积分兑换商品".to_string(),
+            method: "GET".to_string(),
+            path: "/products/redeemable".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupons_list".to_string(),
+            name: "优惠券列表".to_string(),
+            method: "GET".to_string(),
+            path: "/coupons".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_create".to_string(),
+            name: "新建优惠券".to_string(),
+            method: "POST".to_string(),
+            path: "/coupons".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_batch_create".to_string(),
+            name: "批量生成券码".to_string(),
+            method: "POST".to_string(),
+            path: "/coupons/batch".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_lookup".to_string(),
+            name: "查券".to_string(),
+            method: "GET".to_string(),
+            path: "/coupons/lookup".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_use".to_string(),
+            name: "核销优惠券".to_string(),
+            method: "POST".to_string(),
+            path: "/coupons/use".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_void".to_string(),
+            name: "作废优惠券".to_string(),
+            method: "POST".to_string(),
+            path: "/coupons/:id/void".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "coupon_usage_report".to_string(),
+            name: "优惠券使用报表".to_string(),
+            method: "GET".to_string(),
+            path: "/coupons/report".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "meituan_orders_parse".to_string(),
+            name: "解析美团验券记录".to_string(),
+            method: "POST".to_string(),
+            path: "/meituan/parse".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "meituan_orders_import_text".to_string(),
+            name: "导入美团验券记录".to_string(),
+            method: "POST".to_string(),
+            path: "/meituan/import".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "meituan_settlements_import".to_string(),
+            name: "导入美团结算单".to_string(),
+            method: "POST".to_string(),
+            path: "/meituan/settlements/import".to_string(),
+            status: "active".to_string(),
+        },
+        ApiRow {
+            id: "meituan_reconciliation".to_string(),
+            name: "美团结算对账".to_string(),
+            method: "GET".to_string(),
+            path: "/meituan/reconciliation".to_string(),
+            status: "active".to_string(),
+        },
         ApiRow {
             id: "shift_records_list".to_string(),
             name: "交班记录".to_string(),
@@ -728,7 +1065,10 @@ pub fn operation_logs_list(_app: AppHandle, token: String, _limit: Option<i64>)
 #[tauri::command]
 pub fn auth_get_brand_settings(app: AppHandle) -> Result<BrandSettings, String> {
     let conn = open_db(&app)?;
-    
+    load_brand_settings(&conn)
+}
+
+pub fn load_brand_settings(conn: &Connection) -> Result<BrandSettings, String> {
     let brand_name: String = conn.query_row("SELECT v FROM kv WHERE k = 'brand_name'", [], |r| r.get(0))
         .optional()
         .map_err(|e| format!("get brand_name: {e}"))?
@@ -813,7 +1153,7 @@ pub struct AuthAccountUpdateInput {
     pub display_name: Option<String>,
     pub equity: Option<f64>,
     pub proxy_host: Option<String>,
-    pub salary_base: Option<f64>,
+    pub salary_base: Option<Money>,
     pub is_hidden: Option<bool>,
     pub profile: Option<String>, // JSON string
 }
@@ -864,7 +1204,7 @@ pub struct AuthAccountRow {
     pub is_active: bool,
     pub proxy_host: Option<String>,
     pub is_hidden: bool,
-    pub salary_base: f64,
+    pub salary_base: Money,
     pub profile: Option<String>,
 }
 
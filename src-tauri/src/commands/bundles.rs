@@ -0,0 +1,94 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{BundleComponentRow, BundleComponentsSetInput, ProductRow};
+use crate::commands::products::{load_linked_products, load_product, load_sellable_product};
+use crate::money::Money;
+
+// A bundle (套餐) is a product of kind 'bundle' whose sale moves its
+// components: "网费 + 饮料", snack combos, Meituan packages.
+
+#[derive(Debug, Clone)]
+pub struct BundleComponent {
+    pub product: ProductRow,
+    pub quantity: f64,
+}
+
+pub fn load_bundle_components(conn: &Connection, bundle_id: &str) -> Result<Vec<BundleComponent>, String> {
+    Ok(load_linked_products(conn, "product_bundle_items", "bundle_id", "component_id", bundle_id)?
+        .into_iter()
+        .map(|(product, quantity)| BundleComponent { product, quantity })
+        .collect())
+}
+
+/// Splits bundle revenue over its components in proportion to their list
+/// value (unit price × quantity). The shares add up to `revenue` exactly.
+pub fn split_revenue(revenue: Money, components: &[BundleComponent]) -> Vec<Money> {
+    let weights: Vec<f64> = components.iter().map(|c| c.product.unit_price.fen() as f64 * c.quantity).collect();
+    revenue.allocate(&weights)
+}
+
+#[tauri::command]
+pub fn bundle_components_list(app: AppHandle, token: String, bundle_id: String) -> Result<Vec<BundleComponentRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    Ok(load_bundle_components(&conn, bundle_id.trim())?
+        .into_iter()
+        .map(|c| BundleComponentRow {
+            component_id: c.product.id,
+            component_name: c.product.name,
+            quantity: c.quantity,
+            unit_price: c.product.unit_price,
+        })
+        .collect())
+}
+
+/// Replaces a bundle's component list; an empty list turns it back into a
+/// plain product.
+#[tauri::command]
+pub fn bundle_components_set(app: AppHandle, input: BundleComponentsSetInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let bundle_id = input.bundle_id.trim().to_string();
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let _ = load_product(&tx, &bundle_id)?.ok_or_else(|| format!("product_not_found: {bundle_id}"))?;
+
+    if !input.components.is_empty() {
+        let used_in: i64 = tx
+            .query_row("SELECT COUNT(1) FROM product_bundle_items WHERE component_id = ?1", [&bundle_id], |r| r.get(0))
+            .map_err(|e| format!("query bundle usage: {e}"))?;
+        if used_in > 0 {
+            return Err(String::from("nested_bundle"));
+        }
+    }
+
+    tx.execute("DELETE FROM product_bundle_items WHERE bundle_id = ?1", [&bundle_id])
+        .map_err(|e| format!("clear bundle: {e}"))?;
+    for (i, c) in input.components.iter().enumerate() {
+        let component_id = c.product_id.trim();
+        if component_id == bundle_id || !c.quantity.is_finite() || c.quantity <= 0.0 {
+            return Err(String::from("invalid"));
+        }
+        let component = load_sellable_product(&tx, component_id)?.ok_or_else(|| format!("product_not_found: {component_id}"))?;
+        if component.kind == "bundle" {
+            return Err(format!("nested_bundle: {}", component.name));
+        }
+        tx.execute(
+            "INSERT INTO product_bundle_items(bundle_id, component_id, quantity, sort_order) VALUES(?1, ?2, ?3, ?4) \
+             ON CONFLICT(bundle_id, component_id) DO UPDATE SET quantity = quantity + excluded.quantity",
+            params![bundle_id, component_id, c.quantity, i as i64],
+        )
+        .map_err(|e| format!("insert bundle item: {e}"))?;
+    }
+    tx.execute(
+        "UPDATE products SET kind = ?2, updated_at = ?3 WHERE id = ?1",
+        params![bundle_id, if input.components.is_empty() { "item" } else { "bundle" }, now],
+    )
+    .map_err(|e| format!("update kind: {e}"))?;
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
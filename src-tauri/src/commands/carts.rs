@@ -0,0 +1,512 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::auth_resolve_account_id;
+use crate::constants::{CART_EXPIRY_SECS, CART_STATUSES};
+use crate::models::*;
+use crate::commands::products::lookup_product_by_code;
+use crate::commands::sales::{checkout, price_items};
+use crate::commands::idempotency::{replayed_result, remember_result};
+use crate::commands::display::{refresh_display, show_paid};
+use crate::money::Money;
+
+// A cart lives on the server so any terminal can pick it up: the till parks
+// one customer's order to serve the next, a tablet hands an order to the desk.
+// Lines keep only what was entered (product, quantity, unit, options); every
+// read prices them again with the checkout engine, so a cart always shows what
+// `pos_checkout` would charge right now. Nothing touches stock until checkout.
+// Open and parked carts expire CART_EXPIRY_SECS after their last change.
+
+/// Marks carts nobody has touched for CART_EXPIRY_SECS as expired.
+fn expire_carts(conn: &Connection, now: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE carts SET status = 'expired', updated_at = ?1 WHERE status IN ('open', 'parked') AND expires_at <= ?1",
+        [now],
+    )
+    .map_err(|e| format!("expire carts: {e}"))?;
+    Ok(())
+}
+
+fn touch_cart(conn: &Connection, id: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE carts SET updated_at = ?2, expires_at = ?3 WHERE id = ?1",
+        params![id, now, now + CART_EXPIRY_SECS],
+    )
+    .map_err(|e| format!("update cart: {e}"))?;
+    Ok(())
+}
+
+fn cart_status(conn: &Connection, id: &str) -> Result<String, String> {
+    conn.query_row("SELECT status FROM carts WHERE id = ?1", [id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query cart: {e}"))?
+        .ok_or_else(|| String::from("not_found"))
+}
+
+fn require_open(conn: &Connection, id: &str) -> Result<(), String> {
+    let status = cart_status(conn, id)?;
+    if status != "open" {
+        return Err(format!("cart_not_open: {status}"));
+    }
+    Ok(())
+}
+
+/// The cart's lines as checkout items, in the order they were added.
+fn load_cart_items(conn: &Connection, cart_id: &str) -> Result<Vec<(String, PosItem)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, product_id, quantity, unit, modifiers FROM cart_items WHERE cart_id = ?1 ORDER BY sort_order ASC")
+        .map_err(|e| format!("prepare cart_items: {e}"))?;
+    let rows = stmt
+        .query_map([cart_id], |r| {
+            let modifiers: String = r.get(4)?;
+            Ok((
+                r.get::<_, String>(0)?,
+                PosItem {
+                    product_id: r.get(1)?,
+                    barcode: None,
+                    quantity: r.get(2)?,
+                    unit: r.get(3)?,
+                    modifiers: Some(serde_json::from_str(&modifiers).unwrap_or_default()),
+                    redeem: false,
+                },
+            ))
+        })
+        .map_err(|e| format!("query cart_items: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row cart_items: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Loads a cart and prices its lines as of `now`.
+pub fn load_cart(conn: &Connection, id: &str, now: i64) -> Result<CartView, String> {
+    let mut cart = conn
+        .query_row(
+            "SELECT id, label, status, terminal, location_id, order_id, created_at, updated_at, expires_at FROM carts WHERE id = ?1",
+            [id],
+            |r| {
+                Ok(CartView {
+                    id: r.get(0)?,
+                    label: r.get(1)?,
+                    status: r.get(2)?,
+                    terminal: r.get(3)?,
+                    location_id: r.get(4)?,
+                    order_id: r.get(5)?,
+                    lines: Vec::new(),
+                    subtotal: Money::ZERO,
+                    discount: Money::ZERO,
+                    total: Money::ZERO,
+                    created_at: r.get(6)?,
+                    updated_at: r.get(7)?,
+                    expires_at: r.get(8)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("query cart: {e}"))?
+        .ok_or_else(|| String::from("not_found"))?;
+
+    let items = load_cart_items(conn, id)?;
+    price_cart_lines(conn, &mut cart, items, now)?;
+    Ok(cart)
+}
+
+/// Prices `items` (line id, item) with the checkout engine and adds them to
+/// `cart` as lines with their totals. A line whose product, unit or options
+/// can no longer be sold stays in the cart at zero with the reason in
+/// `unavailable`, so one stale line does not hide the rest of the cart.
+pub fn price_cart_lines(conn: &Connection, cart: &mut CartView, items: Vec<(String, PosItem)>, now: i64) -> Result<(), String> {
+    let mut sellable = Vec::new();
+    let mut lines: Vec<(usize, CartLineRow)> = Vec::new();
+    for (i, (item_id, it)) in items.into_iter().enumerate() {
+        match price_items(conn, std::slice::from_ref(&it), now) {
+            Ok(_) => sellable.push((i, item_id, it)),
+            Err(e) => {
+                let product_name: Option<String> = conn
+                    .query_row("SELECT name FROM products WHERE id = ?1", [&it.product_id], |r| r.get(0))
+                    .optional()
+                    .map_err(|e| format!("query product: {e}"))?;
+                lines.push((
+                    i,
+                    CartLineRow {
+                        id: item_id,
+                        product_name: product_name.unwrap_or_else(|| it.product_id.clone()),
+                        product_id: it.product_id,
+                        quantity: it.quantity,
+                        unit: it.unit,
+                        modifiers: it.modifiers.unwrap_or_default(),
+                        modifier_names: String::new(),
+                        unit_price: Money::ZERO,
+                        gross: Money::ZERO,
+                        discount: Money::ZERO,
+                        promotion_name: None,
+                        net: Money::ZERO,
+                        unavailable: Some(e),
+                    },
+                ));
+            }
+        }
+    }
+
+    let pos_items: Vec<PosItem> = sellable.iter().map(|(_, _, it)| it.clone()).collect();
+    let (resolved, priced) = price_items(conn, &pos_items, now)?;
+    for (((i, item_id, it), (product, modifiers)), line) in sellable.into_iter().zip(resolved).zip(priced) {
+        let discount = line.promotion.as_ref().map(|p| p.discount).unwrap_or_default();
+        cart.subtotal += line.gross();
+        cart.discount += discount;
+        cart.total += line.net();
+        lines.push((
+            i,
+            CartLineRow {
+                id: item_id,
+                product_id: product.id,
+                product_name: product.name,
+                quantity: it.quantity,
+                unit: it.unit,
+                modifiers: modifiers.iter().map(|m| m.option_id.clone()).collect(),
+                modifier_names: modifiers.iter().map(|m| m.option_name.as_str()).collect::<Vec<_>>().join("、"),
+                unit_price: line.sold_unit_price(),
+                gross: line.gross(),
+                discount,
+                promotion_name: line.promotion.as_ref().map(|p| p.name.clone()),
+                net: line.net(),
+                unavailable: None,
+            },
+        ));
+    }
+    lines.sort_by_key(|(i, _)| *i);
+    cart.lines.extend(lines.into_iter().map(|(_, line)| line));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cart_create(app: AppHandle, input: CartCreateInput) -> Result<CartView, String> {
+    let conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let terminal = input.terminal.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let label = input.label.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let location_id = input.location_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO carts(id, label, status, terminal, location_id, actor_id, created_at, updated_at, expires_at) VALUES(?1, ?2, 'open', ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![id, label, terminal, location_id, actor_id, now, now, now + CART_EXPIRY_SECS],
+    )
+    .map_err(|e| format!("insert cart: {e}"))?;
+    refresh_display(&conn, terminal.as_deref(), now);
+    load_cart(&conn, &id, now)
+}
+
+#[tauri::command]
+pub fn cart_get(app: AppHandle, token: String, id: String) -> Result<CartView, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    expire_carts(&conn, now)?;
+    load_cart(&conn, id.trim(), now)
+}
+
+/// Carts in `status`, or every open and parked cart, newest change first.
+#[tauri::command]
+pub fn carts_list(app: AppHandle, token: String, status: Option<String>) -> Result<Vec<CartView>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    expire_carts(&conn, now)?;
+    let status = status.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if status.is_some_and(|s| !CART_STATUSES.contains(&s)) {
+        return Err(String::from("invalid"));
+    }
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM carts WHERE (?1 IS NULL AND status IN ('open', 'parked')) OR status = ?1 \
+             ORDER BY updated_at DESC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let ids = stmt
+        .query_map([status], |r| r.get::<_, String>(0))
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for id in ids {
+        let id = id.map_err(|e| format!("row: {e}"))?;
+        out.push(load_cart(&conn, &id, now)?);
+    }
+    Ok(out)
+}
+
+/// Adds a line to an open cart. The same product with the same unit and
+/// options bumps the existing line instead of adding a second one.
+#[tauri::command]
+pub fn cart_item_add(app: AppHandle, input: CartItemAddInput) -> Result<CartView, String> {
+    let mut conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    if !input.quantity.is_finite() || input.quantity <= 0.0 {
+        return Err(String::from("invalid"));
+    }
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    expire_carts(&tx, now)?;
+    let cart_id = input.cart_id.trim();
+    require_open(&tx, cart_id)?;
+
+    let mut unit = input.unit.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let product_id = match input.barcode.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) if input.product_id.trim().is_empty() => {
+            let hit = lookup_product_by_code(&tx, code)?.ok_or_else(|| format!("barcode_not_found: {code}"))?;
+            unit = unit.or(hit.unit);
+            hit.product.id
+        }
+        _ => input.product_id.trim().to_string(),
+    };
+    let mut option_ids: Vec<String> = input.modifiers.unwrap_or_default().iter().map(|s| s.trim().to_string()).collect();
+    option_ids.sort();
+    option_ids.dedup();
+    let modifiers = serde_json::to_string(&option_ids).map_err(|e| format!("modifiers: {e}"))?;
+    // Unknown products, units and options are rejected before anything is
+    // saved; reading the cart back would only flag them.
+    let item = PosItem {
+        product_id: product_id.clone(),
+        barcode: None,
+        quantity: input.quantity,
+        unit: unit.clone(),
+        modifiers: Some(option_ids.clone()),
+        redeem: false,
+    };
+    price_items(&tx, &[item], now)?;
+
+    let existing: Option<String> = tx
+        .query_row(
+            "SELECT id FROM cart_items WHERE cart_id = ?1 AND product_id = ?2 AND COALESCE(unit, '') = COALESCE(?3, '') AND modifiers = ?4",
+            params![cart_id, product_id, unit, modifiers],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("query cart_items: {e}"))?;
+    match existing {
+        Some(item_id) => {
+            tx.execute(
+                "UPDATE cart_items SET quantity = quantity + ?2 WHERE id = ?1",
+                params![item_id, input.quantity],
+            )
+            .map_err(|e| format!("update cart_items: {e}"))?;
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO cart_items(id, cart_id, product_id, quantity, unit, modifiers, sort_order, created_at) \
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM cart_items WHERE cart_id = ?2), ?7)",
+                params![Uuid::new_v4().to_string(), cart_id, product_id, input.quantity, unit, modifiers, now],
+            )
+            .map_err(|e| format!("insert cart_items: {e}"))?;
+        }
+    }
+    touch_cart(&tx, cart_id, now)?;
+    let cart = load_cart(&tx, cart_id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    refresh_display(&conn, cart.terminal.as_deref(), now);
+    Ok(cart)
+}
+
+#[tauri::command]
+pub fn cart_item_remove(app: AppHandle, token: String, cart_id: String, item_id: String) -> Result<CartView, String> {
+    let mut conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    expire_carts(&tx, now)?;
+    let cart_id = cart_id.trim();
+    require_open(&tx, cart_id)?;
+    let n = tx
+        .execute("DELETE FROM cart_items WHERE id = ?1 AND cart_id = ?2", params![item_id.trim(), cart_id])
+        .map_err(|e| format!("delete cart_items: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    touch_cart(&tx, cart_id, now)?;
+    let cart = load_cart(&tx, cart_id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    refresh_display(&conn, cart.terminal.as_deref(), now);
+    Ok(cart)
+}
+
+/// Puts an open cart aside so the terminal can serve someone else.
+#[tauri::command]
+pub fn cart_park(app: AppHandle, token: String, id: String, label: Option<String>) -> Result<CartView, String> {
+    let mut conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    expire_carts(&tx, now)?;
+    let id = id.trim();
+    require_open(&tx, id)?;
+    let label = label.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    // Parking clears the terminal, so note whose display to clear first.
+    let terminal: Option<String> = tx
+        .query_row("SELECT terminal FROM carts WHERE id = ?1", [id], |r| r.get(0))
+        .map_err(|e| format!("query cart: {e}"))?;
+    tx.execute(
+        "UPDATE carts SET status = 'parked', terminal = NULL, label = COALESCE(?2, label) WHERE id = ?1",
+        params![id, label],
+    )
+    .map_err(|e| format!("update cart: {e}"))?;
+    touch_cart(&tx, id, now)?;
+    let cart = load_cart(&tx, id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    refresh_display(&conn, terminal.as_deref(), now);
+    Ok(cart)
+}
+
+/// Picks up a parked cart on `terminal`, which need not be the one that parked it.
+#[tauri::command]
+pub fn cart_resume(app: AppHandle, token: String, id: String, terminal: Option<String>) -> Result<CartView, String> {
+    let mut conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    expire_carts(&tx, now)?;
+    let id = id.trim();
+    let terminal = terminal.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    // Only one terminal can win a parked cart.
+    let n = tx
+        .execute(
+            "UPDATE carts SET status = 'open', terminal = ?2 WHERE id = ?1 AND status = 'parked'",
+            params![id, terminal],
+        )
+        .map_err(|e| format!("update cart: {e}"))?;
+    if n == 0 {
+        return Err(format!("cart_not_parked: {}", cart_status(&tx, id)?));
+    }
+    touch_cart(&tx, id, now)?;
+    let cart = load_cart(&tx, id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    refresh_display(&conn, terminal.as_deref(), now);
+    Ok(cart)
+}
+
+#[tauri::command]
+pub fn cart_discard(app: AppHandle, token: String, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let id = id.trim();
+    let terminal: Option<String> = conn
+        .query_row("SELECT terminal FROM carts WHERE id = ?1", [id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query cart: {e}"))?
+        .flatten();
+    let n = conn
+        .execute(
+            "UPDATE carts SET status = 'discarded', updated_at = ?2 WHERE id = ?1 AND status IN ('open', 'parked')",
+            params![id, now],
+        )
+        .map_err(|e| format!("update cart: {e}"))?;
+    if n == 0 {
+        return Err(format!("cart_not_open: {}", cart_status(&conn, id)?));
+    }
+    refresh_display(&conn, terminal.as_deref(), now);
+    Ok(())
+}
+
+/// Checks an open cart out as a sales order, priced and paid exactly like
+/// `pos_checkout`. Checking out a cart twice returns the first order.
+#[tauri::command]
+pub fn cart_checkout(app: AppHandle, input: CartCheckoutInput) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| format!("tx: {e}"))?;
+    expire_carts(&tx, now)?;
+    let cart_id = input.cart_id.trim();
+    let (status, terminal, location_id, order_id): (String, Option<String>, Option<String>, Option<String>) = tx
+        .query_row("SELECT status, terminal, location_id, order_id FROM carts WHERE id = ?1", [cart_id], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))
+        })
+        .optional()
+        .map_err(|e| format!("query cart: {e}"))?
+        .ok_or_else(|| String::from("not_found"))?;
+    if let Some(id) = order_id.filter(|_| status == "checked_out") {
+        return Ok(id);
+    }
+    if let Some(id) = replayed_result(&tx, "cart_checkout", input.idempotency_key.as_deref(), now)? {
+        return Ok(id);
+    }
+    if status != "open" {
+        return Err(format!("cart_not_open: {status}"));
+    }
+
+    let items: Vec<PosItem> = load_cart_items(&tx, cart_id)?.into_iter().map(|(_, it)| it).collect();
+    if items.is_empty() {
+        return Err(String::from("empty_cart"));
+    }
+    let order = PosCheckoutInput {
+        token: input.token.clone(),
+        date_ymd: input.date_ymd,
+        shift: input.shift,
+        employee: input.employee,
+        items,
+        location_id: input.location_id.filter(|s| !s.trim().is_empty()).or(location_id),
+        payments: input.payments,
+        rounding: input.rounding,
+        order_id: None,
+        idempotency_key: None,
+        member_id: input.member_id,
+        coupon_code: input.coupon_code,
+        terminal: terminal.clone(),
+    };
+    let order_id = Uuid::new_v4().to_string();
+    checkout(&tx, &order_id, &order, &actor_id, now)?;
+    tx.execute(
+        "UPDATE carts SET status = 'checked_out', order_id = ?2, updated_at = ?3 WHERE id = ?1",
+        params![cart_id, order_id, now],
+    )
+    .map_err(|e| format!("update cart: {e}"))?;
+    remember_result(&tx, "cart_checkout", input.idempotency_key.as_deref(), &order_id, now)?;
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    show_paid(&conn, terminal.as_deref(), &order_id, now);
+    Ok(order_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+
+    #[test]
+    fn stale_lines_are_flagged_instead_of_failing_the_cart() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at) \
+               VALUES('cola', '可乐', '饮料', 300, 0, 1, 0, 0, 1, 0, 0), ('chips', '薯片', '零食', 800, 0, 1, 0, 0, 1, 0, 0);\
+             INSERT INTO carts(id, status, created_at, updated_at, expires_at) VALUES('c1', 'parked', 0, 0, 99);\
+             INSERT INTO cart_items(id, cart_id, product_id, quantity, sort_order, created_at) VALUES('l1', 'c1', 'cola', 2, 0, 0);\
+             INSERT INTO cart_items(id, cart_id, product_id, quantity, unit, sort_order, created_at) VALUES('l2', 'c1', 'chips', 1, '箱', 1, 0);\
+             UPDATE products SET deleted_at = 5 WHERE id = 'cola';",
+        )
+        .unwrap();
+
+        let cart = load_cart(&conn, "c1", 10).unwrap();
+        let flags: Vec<(&str, &str, bool)> = cart.lines.iter().map(|l| (l.id.as_str(), l.product_name.as_str(), l.unavailable.is_some())).collect();
+        assert_eq!(flags, vec![("l1", "可乐", true), ("l2", "薯片", true)]);
+        assert_eq!(cart.total, Money::ZERO);
+
+        conn.execute("DELETE FROM cart_items WHERE id = 'l2'", []).unwrap();
+        conn.execute("INSERT INTO cart_items(id, cart_id, product_id, quantity, sort_order, created_at) VALUES('l3', 'c1', 'chips', 1, 2, 0)", [])
+            .unwrap();
+        let cart = load_cart(&conn, "c1", 10).unwrap();
+        assert_eq!(cart.lines.iter().map(|l| l.id.as_str()).collect::<Vec<_>>(), vec!["l1", "l3"]);
+        assert_eq!(cart.lines[1].unavailable, None);
+        assert_eq!(cart.total, Money::from_fen(800));
+    }
+}
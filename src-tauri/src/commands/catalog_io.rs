@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{ProductRow, ProductImportInput, ProductImportReport, ProductImportRow};
+use crate::commands::products::{load_product, sku_is_barcode, PRODUCT_COLUMNS, map_product_row};
+use crate::commands::inventory::seed_product_stock;
+use crate::commands::units::spec_conflict;
+use crate::commands::pricing::record_price;
+use crate::commands::categories::resolve_category;
+use crate::money::Money;
+
+// Spreadsheet columns, as (field, header written on export). Import accepts
+// either name as a header; `mapping` can point a field at any other header.
+const COLUMNS: [(&str, &str); 10] = [
+    ("id", "编号"),
+    ("name", "名称"),
+    ("category", "分类"),
+    ("unit_price", "售价"),
+    ("cost_price", "成本价"),
+    ("spec", "规格"),
+    ("base_unit", "单位"),
+    ("sku", "SKU"),
+    ("barcodes", "条码"),
+    ("is_active", "启用"),
+];
+
+const UTF8_BOM: &str = "\u{feff}";
+
+fn load_barcodes(conn: &Connection, product_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT code FROM product_barcodes WHERE product_id = ?1 ORDER BY created_at ASC")
+        .map_err(|e| format!("prepare barcodes: {e}"))?;
+    let rows = stmt
+        .query_map([product_id], |r| r.get::<_, String>(0))
+        .map_err(|e| format!("query barcodes: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row barcodes: {e}"))?);
+    }
+    Ok(out)
+}
+
+fn export_rows(conn: &Connection) -> Result<Vec<(ProductRow, Vec<String>)>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE deleted_at IS NULL ORDER BY category ASC, name ASC"))
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt.query_map([], map_product_row).map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        let p = r.map_err(|e| format!("row: {e}"))?;
+        let codes = load_barcodes(conn, &p.id)?;
+        out.push((p, codes));
+    }
+    Ok(out)
+}
+
+fn export_cells(p: &ProductRow, codes: &[String]) -> [String; 10] {
+    [
+        p.id.clone(),
+        p.name.clone(),
+        p.category.clone(),
+        p.unit_price.to_string(),
+        p.cost_price.to_string(),
+        p.spec.to_string(),
+        p.base_unit.clone(),
+        p.sku.clone().unwrap_or_default(),
+        codes.join("|"),
+        String::from(if p.is_active { "1" } else { "0" }),
+    ]
+}
+
+/// Writes the catalog as CSV (UTF-8 with BOM, so Excel opens it) or XLSX.
+pub fn export_products(conn: &Connection, format: &str) -> Result<Vec<u8>, String> {
+    let rows = export_rows(conn)?;
+    match format {
+        "csv" => {
+            let mut wtr = csv::Writer::from_writer(UTF8_BOM.as_bytes().to_vec());
+            wtr.write_record(COLUMNS.iter().map(|(_, h)| *h))
+                .map_err(|e| format!("csv write: {e}"))?;
+            for (p, codes) in rows.iter() {
+                wtr.write_record(export_cells(p, codes))
+                    .map_err(|e| format!("csv write: {e}"))?;
+            }
+            wtr.into_inner().map_err(|e| format!("csv flush: {e}"))
+        }
+        "xlsx" => {
+            let mut wb = rust_xlsxwriter::Workbook::new();
+            let ws = wb.add_worksheet();
+            for (c, (_, h)) in COLUMNS.iter().enumerate() {
+                ws.write_string(0, c as u16, *h).map_err(|e| format!("xlsx write: {e}"))?;
+            }
+            for (i, (p, codes)) in rows.iter().enumerate() {
+                let r = (i + 1) as u32;
+                for (c, cell) in export_cells(p, codes).iter().enumerate() {
+                    let col = c as u16;
+                    // Prices and spec stay numeric so the sheet can be edited with formulas.
+                    match COLUMNS[c].0 {
+                        "unit_price" | "cost_price" | "spec" => {
+                            ws.write_number(r, col, cell.parse::<f64>().unwrap_or(0.0))
+                                .map_err(|e| format!("xlsx write: {e}"))?;
+                        }
+                        _ => {
+                            ws.write_string(r, col, cell).map_err(|e| format!("xlsx write: {e}"))?;
+                        }
+                    }
+                }
+            }
+            wb.save_to_buffer().map_err(|e| format!("xlsx save: {e}"))
+        }
+        _ => Err(String::from("unsupported_format")),
+    }
+}
+
+/// Reads the first sheet (XLSX) or the whole file (CSV) as rows of text cells.
+pub fn read_table(format: &str, content: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    match format {
+        "csv" => {
+            let text = std::str::from_utf8(content).map_err(|_| String::from("csv_not_utf8"))?;
+            let text = text.strip_prefix(UTF8_BOM).unwrap_or(text);
+            let mut rdr = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(text.as_bytes());
+            let mut out = Vec::new();
+            for rec in rdr.records() {
+                let rec = rec.map_err(|e| format!("csv read: {e}"))?;
+                out.push(rec.iter().map(|c| c.trim().to_string()).collect());
+            }
+            Ok(out)
+        }
+        "xlsx" => {
+            use calamine::Reader;
+            let mut wb: calamine::Xlsx<_> = calamine::Xlsx::new(Cursor::new(content.to_vec()))
+                .map_err(|e| format!("xlsx open: {e}"))?;
+            let range = wb
+                .worksheet_range_at(0)
+                .ok_or_else(|| String::from("xlsx_empty"))?
+                .map_err(|e| format!("xlsx read: {e}"))?;
+            Ok(range
+                .rows()
+                .map(|row| row.iter().map(|c| c.to_string().trim().to_string()).collect())
+                .collect())
+        }
+        _ => Err(String::from("unsupported_format")),
+    }
+}
+
+/// Field name -> column index, from the header row and the caller's mapping.
+fn resolve_columns(header: &[String], mapping: Option<&HashMap<String, String>>) -> HashMap<&'static str, usize> {
+    let find = |label: &str| header.iter().position(|h| h.eq_ignore_ascii_case(label.trim()));
+    let mut out = HashMap::new();
+    for (field, title) in COLUMNS.iter() {
+        let mapped = mapping.and_then(|m| m.get(*field)).map(|s| s.trim()).filter(|s| !s.is_empty());
+        let idx = match mapped {
+            Some(label) => find(label),
+            None => find(field).or_else(|| find(title)),
+        };
+        if let Some(i) = idx {
+            out.insert(*field, i);
+        }
+    }
+    out
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    let cleaned: String = s.chars().filter(|c| !matches!(c, '¥' | '￥' | ',' | ' ')).collect();
+    cleaned.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" | "是" | "启用" => Some(true),
+        "0" | "false" | "no" | "n" | "否" | "停用" => Some(false),
+        _ => None,
+    }
+}
+
+fn find_existing(conn: &Connection, id: Option<&str>, sku: Option<&str>, name: Option<&str>) -> Result<Option<ProductRow>, String> {
+    if let Some(id) = id {
+        return load_product(conn, id);
+    }
+    let lookup = |sql: &str, v: &str| -> Result<Option<String>, String> {
+        conn.query_row(sql, [v], |r| r.get(0))
+            .optional()
+            .map_err(|e| format!("query product: {e}"))
+    };
+    let hit = match (sku, name) {
+        (Some(s), _) => lookup("SELECT id FROM products WHERE sku = ?1", s)?,
+        (None, Some(n)) => lookup("SELECT id FROM products WHERE name = ?1", n)?,
+        _ => None,
+    };
+    match hit {
+        Some(id) => load_product(conn, &id),
+        None => Ok(None),
+    }
+}
+
+/// Validates and writes one spreadsheet row. Problems are returned in the row
+/// report instead of as an error so a preview can list all of them.
+fn import_row(conn: &Connection, cols: &HashMap<&'static str, usize>, cells: &[String], now: i64) -> Result<ProductImportRow, String> {
+    let cell = |field: &str| cols.get(field).and_then(|i| cells.get(*i)).map(|s| s.trim()).filter(|s| !s.is_empty());
+    let mut errors: Vec<String> = Vec::new();
+    let mut number = |field: &str| match cell(field) {
+        Some(v) => match parse_number(v) {
+            Some(n) if n >= 0.0 => Some(n),
+            _ => {
+                errors.push(format!("invalid {field}: {v}"));
+                None
+            }
+        },
+        None => None,
+    };
+    let unit_price = number("unit_price").map(Money::from_yuan);
+    let cost_price = number("cost_price").map(Money::from_yuan);
+    let spec = number("spec");
+    let is_active = match cell("is_active") {
+        Some(v) => match parse_bool(v) {
+            Some(b) => Some(b),
+            None => {
+                errors.push(format!("invalid is_active: {v}"));
+                None
+            }
+        },
+        None => None,
+    };
+    let name = cell("name").map(str::to_string);
+    let sku = cell("sku").map(str::to_string);
+    let barcodes: Vec<String> = cell("barcodes")
+        .map(|v| v.split(['|', ',', ' ']).map(str::trim).filter(|c| !c.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let existing = find_existing(conn, cell("id"), sku.as_deref(), name.as_deref())?;
+    let mut report = ProductImportRow {
+        row: 0,
+        action: String::new(),
+        product_id: existing.as_ref().map(|p| p.id.clone()),
+        name: name.clone().or_else(|| existing.as_ref().map(|p| p.name.clone())).unwrap_or_default(),
+        errors: Vec::new(),
+    };
+    if existing.is_none() && name.is_none() {
+        errors.push(String::from("missing name"));
+    }
+    let category = match cell("category") {
+        Some(c) => match resolve_category(conn, c) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        },
+        None => None,
+    };
+    if let (Some(p), Some(s)) = (existing.as_ref(), spec) {
+        errors.extend(spec_conflict(conn, &p.id, s)?);
+    }
+    if let Some(code) = sku.as_deref() {
+        if sku_is_barcode(conn, code, existing.as_ref().map(|p| p.id.as_str()).or(cell("id")))? {
+            errors.push(format!("duplicate_code: {code}"));
+        }
+    }
+    if !errors.is_empty() {
+        report.action = String::from("error");
+        report.errors = errors;
+        return Ok(report);
+    }
+
+    let map_conflict = |e: rusqlite::Error| {
+        let msg = e.to_string();
+        if msg.contains("products.sku") {
+            String::from("duplicate_sku")
+        } else if msg.contains("products.name") {
+            String::from("duplicate_name")
+        } else {
+            format!("execute: {e}")
+        }
+    };
+
+    let product_id = match existing.as_ref() {
+        Some(p) => {
+            let next = ProductRow {
+                name: name.clone().unwrap_or_else(|| p.name.clone()),
+                category: category.clone().unwrap_or_else(|| p.category.clone()),
+                unit_price: unit_price.unwrap_or(p.unit_price),
+                cost_price: cost_price.unwrap_or(p.cost_price),
+                spec: spec.unwrap_or(p.spec),
+                base_unit: cell("base_unit").map(str::to_string).unwrap_or_else(|| p.base_unit.clone()),
+                sku: sku.clone().or_else(|| p.sku.clone()),
+                is_active: is_active.unwrap_or(p.is_active),
+                ..p.clone()
+            };
+            let unchanged = next.name == p.name
+                && next.category == p.category
+                && next.unit_price == p.unit_price
+                && next.cost_price == p.cost_price
+                && next.spec == p.spec
+                && next.base_unit == p.base_unit
+                && next.sku == p.sku
+                && next.is_active == p.is_active;
+            if !unchanged {
+                if let Err(e) = conn.execute(
+                    "UPDATE products SET name = ?2, category = ?3, unit_price = ?4, cost_price = ?5, spec = ?6, base_unit = ?7, sku = ?8, \
+                     is_active = ?9, deleted_at = NULL, updated_at = ?10 WHERE id = ?1",
+                    params![
+                        p.id, next.name, next.category, next.unit_price, next.cost_price, next.spec, next.base_unit, next.sku,
+                        if next.is_active { 1i64 } else { 0i64 }, now
+                    ],
+                ) {
+                    report.action = String::from("error");
+                    report.errors.push(map_conflict(e));
+                    return Ok(report);
+                }
+                record_price(conn, &p.id, next.unit_price, now, Some("import"), None)?;
+            }
+            report.action = String::from(if unchanged { "skip" } else { "update" });
+            p.id.clone()
+        }
+        None => {
+            let id = cell("id").map(str::to_string).unwrap_or_else(|| Uuid::new_v4().to_string());
+            let category = match category {
+                Some(c) => c,
+                None => resolve_category(conn, "")?,
+            };
+            let base_unit = cell("base_unit").unwrap_or(crate::constants::DEFAULT_BASE_UNIT).to_string();
+            let unit_price = unit_price.unwrap_or_default();
+            if let Err(e) = conn.execute(
+                "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at, base_unit, sku) \
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, 0, 0, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    id, name, category, unit_price, cost_price.unwrap_or_default(), spec.unwrap_or(0.0),
+                    if is_active.unwrap_or(true) { 1i64 } else { 0i64 }, now, now, base_unit, sku
+                ],
+            ) {
+                report.action = String::from("error");
+                report.errors.push(map_conflict(e));
+                return Ok(report);
+            }
+            seed_product_stock(conn, &id, 0.0, 0.0, now)?;
+            record_price(conn, &id, unit_price, now, Some("import"), None)?;
+            report.action = String::from("create");
+            report.product_id = Some(id.clone());
+            id
+        }
+    };
+
+    for code in barcodes.iter() {
+        let owner: Option<String> = conn
+            .query_row(
+                "SELECT product_id FROM product_barcodes WHERE code = ?1 \
+                 UNION ALL SELECT id FROM products WHERE sku = ?1 AND id != ?2 LIMIT 1",
+                params![code, product_id],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("query barcode: {e}"))?;
+        match owner {
+            Some(o) if o == product_id => {}
+            Some(_) => {
+                report.action = String::from("error");
+                report.errors.push(format!("duplicate_code: {code}"));
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO product_barcodes(code, product_id, unit, created_at) VALUES(?1, ?2, NULL, ?3)",
+                    params![code, product_id, now],
+                )
+                .map_err(|e| format!("insert barcode: {e}"))?;
+                if report.action == "skip" {
+                    report.action = String::from("update");
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Runs the whole import inside one transaction. A dry run, or any row with
+/// errors, rolls everything back, so the report doubles as the preview.
+pub fn import_products(conn: &mut Connection, input: &ProductImportInput) -> Result<ProductImportReport, String> {
+    let format = input.format.trim().to_lowercase();
+    let table = read_table(&format, &input.content)?;
+    let (header, body) = table.split_first().ok_or_else(|| String::from("empty_file"))?;
+    let cols = resolve_columns(header, input.mapping.as_ref());
+    if !cols.contains_key("name") && !cols.contains_key("id") && !cols.contains_key("sku") {
+        return Err(String::from("missing_name_column"));
+    }
+    let dry_run = input.dry_run.unwrap_or(true);
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let mut rows = Vec::new();
+    for (i, cells) in body.iter().enumerate() {
+        if cells.iter().all(|c| c.is_empty()) {
+            continue;
+        }
+        let mut r = import_row(&tx, &cols, cells, now)?;
+        // Row numbers match the spreadsheet: the header is row 1.
+        r.row = i + 2;
+        rows.push(r);
+    }
+
+    let count = |action: &str| rows.iter().filter(|r| r.action == action).count();
+    let errors = count("error");
+    let applied = !dry_run && errors == 0;
+    if applied {
+        tx.commit().map_err(|e| format!("commit: {e}"))?;
+    }
+    Ok(ProductImportReport {
+        dry_run,
+        applied,
+        created: count("create"),
+        updated: count("update"),
+        skipped: count("skip"),
+        errors,
+        rows,
+    })
+}
+
+#[tauri::command]
+pub fn products_export(app: AppHandle, token: String, format: String) -> Result<tauri::ipc::Response, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    Ok(tauri::ipc::Response::new(export_products(&conn, format.trim())?))
+}
+
+#[tauri::command]
+pub fn products_import(app: AppHandle, input: ProductImportInput) -> Result<ProductImportReport, String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+    import_products(&mut conn, &input)
+}
@@ -0,0 +1,217 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::constants::DEFAULT_CATEGORY;
+use crate::models::{ProductCategoryRow, ProductCategoryUpsertInput, ProductCategoryMergeInput};
+
+// `products.category` keeps the category name; `product_categories` is the
+// list of valid names plus their display and accounting settings. Renames and
+// merges rewrite the products in the same transaction.
+
+/// The first active category, used when a product is saved without one.
+pub fn default_category(conn: &Connection) -> Result<String, String> {
+    let name: Option<String> = conn
+        .query_row(
+            "SELECT name FROM product_categories WHERE is_active = 1 ORDER BY sort_order ASC, name ASC LIMIT 1",
+            [],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("query default category: {e}"))?;
+    Ok(name.unwrap_or_else(|| DEFAULT_CATEGORY.to_string()))
+}
+
+/// Checks a category name typed for a product; an empty name means the
+/// default. Inactive categories take no new products.
+pub fn resolve_category(conn: &Connection, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return default_category(conn);
+    }
+    let active: Option<i64> = conn
+        .query_row("SELECT is_active FROM product_categories WHERE name = ?1", [name], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query category: {e}"))?;
+    match active {
+        None => Err(format!("category_not_found: {name}")),
+        Some(0) => Err(format!("category_inactive: {name}")),
+        Some(_) => Ok(name.to_string()),
+    }
+}
+
+/// The accounting item entries for a category are booked under: its
+/// `account_item`, or the category name when none is set.
+pub fn category_account_item(conn: &Connection, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    let item: Option<Option<String>> = conn
+        .query_row("SELECT account_item FROM product_categories WHERE name = ?1", [name], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query category: {e}"))?;
+    match item {
+        None => Err(format!("category_not_found: {name}")),
+        Some(item) => Ok(item.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| name.to_string())),
+    }
+}
+
+/// Adds a category seen in imported data if it is not known yet.
+pub fn ensure_category(conn: &Connection, name: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO product_categories(id, name, sort_order, is_active, created_at, updated_at) \
+         VALUES(?1, ?2, (SELECT COALESCE(MAX(sort_order), -1) + 1 FROM product_categories), 1, ?3, ?4)",
+        params![Uuid::new_v4().to_string(), name.trim(), now, now],
+    )
+    .map_err(|e| format!("insert product_categories: {e}"))?;
+    Ok(())
+}
+
+fn category_name(conn: &Connection, id: &str) -> Result<String, String> {
+    conn.query_row("SELECT name FROM product_categories WHERE id = ?1", [id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query category: {e}"))?
+        .ok_or_else(|| String::from("not_found"))
+}
+
+#[tauri::command]
+pub fn product_categories_list(app: AppHandle, token: String, include_inactive: Option<bool>) -> Result<Vec<ProductCategoryRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut sql = String::from(
+        "SELECT c.id, c.name, c.sort_order, c.is_active, c.color, c.icon, c.account_item, c.tax_rate, \
+                (SELECT COUNT(1) FROM products p WHERE p.category = c.name) \
+         FROM product_categories c",
+    );
+    if !include_inactive.unwrap_or(false) {
+        sql.push_str(" WHERE c.is_active = 1");
+    }
+    sql.push_str(" ORDER BY c.sort_order ASC, c.name ASC");
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(ProductCategoryRow {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                sort_order: r.get(2)?,
+                is_active: r.get::<_, i64>(3)? != 0,
+                color: r.get(4)?,
+                icon: r.get(5)?,
+                account_item: r.get(6)?,
+                tax_rate: r.get(7)?,
+                product_count: r.get(8)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Creates a category or updates its settings. The name of an existing
+/// category only changes through `product_category_rename`.
+#[tauri::command]
+pub fn product_category_upsert(app: AppHandle, input: ProductCategoryUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let tax_rate = input.tax_rate.unwrap_or(0.0);
+    if name.is_empty() || !tax_rate.is_finite() || !(0.0..=1.0).contains(&tax_rate) {
+        return Err(String::from("invalid"));
+    }
+    let color = input.color.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let icon = input.icon.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let account_item = input.account_item.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let sort_order = input.sort_order.unwrap_or(0);
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO product_categories(id, name, sort_order, is_active, color, icon, account_item, tax_rate, created_at, updated_at) \
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![id, name, sort_order, is_active, color, icon, account_item, tax_rate, now, now],
+        )
+        .map_err(|e| {
+            if e.to_string().contains("product_categories.name") { String::from("duplicate_category") } else { format!("insert product_categories: {e}") }
+        })?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE product_categories SET sort_order = ?2, is_active = ?3, color = ?4, icon = ?5, account_item = ?6, tax_rate = ?7, updated_at = ?8 WHERE id = ?1",
+            params![id, sort_order, is_active, color, icon, account_item, tax_rate, now],
+        )
+        .map_err(|e| format!("update product_categories: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn product_category_rename(app: AppHandle, token: String, id: String, name: String) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    let now = now_ts()?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let old_name = category_name(&tx, id.trim())?;
+    if old_name == name {
+        return Ok(());
+    }
+    tx.execute(
+        "UPDATE product_categories SET name = ?2, updated_at = ?3 WHERE id = ?1",
+        params![id.trim(), name, now],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("product_categories.name") { String::from("duplicate_category") } else { format!("update product_categories: {e}") }
+    })?;
+    tx.execute(
+        "UPDATE products SET category = ?2, updated_at = ?3 WHERE category = ?1",
+        params![old_name, name, now],
+    )
+    .map_err(|e| format!("update products: {e}"))?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+/// Moves every product of `source_id` into `target_id` and drops the source.
+#[tauri::command]
+pub fn product_category_merge(app: AppHandle, input: ProductCategoryMergeInput) -> Result<usize, String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let source_id = input.source_id.trim().to_string();
+    let target_id = input.target_id.trim().to_string();
+    if source_id.is_empty() || target_id.is_empty() || source_id == target_id {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let source = category_name(&tx, &source_id)?;
+    let target = category_name(&tx, &target_id)?;
+    let moved = tx
+        .execute(
+            "UPDATE products SET category = ?2, updated_at = ?3 WHERE category = ?1",
+            params![source, target, now],
+        )
+        .map_err(|e| format!("update products: {e}"))?;
+    tx.execute("DELETE FROM product_categories WHERE id = ?1", [&source_id])
+        .map_err(|e| format!("delete product_categories: {e}"))?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(moved)
+}
@@ -12,8 +12,15 @@ pub fn db_replace_from_cloud(app: AppHandle, input: DbReplaceFromCloudInput) ->
     let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
 
     tx.execute_batch(
-        "DELETE FROM products;\
-         DELETE FROM sales_items;\
+        "DELETE FROM sales_items;\
+         DELETE FROM cart_items;\
+         DELETE FROM carts;\
+         DELETE FROM seat_order_items;\
+         DELETE FROM seat_orders;\
+         DELETE FROM products;\
+         DELETE FROM product_stock;\
+         DELETE FROM product_prices;\
+         DELETE FROM payments;\
          DELETE FROM sales_orders;\
          DELETE FROM accounting_entries;\
          DELETE FROM meituan_orders;\
@@ -92,8 +99,8 @@ pub fn db_replace_from_cloud(app: AppHandle, input: DbReplaceFromCloudInput) ->
     {
         let mut stmt = tx
             .prepare(
-                "INSERT INTO sales_items(id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at)\
-                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+                "INSERT INTO sales_items(id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at, product_id)\
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, (SELECT id FROM products WHERE id = ?19))",
             )
             .map_err(|e| format!("prepare sales_items: {e}"))?;
         for it in input.sales_items.iter() {
@@ -101,6 +108,7 @@ pub fn db_replace_from_cloud(app: AppHandle, input: DbReplaceFromCloudInput) ->
                 it.id, it.order_id, it.product_name, it.original, it.restock, it.remaining,
                 it.redeem, it.redeem_mode, it.loss, it.purchase, it.stock_prev, it.stock,
                 it.sales, it.revenue, it.unit_price, it.cost_price, it.spec, it.created_at,
+                it.product_id,
             ])
             .map_err(|e| format!("insert sales_items: {e}"))?;
         }
@@ -1,26 +1,356 @@
 use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
 use uuid::Uuid;
-use crate::db::open_db;
+use crate::db::{open_db, now_ts, now_ymd};
 use crate::state::{auth_resolve_account_id, require_admin};
-use crate::models::{CouponRow, CouponUseInput, CouponCreateInput};
+use crate::constants::{COUPON_BATCH_MAX, COUPON_CODE_ALPHABET, COUPON_CODE_LEN, COUPON_STATUSES};
+use crate::models::*;
+use crate::money::Money;
 
+// Store coupons are fixed-value vouchers with a printable code. A coupon can
+// be redeemed `max_uses` times (once by default) inside its validity window,
+// at most once per order; every redemption is a row in coupon_redemptions
+// bound to the order it was taken off. At checkout the coupon comes off the
+// order total before rounding, the same way 抹零 does, so lines keep their
+// list and promotion prices. A refund does not give the coupon back.
+
+const COUPON_COLUMNS: &str =
+    "id, code, name, face_value, min_spend, valid_from, valid_until, max_uses, use_count, status, batch_id, created_at, used_at";
+
+fn coupon_from_row(r: &rusqlite::Row) -> rusqlite::Result<CouponRow> {
+    Ok(CouponRow {
+        id: r.get(0)?,
+        code: r.get(1)?,
+        name: r.get(2)?,
+        face_value: r.get(3)?,
+        min_spend: r.get(4)?,
+        valid_from: r.get(5)?,
+        valid_until: r.get(6)?,
+        max_uses: r.get(7)?,
+        use_count: r.get(8)?,
+        status: r.get(9)?,
+        batch_id: r.get(10)?,
+        created_at: r.get(11)?,
+        used_at: r.get(12)?,
+    })
+}
+
+/// Codes are matched without case, spaces or dashes, as they get typed from paper.
+pub fn normalize_coupon_code(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+fn generate_code(prefix: &str) -> String {
+    let mut n = Uuid::new_v4().as_u128();
+    let base = COUPON_CODE_ALPHABET.len() as u128;
+    let mut code = String::from(prefix);
+    for _ in 0..COUPON_CODE_LEN {
+        code.push(COUPON_CODE_ALPHABET[(n % base) as usize] as char);
+        n /= base;
+    }
+    code
+}
+
+pub fn load_coupon_by_code(conn: &Connection, code: &str) -> Result<CouponRow, String> {
+    conn.query_row(
+        &format!("SELECT {COUPON_COLUMNS} FROM coupons WHERE code = ?1"),
+        [normalize_coupon_code(code)],
+        coupon_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("query coupon: {e}"))?
+    .ok_or_else(|| String::from("coupon_not_found"))
+}
+
+/// Why `coupon` can't be taken off an order of `amount` at `now`, if it can't.
+fn coupon_unusable(coupon: &CouponRow, amount: Money, now: i64) -> Option<String> {
+    if coupon.status == "void" {
+        return Some(String::from("coupon_void"));
+    }
+    if coupon.status == "used" || coupon.use_count >= coupon.max_uses {
+        return Some(String::from("coupon_used"));
+    }
+    if coupon.valid_from.is_some_and(|t| now < t) {
+        return Some(String::from("coupon_not_started"));
+    }
+    if coupon.valid_until.is_some_and(|t| now >= t) {
+        return Some(String::from("coupon_expired"));
+    }
+    if amount < coupon.min_spend {
+        return Some(format!("coupon_min_spend: {}", coupon.min_spend));
+    }
+    None
+}
+
+/// Redeems the coupon `code` against `order_id`, an order of `amount`, and
+/// returns the discount: its face value, but never more than the order. Taking
+/// a use and checking there is one left are a single update, so a code used at
+/// two tills at once only works at one.
+pub fn redeem_coupon(
+    conn: &Connection,
+    code: &str,
+    order_id: &str,
+    amount: Money,
+    date_ymd: &str,
+    actor_id: &str,
+    now: i64,
+) -> Result<Money, String> {
+    let coupon = load_coupon_by_code(conn, code)?;
+    if let Some(e) = coupon_unusable(&coupon, amount, now) {
+        return Err(e);
+    }
+    let n = conn
+        .execute(
+            "UPDATE coupons SET use_count = use_count + 1, \
+                    status = CASE WHEN use_count + 1 >= max_uses THEN 'used' ELSE status END, \
+                    used_at = ?2, updated_at = ?2 \
+             WHERE id = ?1 AND status = 'active' AND use_count < max_uses",
+            params![coupon.id, now],
+        )
+        .map_err(|e| format!("update coupon: {e}"))?;
+    if n == 0 {
+        return Err(String::from("coupon_used"));
+    }
+    let discount = coupon.face_value.min(amount);
+    conn.execute(
+        "INSERT INTO coupon_redemptions(id, coupon_id, order_id, discount, date_ymd, actor_id, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![Uuid::new_v4().to_string(), coupon.id, order_id, discount, date_ymd, actor_id, now],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("UNIQUE") { String::from("coupon_used") } else { format!("insert coupon redemption: {e}") }
+    })?;
+    Ok(discount)
+}
+
+/// Checks the terms shared by single coupons and batches.
+fn check_terms(name: &str, face_value: Money, min_spend: Money, valid_from: Option<i64>, valid_until: Option<i64>, max_uses: i64) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if face_value <= Money::ZERO || min_spend < Money::ZERO || max_uses < 1 {
+        return Err(String::from("invalid"));
+    }
+    if let (Some(from), Some(until)) = (valid_from, valid_until) {
+        if until <= from {
+            return Err(String::from("invalid"));
+        }
+    }
+    Ok(())
+}
+
+/// Coupons, newest first, optionally of one status or batch.
 #[tauri::command]
-pub fn coupons_list(app: AppHandle, token: String) -> Result<Vec<CouponRow>, String> {
-    let _conn = open_db(&app)?;
+pub fn coupons_list(app: AppHandle, token: String, status: Option<String>, batch_id: Option<String>) -> Result<Vec<CouponRow>, String> {
+    let conn = open_db(&app)?;
     let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
-    Ok(Vec::new())
+    let status = status.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    if status.as_deref().is_some_and(|s| !COUPON_STATUSES.contains(&s)) {
+        return Err(String::from("invalid"));
+    }
+    let batch_id = batch_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {COUPON_COLUMNS} FROM coupons WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR batch_id = ?2) \
+             ORDER BY created_at DESC, code ASC"
+        ))
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt.query_map(params![status, batch_id], coupon_from_row).map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
 }
 
+/// Looks a code up at the till, before checkout, so the cashier can tell the
+/// customer what it is worth.
+#[tauri::command]
+pub fn coupon_lookup(app: AppHandle, token: String, code: String) -> Result<CouponRow, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_coupon_by_code(&conn, &code)
+}
+
+/// Redeems a coupon against an order rung up elsewhere, for its full face
+/// value. Sales through `pos_checkout` take the coupon as `coupon_code` instead.
 #[tauri::command]
 pub fn coupon_use(app: AppHandle, input: CouponUseInput) -> Result<bool, String> {
-    let _conn = open_db(&app)?;
-    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let order_id = input.order_id.trim();
+    if input.code.trim().is_empty() || order_id.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let now = now_ts()?;
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    // The order's amount isn't known here, so its minimum spend counts as met.
+    let coupon = load_coupon_by_code(&tx, &input.code)?;
+    redeem_coupon(&tx, &coupon.code, order_id, coupon.face_value.max(coupon.min_spend), &now_ymd()?, &actor_id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
     Ok(true)
 }
 
 #[tauri::command]
 pub fn coupon_create(app: AppHandle, input: CouponCreateInput) -> Result<String, String> {
     let conn = open_db(&app)?;
-    let _ = require_admin(&conn, input.token.trim())?;
-    Ok(format!("coupon_{}", Uuid::new_v4().to_string()))
+    let actor_id = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let min_spend = input.min_spend.unwrap_or(Money::ZERO);
+    let max_uses = input.max_uses.unwrap_or(1);
+    check_terms(&name, input.face_value, min_spend, input.valid_from, input.valid_until, max_uses)?;
+    let code = match normalize_coupon_code(&input.code) {
+        c if c.is_empty() => generate_code(""),
+        c => c,
+    };
+    let now = now_ts()?;
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO coupons(id, code, name, face_value, min_spend, valid_from, valid_until, max_uses, created_by, created_at, updated_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![id, code, name, input.face_value, min_spend, input.valid_from, input.valid_until, max_uses, actor_id, now, now],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("coupons.code") { String::from("duplicate_code") } else { format!("insert coupon: {e}") }
+    })?;
+    Ok(id)
+}
+
+/// Generates `count` coupons with the same terms and unique random codes, to
+/// print and hand out.
+#[tauri::command]
+pub fn coupon_batch_create(app: AppHandle, input: CouponBatchInput) -> Result<CouponBatch, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let min_spend = input.min_spend.unwrap_or(Money::ZERO);
+    let max_uses = input.max_uses.unwrap_or(1);
+    check_terms(&name, input.face_value, min_spend, input.valid_from, input.valid_until, max_uses)?;
+    if !(1..=COUPON_BATCH_MAX).contains(&input.count) {
+        return Err(String::from("invalid"));
+    }
+    let prefix = normalize_coupon_code(input.prefix.as_deref().unwrap_or(""));
+
+    let now = now_ts()?;
+    let batch_id = Uuid::new_v4().to_string();
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let mut codes = Vec::new();
+    while codes.len() < input.count as usize {
+        let code = generate_code(&prefix);
+        // A clash with an existing code just draws again.
+        let n = tx
+            .execute(
+                "INSERT OR IGNORE INTO coupons(id, code, name, face_value, min_spend, valid_from, valid_until, max_uses, batch_id, created_by, created_at, updated_at) \
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![Uuid::new_v4().to_string(), code, name, input.face_value, min_spend, input.valid_from, input.valid_until, max_uses, batch_id, actor_id, now, now],
+            )
+            .map_err(|e| format!("insert coupon: {e}"))?;
+        if n == 1 {
+            codes.push(code);
+        }
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(CouponBatch { batch_id, codes })
+}
+
+/// Withdraws a coupon that has not been used up; it can't be redeemed after.
+#[tauri::command]
+pub fn coupon_void(app: AppHandle, token: String, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    let n = conn
+        .execute(
+            "UPDATE coupons SET status = 'void', updated_at = ?2 WHERE id = ?1 AND status = 'active'",
+            params![id.trim(), now_ts()?],
+        )
+        .map_err(|e| format!("update coupon: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(())
+}
+
+/// Coupons issued, used and expired per batch, and the discount they gave
+/// between `date_from` and `date_to` (YYYY-MM-DD, inclusive).
+#[tauri::command]
+pub fn coupon_usage_report(app: AppHandle, token: String, date_from: String, date_to: String) -> Result<CouponUsageReport, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let (date_from, date_to) = (date_from.trim().to_string(), date_to.trim().to_string());
+    if date_from.is_empty() || date_to.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let now = now_ts()?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.batch_id, c.name, COUNT(1), \
+                    SUM(CASE WHEN c.use_count > 0 THEN 1 ELSE 0 END), \
+                    SUM(CASE WHEN c.status = 'void' THEN 1 ELSE 0 END), \
+                    SUM(CASE WHEN c.status = 'active' AND c.valid_until <= ?3 THEN 1 ELSE 0 END), \
+                    COALESCE(SUM(r.n), 0), COALESCE(SUM(r.discount), 0) \
+             FROM coupons c \
+             LEFT JOIN (SELECT coupon_id, COUNT(1) AS n, SUM(discount) AS discount FROM coupon_redemptions \
+                        WHERE date_ymd >= ?1 AND date_ymd <= ?2 GROUP BY coupon_id) r ON r.coupon_id = c.id \
+             GROUP BY COALESCE(c.batch_id, c.id), c.name \
+             HAVING COALESCE(SUM(r.n), 0) > 0 OR MAX(date(c.created_at, 'unixepoch', 'localtime')) BETWEEN ?1 AND ?2 \
+             ORDER BY MIN(c.created_at) DESC",
+        )
+        .map_err(|e| format!("prepare coupon stats: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_from, date_to, now], |r| {
+            Ok(CouponUsageStat {
+                batch_id: r.get(0)?,
+                name: r.get(1)?,
+                issued: r.get(2)?,
+                used: r.get(3)?,
+                voided: r.get(4)?,
+                expired: r.get(5)?,
+                redemptions: r.get(6)?,
+                discount: r.get(7)?,
+            })
+        })
+        .map_err(|e| format!("query coupon stats: {e}"))?;
+    let mut stats = Vec::new();
+    for r in rows {
+        stats.push(r.map_err(|e| format!("row coupon stats: {e}"))?);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.id, r.coupon_id, c.code, c.name, r.order_id, r.discount, r.date_ymd, r.actor_id, r.created_at \
+             FROM coupon_redemptions r JOIN coupons c ON c.id = r.coupon_id \
+             WHERE r.date_ymd >= ?1 AND r.date_ymd <= ?2 ORDER BY r.created_at DESC",
+        )
+        .map_err(|e| format!("prepare coupon redemptions: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_from, date_to], |r| {
+            Ok(CouponRedemptionRow {
+                id: r.get(0)?,
+                coupon_id: r.get(1)?,
+                code: r.get(2)?,
+                name: r.get(3)?,
+                order_id: r.get(4)?,
+                discount: r.get(5)?,
+                date_ymd: r.get(6)?,
+                actor_id: r.get(7)?,
+                created_at: r.get(8)?,
+            })
+        })
+        .map_err(|e| format!("query coupon redemptions: {e}"))?;
+    let mut details = Vec::new();
+    for r in rows {
+        details.push(r.map_err(|e| format!("row coupon redemptions: {e}"))?);
+    }
+
+    Ok(CouponUsageReport {
+        redemptions: details.len() as i64,
+        discount: details.iter().map(|d| d.discount).sum(),
+        date_from,
+        date_to,
+        stats,
+        details,
+    })
 }
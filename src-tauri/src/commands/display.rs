@@ -0,0 +1,297 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, display_channels, display_pair_failures, log_to_system};
+use crate::constants::{DISPLAY_PAIR_CODE_TTL_SECS, DISPLAY_PAIR_FAILURE_WINDOW_SECS, DISPLAY_PAIR_MAX_FAILURES};
+use crate::models::*;
+use crate::commands::carts::{load_cart, price_cart_lines};
+use crate::commands::receipts::load_receipt;
+use crate::commands::receipt_links::create_receipt_link;
+use crate::http_server::lan_base_url;
+use crate::money::Money;
+
+// A customer display is a second screen (monitor or tablet) that follows one
+// till. The till starts a pairing and shows a six-digit code; the display page
+// at /display trades the code for a key and streams /api/display/stream. The
+// feed shows the till's open cart as it is rung up (or the cart the till keeps
+// itself, pushed with `display_cart_push`) and, after checkout, the total with
+// a QR code for the electronic receipt.
+
+const DISPLAY_CHANNEL_CAPACITY: usize = 16;
+
+fn display_sender(terminal: &str) -> Option<broadcast::Sender<String>> {
+    let mut channels = display_channels().lock().ok()?;
+    Some(
+        channels
+            .entry(terminal.to_string())
+            .or_insert_with(|| broadcast::channel(DISPLAY_CHANNEL_CAPACITY).0)
+            .clone(),
+    )
+}
+
+/// Follows a till's feed; each message is a JSON `DisplayMessage`.
+pub fn subscribe_display(terminal: &str) -> Option<broadcast::Receiver<String>> {
+    display_sender(terminal).map(|tx| tx.subscribe())
+}
+
+fn has_viewers(terminal: &str) -> bool {
+    display_channels()
+        .lock()
+        .ok()
+        .and_then(|c| c.get(terminal).map(|tx| tx.receiver_count() > 0))
+        .unwrap_or(false)
+}
+
+fn publish(terminal: &str, message: &DisplayMessage) {
+    if let (Some(tx), Ok(json)) = (display_sender(terminal), serde_json::to_string(message)) {
+        // Nobody watching is not an error.
+        let _ = tx.send(json);
+    }
+}
+
+/// What `terminal`'s display shows right now: its open cart, or idle.
+pub fn display_snapshot(conn: &Connection, terminal: &str, now: i64) -> Result<DisplayMessage, String> {
+    let cart_id: Option<String> = conn
+        .query_row(
+            "SELECT id FROM carts WHERE terminal = ?1 AND status = 'open' ORDER BY updated_at DESC LIMIT 1",
+            [terminal],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("query cart: {e}"))?;
+    let cart = match cart_id {
+        Some(id) => Some(load_cart(conn, &id, now)?),
+        None => None,
+    };
+    Ok(DisplayMessage {
+        kind: String::from(if cart.is_some() { "cart" } else { "idle" }),
+        total: cart.as_ref().map(|c| c.total),
+        cart,
+        receipt_url: None,
+        qr_svg: None,
+    })
+}
+
+/// Pushes the till's current cart to its displays. A display that can't be
+/// updated must never fail the sale, so errors are only logged.
+pub fn refresh_display(conn: &Connection, terminal: Option<&str>, now: i64) {
+    let Some(terminal) = terminal.filter(|t| has_viewers(t)) else {
+        return;
+    };
+    match display_snapshot(conn, terminal, now) {
+        Ok(message) => publish(terminal, &message),
+        Err(e) => log_to_system("warn", "display", &format!("顾客屏刷新失败: {terminal}"), Some(&e)),
+    }
+}
+
+/// Shows the amount just paid and a QR code for the electronic receipt.
+pub fn show_paid(conn: &Connection, terminal: Option<&str>, order_id: &str, now: i64) {
+    let Some(terminal) = terminal.filter(|t| has_viewers(t)) else {
+        return;
+    };
+    let total = match load_receipt(conn, order_id) {
+        Ok(receipt) => receipt.total,
+        Err(e) => {
+            log_to_system("warn", "display", &format!("顾客屏刷新失败: {terminal}"), Some(&e));
+            return;
+        }
+    };
+    let link = create_receipt_link(conn, order_id, now)
+        .map_err(|e| log_to_system("warn", "display", &format!("电子小票链接生成失败: {order_id}"), Some(&e)))
+        .ok();
+    publish(
+        terminal,
+        &DisplayMessage {
+            kind: String::from("paid"),
+            cart: None,
+            total: Some(total),
+            receipt_url: link.as_ref().map(|l| l.url.clone()),
+            qr_svg: link.map(|l| l.qr_svg),
+        },
+    );
+}
+
+/// Trades a pairing code for the key the display streams with. Anyone on the
+/// LAN can try codes, so once DISPLAY_PAIR_MAX_FAILURES wrong ones came in
+/// within the window every attempt is refused until the window has passed.
+pub fn pair_display(conn: &Connection, code: &str, now: i64) -> Result<DisplayPaired, String> {
+    let mut failures = display_pair_failures().lock().map_err(|_| String::from("display pairing unavailable"))?;
+    failures.retain(|t| *t > now - DISPLAY_PAIR_FAILURE_WINDOW_SECS);
+    if failures.len() >= DISPLAY_PAIR_MAX_FAILURES {
+        return Err(String::from("too_many_attempts"));
+    }
+    let found: Option<(String, String)> = conn
+        .query_row(
+            "SELECT id, terminal FROM customer_displays WHERE pair_code = ?1 AND pair_expires_at > ?2 AND display_key IS NULL",
+            params![code.trim(), now],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("query display: {e}"))?;
+    let Some((id, terminal)) = found else {
+        failures.push(now);
+        return Err(String::from("invalid_code"));
+    };
+    let key = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    conn.execute(
+        "UPDATE customer_displays SET display_key = ?2, pair_code = NULL, pair_expires_at = NULL, paired_at = ?3, last_seen_at = ?3 WHERE id = ?1",
+        params![id, key, now],
+    )
+    .map_err(|e| format!("update display: {e}"))?;
+    Ok(DisplayPaired { key, terminal })
+}
+
+/// Whether `key` still belongs to a paired display. An open stream checks it
+/// before every message, so an unpaired display stops receiving.
+pub fn display_key_valid(conn: &Connection, key: &str) -> bool {
+    conn.query_row("SELECT EXISTS(SELECT 1 FROM customer_displays WHERE display_key = ?1)", [key.trim()], |r| r.get(0))
+        .unwrap_or(false)
+}
+
+/// The till a paired display follows.
+pub fn display_terminal(conn: &Connection, key: &str, now: i64) -> Result<String, String> {
+    let terminal: String = conn
+        .query_row("SELECT terminal FROM customer_displays WHERE display_key = ?1", [key.trim()], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query display: {e}"))?
+        .ok_or_else(|| String::from("unauthorized"))?;
+    conn.execute("UPDATE customer_displays SET last_seen_at = ?2 WHERE display_key = ?1", params![key.trim(), now])
+        .map_err(|e| format!("update display: {e}"))?;
+    Ok(terminal)
+}
+
+/// Starts pairing a display with `terminal` and returns the code to enter on it.
+#[tauri::command]
+pub fn display_pair_start(app: AppHandle, token: String, terminal: String) -> Result<DisplayPairing, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let terminal = terminal.trim().to_string();
+    if terminal.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    // Codes only need to be unique among the ones still waiting to be used.
+    let mut code = String::new();
+    for _ in 0..10 {
+        let candidate = format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000);
+        let taken: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM customer_displays WHERE pair_code = ?1 AND pair_expires_at > ?2)",
+                params![candidate, now],
+                |r| r.get(0),
+            )
+            .map_err(|e| format!("query display: {e}"))?;
+        if !taken {
+            code = candidate;
+            break;
+        }
+    }
+    if code.is_empty() {
+        return Err(String::from("pair code unavailable"));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let expires_at = now + DISPLAY_PAIR_CODE_TTL_SECS;
+    conn.execute(
+        "INSERT INTO customer_displays(id, terminal, pair_code, pair_expires_at, created_at) VALUES(?1, ?2, ?3, ?4, ?5)",
+        params![id, terminal, code, expires_at, now],
+    )
+    .map_err(|e| format!("insert display: {e}"))?;
+    Ok(DisplayPairing { id, terminal, code, expires_at, url: format!("{}/display", lan_base_url()) })
+}
+
+#[tauri::command]
+pub fn displays_list(app: AppHandle, token: String) -> Result<Vec<DisplayRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, terminal, display_key IS NOT NULL, paired_at, last_seen_at, created_at FROM customer_displays \
+             WHERE display_key IS NOT NULL OR pair_expires_at > ?1 ORDER BY terminal ASC, created_at ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([now_ts()?], |r| {
+            Ok(DisplayRow {
+                id: r.get(0)?,
+                terminal: r.get(1)?,
+                paired: r.get(2)?,
+                paired_at: r.get(3)?,
+                last_seen_at: r.get(4)?,
+                created_at: r.get(5)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Forgets a display; it has to be paired again to show anything.
+#[tauri::command]
+pub fn display_unpair(app: AppHandle, token: String, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let terminal: String = conn
+        .query_row("SELECT terminal FROM customer_displays WHERE id = ?1", [id.trim()], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query display: {e}"))?
+        .ok_or_else(|| String::from("not_found"))?;
+    conn.execute("DELETE FROM customer_displays WHERE id = ?1", [id.trim()])
+        .map_err(|e| format!("delete display: {e}"))?;
+    // Send the till's feed a message so the stream of the display just
+    // unpaired finds its key gone and closes now rather than on the next sale.
+    refresh_display(&conn, Some(&terminal), now_ts()?);
+    Ok(())
+}
+
+/// Shows a cart the till keeps itself, priced as checkout would price it, on
+/// the till's displays. The till sends it after every change; an empty cart
+/// shows the idle screen.
+#[tauri::command]
+pub fn display_cart_push(app: AppHandle, input: DisplayCartPushInput) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let terminal = input.terminal.trim();
+    if terminal.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if !has_viewers(terminal) {
+        return Ok(());
+    }
+    if input.items.is_empty() {
+        publish(terminal, &DisplayMessage { kind: String::from("idle"), cart: None, total: None, receipt_url: None, qr_svg: None });
+        return Ok(());
+    }
+    let mut cart = CartView {
+        id: String::new(),
+        label: None,
+        status: String::from("open"),
+        terminal: Some(terminal.to_string()),
+        location_id: None,
+        order_id: None,
+        lines: Vec::new(),
+        subtotal: Money::ZERO,
+        discount: Money::ZERO,
+        total: Money::ZERO,
+        created_at: now,
+        updated_at: now,
+        expires_at: now,
+    };
+    let items = input.items.into_iter().enumerate().map(|(i, it)| (i.to_string(), it)).collect();
+    price_cart_lines(&conn, &mut cart, items, now)?;
+    publish(
+        terminal,
+        &DisplayMessage { kind: String::from("cart"), total: Some(cart.total), cart: Some(cart), receipt_url: None, qr_svg: None },
+    );
+    Ok(())
+}
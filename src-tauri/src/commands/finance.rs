@@ -1,24 +1,33 @@
 use tauri::AppHandle;
 use crate::db::open_db;
 use crate::state::{auth_resolve_account_id};
-use crate::models::{DailyStat, FinanceDailyReport, FinanceEntry, FinanceWeeklyReport, FinanceMonthlyReport, CategoryStat, DividendReport, ShareholderDividend};
+use crate::models::{DailyStat, FinanceDailyReport, FinanceEntry, FinanceWeeklyReport, FinanceMonthlyReport, CategoryStat, DividendReport, ShareholderDividend, SalesCategoryStat};
+use crate::commands::members::{member_liability_at, member_topups_between};
+use crate::commands::loyalty::{points_outstanding_at, points_value};
+use crate::money::Money;
 
 #[tauri::command]
 pub fn finance_daily_report(app: AppHandle, token: String, date_ymd: String) -> Result<FinanceDailyReport, String> {
     let conn = open_db(&app)?;
     let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
 
-    let income: f64 = conn.query_row(
+    let income: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd = ?1 AND entry_type = 'income'",
         [date_ymd.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
 
-    let expense: f64 = conn.query_row(
+    let expense: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd = ?1 AND entry_type = 'expense'",
         [date_ymd.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
+
+    let refunds: Money = conn.query_row(
+        "SELECT COALESCE(-SUM(total_revenue), 0) FROM sales_orders WHERE date_ymd = ?1 AND kind != 'sale'",
+        [date_ymd.clone()],
+        |r| r.get(0)
+    ).unwrap_or_default();
 
     let shift_count: i64 = conn.query_row(
         "SELECT COUNT(1) FROM shift_records WHERE date_ymd = ?1",
@@ -34,18 +43,30 @@ pub fn finance_daily_report(app: AppHandle, token: String, date_ymd: String) ->
     while let Some(r) = rows.next().map_err(|e| format!("next: {e}"))? {
         details.push(FinanceEntry {
             item: r.get(0).unwrap_or_default(),
-            amount: r.get(1).unwrap_or(0.0),
+            amount: r.get(1).unwrap_or_default(),
             type_: r.get(2).unwrap_or_default(),
         });
     }
 
+    // Top-ups are owed to members until spent, so they sit beside income, not in it.
+    let member_topups = member_topups_between(&conn, &date_ymd, &date_ymd)?;
+    let member_liability = member_liability_at(&conn, &date_ymd)?;
+    // Points owed are a liability too.
+    let points_outstanding = points_outstanding_at(&conn, &date_ymd)?;
+    let points_liability = points_value(&conn, points_outstanding)?;
+
     Ok(FinanceDailyReport {
         date_ymd,
         total_income: income,
         total_expense: expense,
         net_profit: income - expense,
+        refunds,
         shift_count,
         details,
+        member_topups,
+        member_liability,
+        points_outstanding,
+        points_liability,
     })
 }
 
@@ -54,8 +75,8 @@ pub fn finance_weekly_report(app: AppHandle, token: String, week_start: String)
     let conn = open_db(&app)?;
     let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
 
-    let mut total_income = 0.0;
-    let mut total_expense = 0.0;
+    let mut total_income = Money::ZERO;
+    let mut total_expense = Money::ZERO;
     let mut daily_stats = Vec::new();
 
     let parts: Vec<&str> = week_start.split('-').collect();
@@ -83,17 +104,17 @@ pub fn finance_weekly_report(app: AppHandle, token: String, week_start: String)
 
         let date_ymd = format!("{:04}-{:02}-{:02}", current_year, current_month, current_day);
 
-        let income: f64 = conn.query_row(
+        let income: Money = conn.query_row(
             "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd = ?1 AND entry_type = 'income'",
             [date_ymd.clone()],
             |r| r.get(0)
-        ).unwrap_or(0.0);
+        ).unwrap_or_default();
 
-        let expense: f64 = conn.query_row(
+        let expense: Money = conn.query_row(
             "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd = ?1 AND entry_type = 'expense'",
             [date_ymd.clone()],
             |r| r.get(0)
-        ).unwrap_or(0.0);
+        ).unwrap_or_default();
 
         total_income += income;
         total_expense += expense;
@@ -118,13 +139,29 @@ pub fn finance_weekly_report(app: AppHandle, token: String, week_start: String)
     }
     let week_end = format!("{:04}-{:02}-{:02}", end_year, end_month, end_day);
 
+    let refunds: Money = conn.query_row(
+        "SELECT COALESCE(-SUM(total_revenue), 0) FROM sales_orders WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND kind != 'sale'",
+        [week_start.clone(), week_end.clone()],
+        |r| r.get(0)
+    ).unwrap_or_default();
+
+    let member_topups = member_topups_between(&conn, &week_start, &week_end)?;
+    let member_liability = member_liability_at(&conn, &week_end)?;
+    let points_outstanding = points_outstanding_at(&conn, &week_end)?;
+    let points_liability = points_value(&conn, points_outstanding)?;
+
     Ok(FinanceWeeklyReport {
         week_start,
         week_end,
         total_income,
         total_expense,
         net_profit: total_income - total_expense,
+        refunds,
         daily_stats,
+        member_topups,
+        member_liability,
+        points_outstanding,
+        points_liability,
     })
 }
 
@@ -136,17 +173,23 @@ pub fn finance_monthly_report(app: AppHandle, token: String, month: String) -> R
     let month_start = format!("{}-01", month);
     let month_end = format!("{}-31", month);
 
-    let total_income: f64 = conn.query_row(
+    let total_income: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND entry_type = 'income'",
         [month_start.clone(), month_end.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
 
-    let total_expense: f64 = conn.query_row(
+    let total_expense: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND entry_type = 'expense'",
         [month_start.clone(), month_end.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
+
+    let refunds: Money = conn.query_row(
+        "SELECT COALESCE(-SUM(total_revenue), 0) FROM sales_orders WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND kind != 'sale'",
+        [month_start.clone(), month_end.clone()],
+        |r| r.get(0)
+    ).unwrap_or_default();
 
     let mut daily_stats = Vec::new();
     let mut stmt = conn.prepare(
@@ -157,8 +200,8 @@ pub fn finance_monthly_report(app: AppHandle, token: String, month: String) -> R
     while let Some(r) = rows.next().map_err(|e| format!("next: {e}"))? {
         daily_stats.push(DailyStat {
             date_ymd: r.get(0).unwrap_or_default(),
-            income: r.get(1).unwrap_or(0.0),
-            expense: r.get(2).unwrap_or(0.0),
+            income: r.get(1).unwrap_or_default(),
+            expense: r.get(2).unwrap_or_default(),
         });
     }
 
@@ -169,9 +212,9 @@ pub fn finance_monthly_report(app: AppHandle, token: String, month: String) -> R
     ).map_err(|e| format!("prepare: {e}"))?;
     let mut rows = stmt.query([month_start.clone(), month_end.clone()]).map_err(|e| format!("query: {e}"))?;
     while let Some(r) = rows.next().map_err(|e| format!("next: {e}"))? {
-        let income = r.get(1).unwrap_or(0.0);
-        let expense = r.get(2).unwrap_or(0.0);
-        if income > 0.0 || expense > 0.0 {
+        let income: Money = r.get(1).unwrap_or_default();
+        let expense: Money = r.get(2).unwrap_or_default();
+        if income > Money::ZERO || expense > Money::ZERO {
             category_stats.push(CategoryStat {
                 category: r.get(0).unwrap_or_default(),
                 income,
@@ -180,13 +223,23 @@ pub fn finance_monthly_report(app: AppHandle, token: String, month: String) -> R
         }
     }
 
+    let member_topups = member_topups_between(&conn, &month_start, &month_end)?;
+    let member_liability = member_liability_at(&conn, &month_end)?;
+    let points_outstanding = points_outstanding_at(&conn, &month_end)?;
+    let points_liability = points_value(&conn, points_outstanding)?;
+
     Ok(FinanceMonthlyReport {
         month,
         total_income,
         total_expense,
         net_profit: total_income - total_expense,
+        refunds,
         daily_stats,
         category_stats,
+        member_topups,
+        member_liability,
+        points_outstanding,
+        points_liability,
     })
 }
 
@@ -199,17 +252,17 @@ pub fn finance_dividend_report(app: AppHandle, token: String, month: String) ->
     let month_start = format!("{}-01", month);
     let month_end = format!("{}-31", month);
 
-    let total_income: f64 = conn.query_row(
+    let total_income: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND entry_type = 'income'",
         [month_start.clone(), month_end.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
 
-    let total_expense: f64 = conn.query_row(
+    let total_expense: Money = conn.query_row(
         "SELECT COALESCE(SUM(amount), 0) FROM accounting_entries WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND entry_type = 'expense'",
         [month_start.clone(), month_end.clone()],
         |r| r.get(0)
-    ).unwrap_or(0.0);
+    ).unwrap_or_default();
 
     let total_profit = total_income - total_expense;
 
@@ -231,17 +284,26 @@ pub fn finance_dividend_report(app: AppHandle, token: String, month: String) ->
         ))
     }).map_err(|e| format!("query: {e}"))?;
 
+    let mut holders = Vec::new();
     for r in rows {
         let (name, equity, pick_name, proxy_host) = r.map_err(|e| format!("row: {e}"))?;
         let equity_safe = if equity.is_finite() && equity > 0.0 { equity } else { 0.0 };
-        
-        // Correct calculation: equity is percentage (e.g. 25.0 means 25%)
-        let dividend_amount = total_profit * (equity_safe / 100.0);
-        
+        holders.push((name, equity_safe, pick_name, proxy_host));
+    }
+
+    // Equity is a percentage (25.0 means 25%). The profit share of all
+    // holders together is rounded once, then split by equity so the
+    // dividends add up to it to the fen.
+    let equity_total: f64 = holders.iter().map(|h| h.1).sum();
+    let distributable = total_profit.mul_f64(equity_total.min(100.0) / 100.0);
+    let weights: Vec<f64> = holders.iter().map(|h| h.1).collect();
+    let dividends = distributable.allocate(&weights);
+
+    for ((name, equity, pick_name, proxy_host), dividend) in holders.into_iter().zip(dividends) {
         shareholders.push(ShareholderDividend {
             name,
-            equity: equity_safe,
-            dividend: dividend_amount,
+            equity,
+            dividend,
             pick_name,
             proxy_host,
         });
@@ -253,3 +315,45 @@ pub fn finance_dividend_report(app: AppHandle, token: String, month: String) ->
         shareholders,
     })
 }
+
+/// Sales lines grouped by the current category of their product; lines not
+/// linked to a product are reported under "未分类". Each category carries its
+/// account item and the output tax its revenue includes at the category's rate.
+#[tauri::command]
+pub fn sales_category_report(app: AppHandle, token: String, date_from: String, date_to: String) -> Result<Vec<SalesCategoryStat>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(p.category, '未分类'), \
+                COALESCE(SUM(i.sales), 0), \
+                COALESCE(SUM(i.revenue), 0), \
+                COALESCE(SUM(ROUND(COALESCE(i.sales, 0) * CASE WHEN COALESCE(i.spec, 0) > 0 THEN i.cost_price / i.spec ELSE COALESCE(i.cost_price, 0) END)), 0), \
+                MAX(c.account_item), COALESCE(MAX(c.tax_rate), 0) \
+         FROM sales_items i \
+         JOIN sales_orders o ON o.id = i.order_id \
+         LEFT JOIN products p ON p.id = i.product_id \
+         LEFT JOIN product_categories c ON c.name = p.category \
+         WHERE o.date_ymd >= ?1 AND o.date_ymd <= ?2 \
+         GROUP BY 1 ORDER BY MIN(COALESCE(c.sort_order, 1000000)) ASC, 3 DESC"
+    ).map_err(|e| format!("prepare: {e}"))?;
+    let mut rows = stmt.query([date_from.trim(), date_to.trim()]).map_err(|e| format!("query: {e}"))?;
+    let mut out = Vec::new();
+    while let Some(r) = rows.next().map_err(|e| format!("next: {e}"))? {
+        let revenue: Money = r.get(2).unwrap_or_default();
+        let cost: Money = r.get(3).unwrap_or_default();
+        let tax_rate: f64 = r.get(5).unwrap_or(0.0);
+        out.push(SalesCategoryStat {
+            category: r.get(0).unwrap_or_default(),
+            quantity: r.get(1).unwrap_or(0.0),
+            revenue,
+            cost,
+            profit: revenue - cost,
+            account_item: r.get(4).unwrap_or_default(),
+            tax_rate,
+            // Prices include tax: revenue × rate / (1 + rate).
+            tax: revenue.mul_f64(tax_rate / (1.0 + tax_rate)),
+        });
+    }
+    Ok(out)
+}
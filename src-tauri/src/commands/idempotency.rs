@@ -0,0 +1,47 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::constants::IDEMPOTENCY_WINDOW_SECS;
+
+// Write commands reachable over HTTP take an optional idempotency key. The
+// first call stores its result under (scope, key); a retry inside the window
+// gets that result back instead of writing again. Both the lookup and the
+// store run in the caller's transaction, which must be IMMEDIATE: a deferred
+// one only takes the write lock at its first write, so two retries could both
+// miss the key and both write.
+
+fn clean_key(key: Option<&str>) -> Option<&str> {
+    key.map(str::trim).filter(|k| !k.is_empty())
+}
+
+/// The result stored by an earlier call with the same key, if still fresh.
+pub fn replayed_result(conn: &Connection, scope: &str, key: Option<&str>, now: i64) -> Result<Option<String>, String> {
+    let key = match clean_key(key) {
+        Some(k) => k,
+        None => return Ok(None),
+    };
+    conn.query_row(
+        "SELECT result FROM idempotency_keys WHERE scope = ?1 AND key = ?2 AND created_at > ?3",
+        params![scope, key, now - IDEMPOTENCY_WINDOW_SECS],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("query idempotency_keys: {e}"))
+}
+
+/// Stores the result of a call under its key and drops expired keys.
+pub fn remember_result(conn: &Connection, scope: &str, key: Option<&str>, result: &str, now: i64) -> Result<(), String> {
+    let key = match clean_key(key) {
+        Some(k) => k,
+        None => return Ok(()),
+    };
+    conn.execute(
+        "DELETE FROM idempotency_keys WHERE created_at <= ?1",
+        [now - IDEMPOTENCY_WINDOW_SECS],
+    )
+    .map_err(|e| format!("prune idempotency_keys: {e}"))?;
+    conn.execute(
+        "INSERT INTO idempotency_keys(scope, key, result, created_at) VALUES(?1, ?2, ?3, ?4)",
+        params![scope, key, result, now],
+    )
+    .map_err(|e| format!("insert idempotency_keys: {e}"))?;
+    Ok(())
+}
@@ -0,0 +1,556 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::constants::{LOCATION_SHELF, LOCATION_STOREROOM};
+use crate::models::*;
+use crate::commands::units::{base_units_cost, purchase_unit, unit_factor};
+use crate::money::Money;
+
+// Location kinds: 'front' (bar shelf, fridge) is what checkout sells from,
+// 'back' is storage in this store, 'store' is another store we transfer to.
+const LOCATION_KINDS: [&str; 3] = ["front", "back", "store"];
+
+/// Applies `delta` to one product/location bucket and records the movement.
+/// Callers are expected to run inside a transaction and call
+/// `sync_product_totals` once they are done with the product.
+pub fn adjust_location_stock(
+    conn: &Connection,
+    product_id: &str,
+    location_id: &str,
+    delta: f64,
+    reason: &str,
+    ref_id: Option<&str>,
+    actor_id: Option<&str>,
+) -> Result<(), String> {
+    let now = now_ts()?;
+    let known: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM stock_locations WHERE id = ?1)", [location_id], |r| r.get(0))
+        .map_err(|e| format!("query location: {e}"))?;
+    if !known {
+        return Err(format!("location_not_found: {location_id}"));
+    }
+    conn.execute(
+        "INSERT INTO product_stock(product_id, location_id, quantity, updated_at) VALUES(?1, ?2, ?3, ?4) \
+         ON CONFLICT(product_id, location_id) DO UPDATE SET quantity = quantity + excluded.quantity, updated_at = excluded.updated_at",
+        params![product_id, location_id, delta, now],
+    )
+    .map_err(|e| format!("update product_stock: {e}"))?;
+    conn.execute(
+        "INSERT INTO stock_movements(id, product_id, location_id, delta, reason, ref_id, actor_id, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![Uuid::new_v4().to_string(), product_id, location_id, delta, reason, ref_id, actor_id, now],
+    )
+    .map_err(|e| format!("insert stock_movement: {e}"))?;
+    Ok(())
+}
+
+/// Keeps the legacy `products.stock` / `products.on_shelf` columns in step with
+/// the per-location quantities: `stock` is everything held by this store and
+/// `on_shelf` is the front-of-house part of it.
+pub fn sync_product_totals(conn: &Connection, product_id: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE products SET \
+            stock = (SELECT COALESCE(SUM(s.quantity), 0) FROM product_stock s JOIN stock_locations l ON l.id = s.location_id WHERE s.product_id = ?1 AND l.kind != 'store'), \
+            on_shelf = (SELECT COALESCE(SUM(s.quantity), 0) FROM product_stock s JOIN stock_locations l ON l.id = s.location_id WHERE s.product_id = ?1 AND l.kind = 'front'), \
+            updated_at = ?2 \
+         WHERE id = ?1",
+        params![product_id, now],
+    )
+    .map_err(|e| format!("sync product totals: {e}"))?;
+    Ok(())
+}
+
+/// Creates the initial shelf/storeroom buckets for a product that has none yet.
+pub fn seed_product_stock(conn: &Connection, product_id: &str, on_shelf: f64, stock: f64, now: i64) -> Result<(), String> {
+    let exists: Option<i64> = conn
+        .query_row("SELECT 1 FROM product_stock WHERE product_id = ?1 LIMIT 1", [product_id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query product_stock: {e}"))?;
+    if exists.is_some() {
+        return Ok(());
+    }
+    let shelf = on_shelf.min(stock).max(0.0);
+    adjust_location_stock(conn, product_id, LOCATION_SHELF, shelf, "init", None, None)?;
+    adjust_location_stock(conn, product_id, LOCATION_STOREROOM, stock - shelf, "init", None, None)?;
+    sync_product_totals(conn, product_id, now)
+}
+
+pub fn default_front_location(conn: &Connection) -> Result<String, String> {
+    conn.query_row(
+        "SELECT id FROM stock_locations WHERE kind = 'front' AND is_active = 1 ORDER BY sort_order ASC, name ASC LIMIT 1",
+        [],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("query front location: {e}"))?
+    .ok_or_else(|| String::from("no_front_location"))
+}
+
+/// Where a sale takes its stock from: `location_id` when given, which must be
+/// an active front-of-house location, otherwise the default one.
+pub fn sale_location(conn: &Connection, location_id: Option<&str>) -> Result<String, String> {
+    match location_id.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(l) if location_kind(conn, l)? == "front" => Ok(l.to_string()),
+        Some(l) => Err(format!("not_front_location: {l}")),
+        None => default_front_location(conn),
+    }
+}
+
+fn location_kind(conn: &Connection, location_id: &str) -> Result<String, String> {
+    conn.query_row(
+        "SELECT kind FROM stock_locations WHERE id = ?1 AND is_active = 1",
+        [location_id],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("query location: {e}"))?
+    .ok_or_else(|| format!("location_not_found: {location_id}"))
+}
+
+fn location_quantity(conn: &Connection, product_id: &str, location_id: &str) -> Result<f64, String> {
+    let qty: Option<f64> = conn
+        .query_row(
+            "SELECT quantity FROM product_stock WHERE product_id = ?1 AND location_id = ?2",
+            params![product_id, location_id],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("query product_stock: {e}"))?;
+    Ok(qty.unwrap_or(0.0))
+}
+
+/// Quantities moved into front-of-house locations from storage during a shift,
+/// i.e. the 补货 column of the shift sales sheet.
+pub fn load_restock_summary(conn: &Connection, date_ymd: &str, shift: &str) -> Result<Vec<RestockSummaryRow>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.product_id, COALESCE(p.name, MAX(t.product_name)), SUM(t.quantity) \
+             FROM stock_transfers t \
+             JOIN stock_locations f ON f.id = t.from_location \
+             JOIN stock_locations d ON d.id = t.to_location \
+             LEFT JOIN products p ON p.id = t.product_id \
+             WHERE t.date_ymd = ?1 AND t.shift = ?2 AND d.kind = 'front' AND f.kind != 'front' \
+             GROUP BY t.product_id ORDER BY 2 ASC",
+        )
+        .map_err(|e| format!("prepare restock: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_ymd, shift], |r| {
+            Ok(RestockSummaryRow {
+                product_id: r.get(0)?,
+                product_name: r.get(1)?,
+                quantity: r.get(2)?,
+            })
+        })
+        .map_err(|e| format!("query restock: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row restock: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn stock_locations_list(app: AppHandle, token: String) -> Result<Vec<StockLocationRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, name, kind, sort_order, is_active FROM stock_locations ORDER BY sort_order ASC, name ASC")
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(StockLocationRow {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                kind: r.get(2)?,
+                sort_order: r.get(3)?,
+                is_active: r.get::<_, i64>(4)? != 0,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn stock_location_upsert(app: AppHandle, input: StockLocationUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let kind = input.kind.trim().to_string();
+    if name.is_empty() || !LOCATION_KINDS.contains(&kind.as_str()) {
+        return Err(String::from("invalid"));
+    }
+    let sort_order = input.sort_order.unwrap_or(0);
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO stock_locations(id, name, kind, sort_order, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, name, kind, sort_order, is_active, now, now],
+        )
+        .map_err(|e| format!("insert stock_locations: {e}"))?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE stock_locations SET name = ?2, kind = ?3, sort_order = ?4, is_active = ?5, updated_at = ?6 WHERE id = ?1",
+            params![id, name, kind, sort_order, is_active, now],
+        )
+        .map_err(|e| format!("update stock_locations: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn product_stock_list(
+    app: AppHandle,
+    token: String,
+    product_id: Option<String>,
+    location_id: Option<String>,
+) -> Result<Vec<ProductStockRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut sql = String::from(
+        "SELECT s.product_id, p.name, s.location_id, l.name, s.quantity \
+         FROM product_stock s \
+         JOIN products p ON p.id = s.product_id \
+         JOIN stock_locations l ON l.id = s.location_id",
+    );
+    let mut where_parts: Vec<&str> = Vec::new();
+    let mut params_v: Vec<String> = Vec::new();
+
+    let pid = product_id.unwrap_or_default().trim().to_string();
+    if !pid.is_empty() {
+        where_parts.push("s.product_id = ?");
+        params_v.push(pid);
+    }
+    let lid = location_id.unwrap_or_default().trim().to_string();
+    if !lid.is_empty() {
+        where_parts.push("s.location_id = ?");
+        params_v.push(lid);
+    }
+    if !where_parts.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&where_parts.join(" AND "));
+    }
+    sql.push_str(" ORDER BY p.name ASC, l.sort_order ASC");
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params_v), |r| {
+            Ok(ProductStockRow {
+                product_id: r.get(0)?,
+                product_name: r.get(1)?,
+                location_id: r.get(2)?,
+                location_name: r.get(3)?,
+                quantity: r.get(4)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn stock_transfer_create(app: AppHandle, input: StockTransferInput) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let date_ymd = input.date_ymd.trim().to_string();
+    let shift = input.shift.trim().to_string();
+    let employee = input.employee.trim().to_string();
+    let product_id = input.product_id.trim().to_string();
+    let from = input.from_location.trim().to_string();
+    let to = input.to_location.trim().to_string();
+    if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() || product_id.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if from == to || input.quantity <= 0.0 || !input.quantity.is_finite() {
+        return Err(String::from("invalid"));
+    }
+    let note = input.note.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let product_name: String = tx
+        .query_row("SELECT name FROM products WHERE id = ?1", [&product_id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query product: {e}"))?
+        .ok_or_else(|| format!("product_not_found: {product_id}"))?;
+    let _ = location_kind(&tx, &from)?;
+    let _ = location_kind(&tx, &to)?;
+    if location_quantity(&tx, &product_id, &from)? < input.quantity {
+        return Err(String::from("insufficient_stock"));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    adjust_location_stock(&tx, &product_id, &from, -input.quantity, "transfer_out", Some(&id), Some(&actor_id))?;
+    adjust_location_stock(&tx, &product_id, &to, input.quantity, "transfer_in", Some(&id), Some(&actor_id))?;
+    tx.execute(
+        "INSERT INTO stock_transfers(id, date_ymd, shift, employee, actor_id, product_id, product_name, from_location, to_location, quantity, note, created_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![id, date_ymd, shift, employee, actor_id, product_id, product_name, from, to, input.quantity, note, now],
+    )
+    .map_err(|e| format!("insert transfer: {e}"))?;
+    sync_product_totals(&tx, &product_id, now)?;
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn stock_transfers_list(
+    app: AppHandle,
+    token: String,
+    date_ymd: Option<String>,
+    shift: Option<String>,
+    product_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<StockTransferRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let limit = limit.unwrap_or(500).clamp(1, 5000);
+
+    let mut sql = String::from(
+        "SELECT id, date_ymd, shift, employee, actor_id, product_id, product_name, from_location, to_location, quantity, note, created_at \
+         FROM stock_transfers",
+    );
+    let mut where_parts: Vec<&str> = Vec::new();
+    let mut params_v: Vec<String> = Vec::new();
+
+    let d = date_ymd.unwrap_or_default().trim().to_string();
+    if !d.is_empty() {
+        where_parts.push("date_ymd = ?");
+        params_v.push(d);
+    }
+    let s = shift.unwrap_or_default().trim().to_string();
+    if !s.is_empty() {
+        where_parts.push("shift = ?");
+        params_v.push(s);
+    }
+    let p = product_id.unwrap_or_default().trim().to_string();
+    if !p.is_empty() {
+        where_parts.push("product_id = ?");
+        params_v.push(p);
+    }
+    if !where_parts.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&where_parts.join(" AND "));
+    }
+    sql.push_str(" ORDER BY created_at DESC LIMIT ");
+    sql.push_str(&limit.to_string());
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params_v), |r| {
+            Ok(StockTransferRow {
+                id: r.get(0)?,
+                date_ymd: r.get(1)?,
+                shift: r.get(2)?,
+                employee: r.get(3)?,
+                actor_id: r.get(4)?,
+                product_id: r.get(5)?,
+                product_name: r.get(6)?,
+                from_location: r.get(7)?,
+                to_location: r.get(8)?,
+                quantity: r.get(9)?,
+                note: r.get(10)?,
+                created_at: r.get(11)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Stocktake correction: sets the counted quantity for one location.
+#[tauri::command]
+pub fn stock_adjust(app: AppHandle, input: StockAdjustInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let product_id = input.product_id.trim().to_string();
+    let location_id = input.location_id.trim().to_string();
+    if product_id.is_empty() || location_id.is_empty() || !input.quantity.is_finite() {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let _ = location_kind(&tx, &location_id)?;
+    let current = location_quantity(&tx, &product_id, &location_id)?;
+    let delta = input.quantity - current;
+    if delta.abs() > 1e-9 {
+        adjust_location_stock(&tx, &product_id, &location_id, delta, "adjust", None, Some(&actor_id))?;
+        sync_product_totals(&tx, &product_id, now)?;
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stock_restock_summary(app: AppHandle, token: String, date_ymd: String, shift: String) -> Result<Vec<RestockSummaryRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_restock_summary(&conn, date_ymd.trim(), shift.trim())
+}
+
+/// Records goods received from a supplier. Quantities are entered in the
+/// purchase unit (箱 by default) and stored as base units; a supplied
+/// `unit_cost` becomes the product's latest cost.
+#[tauri::command]
+pub fn stock_receive(app: AppHandle, input: StockReceiveInput) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let date_ymd = input.date_ymd.trim().to_string();
+    let shift = input.shift.trim().to_string();
+    let employee = input.employee.trim().to_string();
+    let product_id = input.product_id.trim().to_string();
+    if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() || product_id.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if input.quantity <= 0.0 || !input.quantity.is_finite() {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let (product_name, base_unit, cost_price, spec): (String, String, Money, f64) = tx
+        .query_row(
+            "SELECT name, base_unit, cost_price, spec FROM products WHERE id = ?1",
+            [&product_id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| format!("query product: {e}"))?
+        .ok_or_else(|| format!("product_not_found: {product_id}"))?;
+
+    let (unit, factor) = match input.unit.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) {
+        Some(u) => {
+            let f = unit_factor(&tx, &product_id, &base_unit, Some(&u))?;
+            (u, f)
+        }
+        None => purchase_unit(&tx, &product_id)?.unwrap_or((base_unit.clone(), 1.0)),
+    };
+    let location_id = input
+        .location_id
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| LOCATION_STOREROOM.to_string());
+    let _ = location_kind(&tx, &location_id)?;
+
+    let base_quantity = input.quantity * factor;
+    let unit_cost = match input.unit_cost {
+        Some(c) if c >= Money::ZERO => {
+            let purchase_factor = if spec > 0.0 { spec } else { 1.0 };
+            tx.execute(
+                "UPDATE products SET cost_price = ?2, updated_at = ?3 WHERE id = ?1",
+                params![product_id, c.mul_f64(purchase_factor / factor), now],
+            )
+            .map_err(|e| format!("update cost_price: {e}"))?;
+            c
+        }
+        _ => base_units_cost(cost_price, spec, factor),
+    };
+    let total_cost = unit_cost.mul_f64(input.quantity);
+
+    let id = Uuid::new_v4().to_string();
+    adjust_location_stock(&tx, &product_id, &location_id, base_quantity, "purchase", Some(&id), Some(&actor_id))?;
+    tx.execute(
+        "INSERT INTO stock_receipts(id, date_ymd, shift, employee, actor_id, product_id, product_name, location_id, unit, quantity, factor, base_quantity, unit_cost, total_cost, created_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        params![
+            id, date_ymd, shift, employee, actor_id, product_id, product_name, location_id,
+            unit, input.quantity, factor, base_quantity, unit_cost, total_cost, now
+        ],
+    )
+    .map_err(|e| format!("insert receipt: {e}"))?;
+    sync_product_totals(&tx, &product_id, now)?;
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn stock_receipts_list(
+    app: AppHandle,
+    token: String,
+    date_ymd: Option<String>,
+    product_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<StockReceiptRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let limit = limit.unwrap_or(500).clamp(1, 5000);
+
+    let mut sql = String::from(
+        "SELECT id, date_ymd, shift, employee, product_id, product_name, location_id, unit, quantity, factor, base_quantity, unit_cost, total_cost, created_at \
+         FROM stock_receipts",
+    );
+    let mut where_parts: Vec<&str> = Vec::new();
+    let mut params_v: Vec<String> = Vec::new();
+
+    let d = date_ymd.unwrap_or_default().trim().to_string();
+    if !d.is_empty() {
+        where_parts.push("date_ymd = ?");
+        params_v.push(d);
+    }
+    let p = product_id.unwrap_or_default().trim().to_string();
+    if !p.is_empty() {
+        where_parts.push("product_id = ?");
+        params_v.push(p);
+    }
+    if !where_parts.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&where_parts.join(" AND "));
+    }
+    sql.push_str(" ORDER BY created_at DESC LIMIT ");
+    sql.push_str(&limit.to_string());
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params_v), |r| {
+            Ok(StockReceiptRow {
+                id: r.get(0)?,
+                date_ymd: r.get(1)?,
+                shift: r.get(2)?,
+                employee: r.get(3)?,
+                product_id: r.get(4)?,
+                product_name: r.get(5)?,
+                location_id: r.get(6)?,
+                unit: r.get(7)?,
+                quantity: r.get(8)?,
+                factor: r.get(9)?,
+                base_quantity: r.get(10)?,
+                unit_cost: r.get(11)?,
+                total_cost: r.get(12)?,
+                created_at: r.get(13)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
@@ -0,0 +1,453 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use std::time::Duration;
+use uuid::Uuid;
+use crate::db::{open_db, now_ts, now_ymd};
+use crate::state::{auth_resolve_account_id, require_admin, log_to_system};
+use crate::constants::{POINTS_ENTRY_KINDS, POINTS_EXPIRY_DAYS_MAX};
+use crate::models::*;
+use crate::commands::kv::load_kv_json;
+use crate::commands::members::{load_member, require_active_member};
+use crate::money::Money;
+
+// Members earn loyalty points on what they pay and spend them on products
+// that have a points price. Points are kept in lots: every entry that adds
+// points (earn, return, positive adjustment) carries `remaining` and an
+// optional `expires_at`, and entries that take points off draw the lots down
+// soonest-expiring first. `members.points` is always the sum of the remaining
+// lots. Expired lots are swept, in a transaction, at startup and hourly, and
+// for a member before their points move; reports only read, and count lots
+// past their expiry as gone whether swept or not.
+
+const LOYALTY_SETTINGS_KEY: &str = "settings.loyalty";
+const POINTS_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const POINTS_ENTRY_COLUMNS: &str =
+    "id, member_id, kind, points, balance_after, remaining, expires_at, order_id, date_ymd, note, actor_id, created_at";
+
+pub fn load_loyalty_settings(conn: &Connection) -> Result<Option<LoyaltySettings>, String> {
+    Ok(load_kv_json(conn, LOYALTY_SETTINGS_KEY)?.and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// A movement to book against a member's points.
+pub struct PointsPosting<'a> {
+    pub kind: &'a str,
+    /// Signed: earning and returns add, redeeming takes off.
+    pub points: i64,
+    /// When the points added expire. Entries that take points off ignore it
+    /// and record the expiry of the last lot they drew from instead, so points
+    /// given back later keep it.
+    pub expires_at: Option<i64>,
+    pub order_id: Option<&'a str>,
+    pub date_ymd: &'a str,
+    pub note: Option<&'a str>,
+    pub actor_id: &'a str,
+}
+
+/// Moves a member's points and records the ledger entry. Like the stored-value
+/// balance, points can't go below zero and the check is part of the update.
+/// Returns the entry id.
+pub fn post_points(conn: &Connection, member_id: &str, p: &PointsPosting, now: i64) -> Result<String, String> {
+    let n = conn
+        .execute(
+            "UPDATE members SET points = points + ?2, updated_at = ?3 WHERE id = ?1 AND points + ?2 >= 0",
+            params![member_id, p.points, now],
+        )
+        .map_err(|e| format!("update member points: {e}"))?;
+    if n == 0 {
+        load_member(conn, member_id)?;
+        return Err(String::from("insufficient_points"));
+    }
+    let balance_after: i64 = conn
+        .query_row("SELECT points FROM members WHERE id = ?1", [member_id], |r| r.get(0))
+        .map_err(|e| format!("query member points: {e}"))?;
+
+    let mut drawn_until: Option<i64> = None;
+    if p.points < 0 {
+        let lots = {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, remaining, expires_at FROM member_points_entries WHERE member_id = ?1 AND remaining > 0 \
+                     ORDER BY expires_at IS NULL, expires_at ASC, created_at ASC, rowid ASC",
+                )
+                .map_err(|e| format!("prepare points lots: {e}"))?;
+            let rows = stmt
+                .query_map([member_id], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?, r.get::<_, Option<i64>>(2)?)))
+                .map_err(|e| format!("query points lots: {e}"))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(|e| format!("row points lots: {e}"))?
+        };
+        let mut left = -p.points;
+        for (lot_id, remaining, expires_at) in lots {
+            if left == 0 {
+                break;
+            }
+            drawn_until = expires_at;
+            let take = left.min(remaining);
+            conn.execute("UPDATE member_points_entries SET remaining = remaining - ?2 WHERE id = ?1", params![lot_id, take])
+                .map_err(|e| format!("update points lot: {e}"))?;
+            left -= take;
+        }
+    }
+
+    let (remaining, expires_at) = if p.points > 0 { (p.points, p.expires_at) } else { (0, drawn_until) };
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO member_points_entries(id, member_id, kind, points, balance_after, remaining, expires_at, order_id, date_ymd, note, actor_id, created_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![id, member_id, p.kind, p.points, balance_after, remaining, expires_at, p.order_id, p.date_ymd, p.note, p.actor_id, now],
+    )
+    .map_err(|e| format!("insert points entry: {e}"))?;
+    Ok(id)
+}
+
+/// Writes off lots that have expired by `now`, for one member or everyone.
+/// Each write-off is dated the day the lot expired so reports for past days
+/// stay right however late the sweep runs.
+pub fn expire_points(conn: &Connection, member_id: Option<&str>, now: i64) -> Result<(), String> {
+    let lots = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, member_id, remaining, date(expires_at, 'unixepoch', 'localtime') FROM member_points_entries \
+                 WHERE remaining > 0 AND expires_at <= ?1 AND (?2 IS NULL OR member_id = ?2) ORDER BY expires_at ASC, rowid ASC",
+            )
+            .map_err(|e| format!("prepare expired points: {e}"))?;
+        let rows = stmt
+            .query_map(params![now, member_id], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, i64>(2)?, r.get::<_, String>(3)?))
+            })
+            .map_err(|e| format!("query expired points: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| format!("row expired points: {e}"))?
+    };
+    for (lot_id, member_id, remaining, date_ymd) in lots {
+        conn.execute("UPDATE member_points_entries SET remaining = 0 WHERE id = ?1", [&lot_id])
+            .map_err(|e| format!("update points lot: {e}"))?;
+        conn.execute(
+            "UPDATE members SET points = points - ?2, updated_at = ?3 WHERE id = ?1",
+            params![member_id, remaining, now],
+        )
+        .map_err(|e| format!("update member points: {e}"))?;
+        let balance_after: i64 = conn
+            .query_row("SELECT points FROM members WHERE id = ?1", [&member_id], |r| r.get(0))
+            .map_err(|e| format!("query member points: {e}"))?;
+        conn.execute(
+            "INSERT INTO member_points_entries(id, member_id, kind, points, balance_after, remaining, order_id, date_ymd, note, created_at) \
+             VALUES(?1, ?2, 'expire', ?3, ?4, 0, NULL, ?5, ?6, ?7)",
+            params![Uuid::new_v4().to_string(), member_id, -remaining, balance_after, date_ymd, lot_id, now],
+        )
+        .map_err(|e| format!("insert points entry: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Sweeps expired points for everyone at startup and then every hour, each
+/// sweep in one transaction.
+pub fn start_points_expiry_worker(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let run = || -> Result<(), String> {
+            let mut conn = open_db(&app)?;
+            let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+            expire_points(&tx, None, now_ts()?)?;
+            tx.commit().map_err(|e| format!("commit: {e}"))
+        };
+        if let Err(e) = run() {
+            log_to_system("error", "loyalty", "积分过期处理出错", Some(&e));
+        }
+        std::thread::sleep(POINTS_SWEEP_INTERVAL);
+    });
+}
+
+/// Loyalty points outstanding at the end of `date_ymd`. Lots that had expired
+/// by then but are not swept yet are left out, as the sweep will date their
+/// write-off the day they expired.
+pub fn points_outstanding_at(conn: &Connection, date_ymd: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(points), 0) \
+                - (SELECT COALESCE(SUM(remaining), 0) FROM member_points_entries \
+                   WHERE remaining > 0 AND date_ymd <= ?1 AND date(expires_at, 'unixepoch', 'localtime') <= ?1) \
+         FROM member_points_entries WHERE date_ymd <= ?1",
+        [date_ymd],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("query points outstanding: {e}"))
+}
+
+/// When points posted at `now` expire, `expiry_days` later.
+fn expiry_from(expiry_days: Option<i64>, now: i64) -> Result<Option<i64>, String> {
+    expiry_days
+        .map(|d| d.checked_mul(86400).and_then(|secs| now.checked_add(secs)).ok_or_else(|| String::from("invalid expiry_days")))
+        .transpose()
+}
+
+/// What `points` are worth under the current settings.
+pub fn points_value(conn: &Connection, points: i64) -> Result<Money, String> {
+    let point_value = load_loyalty_settings(conn)?.map(|s| s.point_value).unwrap_or(Money::ZERO);
+    point_value
+        .fen()
+        .checked_mul(points)
+        .map(Money::from_fen)
+        .ok_or_else(|| String::from("points_value overflow"))
+}
+
+/// Points for `quantity` base units of a product given away for points.
+pub fn redeem_cost(conn: &Connection, product: &ProductRow, quantity: f64) -> Result<i64, String> {
+    let points: Option<i64> = conn
+        .query_row("SELECT redeem_points FROM products WHERE id = ?1", [&product.id], |r| r.get(0))
+        .map_err(|e| format!("query redeem points: {e}"))?;
+    match points.filter(|p| *p > 0) {
+        Some(p) if quantity > 0.0 => Ok((p as f64 * quantity).ceil() as i64),
+        _ => Err(format!("not_redeemable: {}", product.name)),
+    }
+}
+
+/// Takes the points an order's redeemed lines cost off the member.
+pub fn redeem_points(conn: &Connection, member_id: &str, points: i64, order_id: &str, date_ymd: &str, actor_id: &str, now: i64) -> Result<(), String> {
+    expire_points(conn, Some(member_id), now)?;
+    post_points(
+        conn,
+        member_id,
+        &PointsPosting { kind: "redeem", points: -points, expires_at: None, order_id: Some(order_id), date_ymd, note: None, actor_id },
+        now,
+    )?;
+    Ok(())
+}
+
+/// Earns the member points on an order's paid lines: each line at its
+/// category's rate or the default, times the bonus multiplier on bonus days,
+/// rounded down for the whole order. Returns the points earned.
+pub fn award_points(
+    conn: &Connection,
+    member_id: &str,
+    earning: &[(String, Money)],
+    order_id: &str,
+    date_ymd: &str,
+    actor_id: &str,
+    now: i64,
+) -> Result<i64, String> {
+    let Some(settings) = load_loyalty_settings(conn)?.filter(|s| s.enabled) else {
+        return Ok(0);
+    };
+    let mut points: f64 = earning
+        .iter()
+        .map(|(category, net)| {
+            let rate = settings
+                .category_rates
+                .iter()
+                .find(|r| &r.category == category)
+                .map(|r| r.points_per_yuan)
+                .unwrap_or(settings.points_per_yuan);
+            net.fen() as f64 / 100.0 * rate
+        })
+        .sum();
+
+    if let Some(multiplier) = settings.bonus_multiplier {
+        let (weekday, local_date): (i64, String) = conn
+            .query_row(
+                "SELECT CAST(strftime('%w', ?1, 'unixepoch', 'localtime') AS INTEGER), date(?1, 'unixepoch', 'localtime')",
+                [now],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .map_err(|e| format!("query local time: {e}"))?;
+        let weekday = if weekday == 0 { 7 } else { weekday };
+        if settings.bonus_weekdays.contains(&weekday) || settings.bonus_dates.contains(&local_date) {
+            points *= multiplier;
+        }
+    }
+
+    // A little slack so 9.999999 from float rates still earns 10.
+    let points = (points + 1e-6).floor() as i64;
+    if points <= 0 {
+        return Ok(0);
+    }
+    let expires_at = expiry_from(settings.expiry_days, now)?;
+    expire_points(conn, Some(member_id), now)?;
+    post_points(
+        conn,
+        member_id,
+        &PointsPosting { kind: "earn", points, expires_at, order_id: Some(order_id), date_ymd, note: None, actor_id },
+        now,
+    )?;
+    Ok(points)
+}
+
+fn load_points_entries(conn: &Connection, filter: &str, args: &[&dyn rusqlite::ToSql]) -> Result<Vec<PointsEntryRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {POINTS_ENTRY_COLUMNS} FROM member_points_entries WHERE {filter}"))
+        .map_err(|e| format!("prepare points entries: {e}"))?;
+    let rows = stmt
+        .query_map(args, |r| {
+            Ok(PointsEntryRow {
+                id: r.get(0)?,
+                member_id: r.get(1)?,
+                kind: r.get(2)?,
+                points: r.get(3)?,
+                balance_after: r.get(4)?,
+                remaining: r.get(5)?,
+                expires_at: r.get(6)?,
+                order_id: r.get(7)?,
+                date_ymd: r.get(8)?,
+                note: r.get(9)?,
+                actor_id: r.get(10)?,
+                created_at: r.get(11)?,
+            })
+        })
+        .map_err(|e| format!("query points entries: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row points entries: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Whether `s` is a real date written YYYY-MM-DD.
+fn is_ymd(conn: &Connection, s: &str) -> bool {
+    conn.query_row("SELECT date(?1) IS ?1", [s], |r| r.get(0)).unwrap_or(false)
+}
+
+#[tauri::command]
+pub fn loyalty_settings_get(app: AppHandle, token: String) -> Result<Option<LoyaltySettings>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_loyalty_settings(&conn)
+}
+
+#[tauri::command]
+pub fn settings_save_loyalty(app: AppHandle, input: LoyaltySettingsInput) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let mut settings = input.settings;
+    for r in settings.category_rates.iter_mut() {
+        r.category = r.category.trim().to_string();
+    }
+    settings.category_rates.retain(|r| !r.category.is_empty());
+    settings.bonus_dates = settings.bonus_dates.iter().map(|d| d.trim().to_string()).filter(|d| !d.is_empty()).collect();
+    let valid_rate = |r: f64| r.is_finite() && r >= 0.0;
+    if !valid_rate(settings.points_per_yuan)
+        || settings.category_rates.iter().any(|r| !valid_rate(r.points_per_yuan))
+        || settings.bonus_weekdays.iter().any(|d| !(1..=7).contains(d))
+        || settings.bonus_dates.iter().any(|d| !is_ymd(&conn, d))
+        || settings.bonus_multiplier.is_some_and(|m| !m.is_finite() || m < 1.0)
+        || settings.expiry_days.is_some_and(|d| !(1..=POINTS_EXPIRY_DAYS_MAX).contains(&d))
+        || settings.point_value < Money::ZERO
+    {
+        return Err(String::from("invalid"));
+    }
+    let now = now_ts()?;
+    let v = serde_json::to_string(&settings).map_err(|e| format!("serialize: {e}"))?;
+    conn.execute(
+        "INSERT INTO kv(k, v, updated_at) VALUES(?1, ?2, ?3) \
+         ON CONFLICT(k) DO UPDATE SET v=excluded.v, updated_at=excluded.updated_at",
+        params![LOYALTY_SETTINGS_KEY, v, now],
+    )
+    .map_err(|e| format!("save loyalty settings: {e}"))?;
+    Ok(())
+}
+
+/// A member's points history, newest first, optionally only entries of `kind`.
+#[tauri::command]
+pub fn member_points_entries(app: AppHandle, token: String, member_id: String, kind: Option<String>, limit: Option<i64>) -> Result<Vec<PointsEntryRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    let kind = kind.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if kind.is_some_and(|k| !POINTS_ENTRY_KINDS.contains(&k)) {
+        return Err(String::from("invalid"));
+    }
+    load_points_entries(
+        &conn,
+        "member_id = ?1 AND (?2 IS NULL OR kind = ?2) ORDER BY created_at DESC, rowid DESC LIMIT ?3",
+        &[&member_id.trim(), &kind, &limit],
+    )
+}
+
+/// Gives or takes points by hand, e.g. a goodwill gesture. Admins only, with a reason.
+#[tauri::command]
+pub fn member_points_adjust(app: AppHandle, input: MemberPointsAdjustInput) -> Result<PointsEntryRow, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let reason = input.reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if input.points == 0 {
+        return Err(String::from("invalid"));
+    }
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let member_id = input.member_id.trim();
+    require_active_member(&tx, member_id)?;
+    expire_points(&tx, Some(member_id), now)?;
+    let expires_at = expiry_from(load_loyalty_settings(&tx)?.and_then(|s| s.expiry_days), now)?;
+    let id = post_points(
+        &tx,
+        member_id,
+        &PointsPosting {
+            kind: "adjust",
+            points: input.points,
+            expires_at,
+            order_id: None,
+            date_ymd: &now_ymd()?,
+            note: Some(&reason),
+            actor_id: &actor_id,
+        },
+        now,
+    )?;
+    let entry = load_points_entries(&tx, "id = ?1", &[&id])?
+        .pop()
+        .ok_or_else(|| String::from("not_found"))?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(entry)
+}
+
+/// Sets how many points one base unit of a product costs; `None` stops it
+/// being redeemable.
+#[tauri::command]
+pub fn product_set_redeem_points(app: AppHandle, token: String, product_id: String, points: Option<i64>) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    if points.is_some_and(|p| p <= 0) {
+        return Err(String::from("invalid"));
+    }
+    let n = conn
+        .execute(
+            "UPDATE products SET redeem_points = ?2, updated_at = ?3 WHERE id = ?1",
+            params![product_id.trim(), points, now_ts()?],
+        )
+        .map_err(|e| format!("update product: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(())
+}
+
+/// Products that can be had for points, by name.
+#[tauri::command]
+pub fn redeemable_products(app: AppHandle, token: String) -> Result<Vec<RedeemableProduct>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, category, base_unit, redeem_points FROM products \
+             WHERE redeem_points > 0 AND is_active = 1 ORDER BY name ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(RedeemableProduct {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                category: r.get(2)?,
+                base_unit: r.get(3)?,
+                redeem_points: r.get(4)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
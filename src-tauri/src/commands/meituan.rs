@@ -0,0 +1,595 @@
+use std::collections::{HashMap, HashSet};
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::*;
+use crate::commands::sales::{insert_meituan_orders, map_meituan_order_row, MEITUAN_ORDER_COLUMNS};
+use crate::commands::catalog_io::read_table;
+use crate::money::Money;
+
+// Verified Meituan vouchers reach the till as text copied out of 开店宝's
+// verification list, one voucher per line, cells separated by tabs or spaces:
+//
+//   2025-10-09 14:23:11  双人下午茶套餐  1234 5678 9012  售价¥39.90  优惠¥4.00  结算价¥35.90
+//
+// A line is read as follows:
+// - dates and times are ignored;
+// - the coupon number is a run of 10 to 16 digits, which may be grouped by
+//   spaces or dashes;
+// - an amount is a number after one of the labels below, or one marked with
+//   ¥ or ending in 元. Unlabelled amounts fill what the labels left out, in
+//   order: one is the price; two are the price and the settlement; three are
+//   the price, the discount and the settlement;
+// - everything else (product name, quantities) is left alone.
+// Full-width digits and punctuation are folded and runs of whitespace
+// collapsed first; that normalized line is the stored `raw_text`, so the same
+// voucher pasted twice is recognized however it was copied.
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Amount,
+    Discount,
+    Financial,
+}
+
+/// Longest first, so 结算金额 wins over 金额.
+const LABELS: [(&str, Field); 16] = [
+    ("结算金额", Field::Financial),
+    ("商家优惠", Field::Discount),
+    ("平台补贴", Field::Discount),
+    ("预计收入", Field::Financial),
+    ("预计到账", Field::Financial),
+    ("团购价", Field::Amount),
+    ("结算价", Field::Financial),
+    ("售价", Field::Amount),
+    ("实付", Field::Amount),
+    ("金额", Field::Amount),
+    ("优惠", Field::Discount),
+    ("减免", Field::Discount),
+    ("补贴", Field::Discount),
+    ("结算", Field::Financial),
+    ("到账", Field::Financial),
+    ("收入", Field::Financial),
+];
+
+const COUPON_MIN_DIGITS: usize = 10;
+const COUPON_MAX_DIGITS: usize = 16;
+
+/// Folds full-width characters and collapses whitespace.
+pub fn normalize_meituan_line(line: &str) -> String {
+    let folded: String = line
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            '￥' => '¥',
+            '：' => ':',
+            '．' => '.',
+            '－' => '-',
+            '\u{3000}' => ' ',
+            c => c,
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn digits_at(chars: &[char], i: usize) -> usize {
+    chars[i.min(chars.len())..].iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+/// Blanks out dates (2025-10-09, 2025/10/09) and times (14:23, 14:23:11) so
+/// their digits are not read as amounts or coupon numbers.
+fn mask_dates(chars: &mut [char]) {
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() || (i > 0 && chars[i - 1].is_ascii_digit()) {
+            i += 1;
+            continue;
+        }
+        let n = digits_at(chars, i);
+        let mut end = i + n;
+        let is_sep = |c: Option<&char>| matches!(c, Some('-') | Some('/') | Some('.'));
+        if n == 4 && is_sep(chars.get(end)) {
+            let m = digits_at(chars, end + 1);
+            if (1..=2).contains(&m) && is_sep(chars.get(end + 1 + m)) {
+                let d = digits_at(chars, end + 2 + m);
+                if (1..=2).contains(&d) {
+                    end = end + 2 + m + d;
+                }
+            }
+        } else if (1..=2).contains(&n) && chars.get(end) == Some(&':') && digits_at(chars, end + 1) == 2 {
+            end += 3;
+            if chars.get(end) == Some(&':') && digits_at(chars, end + 1) == 2 {
+                end += 3;
+            }
+        }
+        if end > i + n {
+            chars[i..end].iter_mut().for_each(|c| *c = ' ');
+        }
+        i = end.max(i + 1);
+    }
+}
+
+/// Finds the coupon number and blanks it out.
+fn take_coupon_no(chars: &mut [char]) -> Result<Option<String>, String> {
+    let mut found: Option<String> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let starts = chars[i].is_ascii_digit()
+            && (i == 0 || !(chars[i - 1].is_ascii_digit() || chars[i - 1] == '.' || chars[i - 1] == '¥'));
+        if !starts {
+            i += 1;
+            continue;
+        }
+        // Every group needs three digits or more, so a quantity next to the
+        // number (x2, 2份) is not swallowed into it.
+        let mut digits = String::new();
+        let mut j = i;
+        let mut end = i;
+        loop {
+            let n = digits_at(chars, j);
+            let after = chars.get(j + n);
+            let is_amount = matches!(after, Some('.') | Some('元'));
+            if n < 3 || is_amount {
+                break;
+            }
+            digits.extend(&chars[j..j + n]);
+            end = j + n;
+            match after {
+                Some(' ') | Some('-') if chars.get(j + n + 1).is_some_and(|c| c.is_ascii_digit()) => j = j + n + 1,
+                _ => break,
+            }
+        }
+        if (COUPON_MIN_DIGITS..=COUPON_MAX_DIGITS).contains(&digits.len()) {
+            if found.as_ref().is_some_and(|f| *f != digits) {
+                return Err(String::from("multiple_coupon_no"));
+            }
+            chars[i..end].iter_mut().for_each(|c| *c = ' ');
+            found = Some(digits);
+        }
+        i = end.max(i + 1);
+    }
+    Ok(found)
+}
+
+/// The label right before position `i`, skipping separators and signs, and
+/// whether a ¥ was among them. Discounts are sometimes shown as -¥4.00.
+fn label_before(chars: &[char], i: usize) -> (Option<Field>, bool) {
+    let mut k = i;
+    let mut yen = false;
+    while k > 0 && matches!(chars[k - 1], ' ' | ':' | '¥' | '-') {
+        yen |= chars[k - 1] == '¥';
+        k -= 1;
+    }
+    let before: String = chars[..k].iter().collect();
+    let field = LABELS.iter().find(|(label, _)| before.ends_with(label)).map(|(_, f)| *f);
+    (field, yen)
+}
+
+/// Reads the price, discount and settlement of a line whose dates and coupon
+/// number are blanked out.
+fn take_amounts(chars: &[char]) -> Result<[Option<Money>; 3], String> {
+    let mut labelled: [Option<Money>; 3] = [None, None, None];
+    let slot = |f: Field| match f {
+        Field::Amount => 0,
+        Field::Discount => 1,
+        Field::Financial => 2,
+    };
+    let mut unlabelled: Vec<Money> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() || (i > 0 && (chars[i - 1].is_ascii_digit() || chars[i - 1] == '.')) {
+            i += 1;
+            continue;
+        }
+        let mut end = i + digits_at(chars, i);
+        if chars.get(end) == Some(&'.') && digits_at(chars, end + 1) > 0 {
+            end += 1 + digits_at(chars, end + 1);
+        }
+        let text: String = chars[i..end].iter().collect();
+        let mut after = end;
+        while chars.get(after) == Some(&' ') {
+            after += 1;
+        }
+        // 元 only marks an amount at the end of a cell: 100元代金券 is a name.
+        let yuan_after = chars.get(after) == Some(&'元') && matches!(chars.get(after + 1), None | Some(' '));
+        let (field, yen) = label_before(chars, i);
+        i = end;
+        if field.is_none() && !yen && !yuan_after {
+            continue;
+        }
+        let value = Money::parse_yuan(&text).ok_or_else(|| format!("invalid_amount: {text}"))?;
+        match field {
+            Some(f) if labelled[slot(f)].is_some() => return Err(String::from("ambiguous_amount")),
+            Some(f) => labelled[slot(f)] = Some(value),
+            None => unlabelled.push(value),
+        }
+    }
+
+    let order: &[Field] = if unlabelled.len() >= 3 {
+        &[Field::Amount, Field::Discount, Field::Financial]
+    } else {
+        &[Field::Amount, Field::Financial]
+    };
+    let mut rest = unlabelled.into_iter();
+    for f in order.iter() {
+        if labelled[slot(*f)].is_none() {
+            labelled[slot(*f)] = rest.next();
+        }
+    }
+    Ok(labelled)
+}
+
+/// Reads one normalized line into (coupon number, price, discount, settlement).
+fn parse_line(line: &str) -> Result<(String, Money, Money, Money), String> {
+    let mut chars: Vec<char> = line.chars().collect();
+    mask_dates(&mut chars);
+    let coupon_no = take_coupon_no(&mut chars)?;
+    let [amount, discount, financial] = take_amounts(&chars)?;
+    let coupon_no = coupon_no.ok_or_else(|| String::from("missing_coupon_no"))?;
+    let amount = amount.ok_or_else(|| String::from("missing_amount"))?;
+    let discount = discount.unwrap_or(Money::ZERO);
+    let financial = financial.unwrap_or(amount - discount);
+    if amount <= Money::ZERO || discount < Money::ZERO || discount > amount || financial < Money::ZERO {
+        return Err(String::from("invalid_amount"));
+    }
+    Ok((coupon_no, amount, discount, financial))
+}
+
+/// Parses pasted verification records. Lines without any digits (blank lines,
+/// table headers) are skipped; every other line gives an item or an error.
+pub fn parse_meituan_text(text: &str, date_ymd: &str, shift: &str, employee: &str, bundle_id: Option<&str>) -> MeituanParseResult {
+    let mut items: Vec<MeituanOrderImportItem> = Vec::new();
+    let mut errors = Vec::new();
+    for (n, raw) in text.lines().enumerate() {
+        let line = normalize_meituan_line(raw);
+        if !line.chars().any(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let parsed = parse_line(&line).and_then(|p| {
+            if items.iter().any(|it| it.coupon_no.as_deref() == Some(p.0.as_str())) {
+                Err(String::from("duplicate_coupon_no"))
+            } else {
+                Ok(p)
+            }
+        });
+        match parsed {
+            Ok((coupon_no, amount, discount, financial)) => items.push(MeituanOrderImportItem {
+                date_ymd: date_ymd.to_string(),
+                shift: shift.to_string(),
+                employee: employee.to_string(),
+                coupon_no: Some(coupon_no),
+                raw_text: line,
+                amount,
+                discount,
+                financial,
+                // What the voucher is worth at the bar: its price.
+                bar_total: amount,
+                bundle_id: bundle_id.map(str::to_string),
+            }),
+            Err(error) => errors.push(MeituanParseError { line: n + 1, text: line, error }),
+        }
+    }
+    MeituanParseResult { items, errors }
+}
+
+fn parse_input(input: &MeituanTextInput) -> Result<MeituanParseResult, String> {
+    let date_ymd = input.date_ymd.trim();
+    let shift = input.shift.trim();
+    let employee = input.employee.trim();
+    if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let bundle_id = input.bundle_id.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    Ok(parse_meituan_text(&input.text, date_ymd, shift, employee, bundle_id))
+}
+
+/// Parses pasted verification records without saving them, for a preview.
+#[tauri::command]
+pub fn meituan_orders_parse(_app: AppHandle, input: MeituanTextInput) -> Result<MeituanParseResult, String> {
+    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    parse_input(&input)
+}
+
+/// Parses pasted verification records and saves the ones that read cleanly.
+#[tauri::command]
+pub fn meituan_orders_import_text(app: AppHandle, input: MeituanTextInput) -> Result<MeituanTextImportResult, String> {
+    let mut conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let parsed = parse_input(&input)?;
+    let total = parsed.items.len();
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let inserted = insert_meituan_orders(&tx, parsed.items, now_ts()?)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(MeituanTextImportResult { inserted, duplicates: total - inserted, errors: parsed.errors })
+}
+
+// Settlement statements. Meituan pays verified vouchers out to the merchant
+// account a few days later; its statement export lists one voucher per row.
+// Each settled voucher is kept once, by coupon number, and the sum of them is
+// the Meituan wallet balance.
+
+/// Statement fields, as (field, headers Meituan's exports use for it).
+const SETTLEMENT_COLUMNS: [(&str, &[&str]); 3] = [
+    ("coupon_no", &["券码", "券号", "团购券码", "验证码", "coupon_no"]),
+    ("amount", &["结算金额", "结算价", "应结金额", "商家应得", "amount"]),
+    ("settle_date", &["结算日期", "结算时间", "账单日期", "settle_date"]),
+];
+
+/// Meituan exports CSV in GBK as often as in UTF-8.
+fn decode_statement(format: &str, content: &[u8]) -> Vec<u8> {
+    if format != "csv" || std::str::from_utf8(content).is_ok() {
+        return content.to_vec();
+    }
+    encoding_rs::GBK.decode(content).0.into_owned().into_bytes()
+}
+
+/// Field name -> column index, or None when the row is not the header.
+fn settlement_columns(row: &[String], mapping: Option<&HashMap<String, String>>) -> Option<HashMap<&'static str, usize>> {
+    let find = |label: &str| row.iter().position(|h| h.eq_ignore_ascii_case(label.trim()));
+    let mut out = HashMap::new();
+    for (field, titles) in SETTLEMENT_COLUMNS.iter() {
+        let mapped = mapping.and_then(|m| m.get(*field)).map(|s| s.trim()).filter(|s| !s.is_empty());
+        let idx = match mapped {
+            Some(label) => find(label),
+            None => titles.iter().find_map(|t| find(t)),
+        };
+        if let Some(i) = idx {
+            out.insert(*field, i);
+        }
+    }
+    (out.contains_key("coupon_no") && out.contains_key("amount")).then_some(out)
+}
+
+/// Reads a statement date: 2025-10-10, 2025/10/10 09:00, or an Excel date serial.
+fn settle_ymd(conn: &Connection, cell: &str) -> Option<String> {
+    let ymd = if let Ok(serial) = cell.parse::<f64>() {
+        conn.query_row("SELECT date('1899-12-30', ?1)", [format!("+{} days", serial.floor() as i64)], |r| r.get::<_, Option<String>>(0))
+            .ok()
+            .flatten()?
+    } else {
+        let day = cell.split([' ', 'T']).next().unwrap_or_default().replace(['/', '.'], "-");
+        let parts: Vec<u32> = day.split('-').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        match parts.as_slice() {
+            [y, m, d] => format!("{y:04}-{m:02}-{d:02}"),
+            _ => return None,
+        }
+    };
+    let valid: bool = conn.query_row("SELECT date(?1) IS ?1", [&ymd], |r| r.get(0)).unwrap_or(false);
+    valid.then_some(ymd)
+}
+
+fn import_settlement_row(
+    conn: &Connection,
+    cols: &HashMap<&'static str, usize>,
+    cells: &[String],
+    default_date: Option<&str>,
+    seen: &mut HashSet<String>,
+) -> Result<(MeituanSettlementImportRow, Option<String>), String> {
+    let cell = |field: &str| cols.get(field).and_then(|i| cells.get(*i)).map(|s| s.trim()).unwrap_or_default();
+    let mut errors = Vec::new();
+
+    let raw_no = cell("coupon_no");
+    let digits: String = raw_no.chars().filter(|c| c.is_ascii_digit()).collect();
+    // Spreadsheets turn long numbers into 1.23457E+11; those cannot be recovered.
+    let coupon_no = if raw_no.contains(['e', 'E']) || !(COUPON_MIN_DIGITS..=COUPON_MAX_DIGITS).contains(&digits.len()) {
+        errors.push(String::from("invalid_coupon_no"));
+        None
+    } else if !seen.insert(digits.clone()) {
+        errors.push(String::from("duplicate_coupon_no"));
+        Some(digits)
+    } else {
+        Some(digits)
+    };
+
+    let raw_amount: String = cell("amount").chars().filter(|c| !matches!(c, '¥' | '￥' | ',' | ' ')).collect();
+    let amount = Money::parse_yuan(&raw_amount);
+    if amount.is_none() {
+        errors.push(String::from("invalid_amount"));
+    }
+
+    let date_cell = cell("settle_date");
+    let settle_date = if date_cell.is_empty() {
+        default_date.map(str::to_string)
+    } else {
+        settle_ymd(conn, date_cell)
+    };
+    if settle_date.is_none() {
+        errors.push(String::from(if date_cell.is_empty() { "missing_settle_date" } else { "invalid_settle_date" }));
+    }
+
+    let mut action = "error";
+    if let (Some(no), Some(amt), true) = (&coupon_no, amount, errors.is_empty()) {
+        let existing: Option<Money> = conn
+            .query_row("SELECT amount FROM meituan_settlements WHERE coupon_no = ?1", [no], |r| r.get(0))
+            .optional()
+            .map_err(|e| format!("query settlement: {e}"))?;
+        action = match existing {
+            Some(prev) if prev == amt => "skip",
+            Some(_) => {
+                errors.push(String::from("already_settled"));
+                "error"
+            }
+            None => "create",
+        };
+    }
+    let row = MeituanSettlementImportRow {
+        row: 0,
+        action: action.to_string(),
+        coupon_no,
+        amount,
+        errors,
+    };
+    Ok((row, settle_date))
+}
+
+/// Imports a statement inside one transaction. A dry run, or any row with
+/// errors, rolls everything back, so the report doubles as the preview.
+pub fn import_settlements(conn: &mut Connection, input: &MeituanSettlementImportInput, actor_id: &str) -> Result<MeituanSettlementImportReport, String> {
+    let format = input.format.trim().to_lowercase();
+    let table = read_table(&format, &decode_statement(&format, &input.content))?;
+    // Exports open with a few title lines; the header is the first row that
+    // names the coupon number and amount columns.
+    let (header_at, cols) = table
+        .iter()
+        .enumerate()
+        .find_map(|(i, row)| settlement_columns(row, input.mapping.as_ref()).map(|c| (i, c)))
+        .ok_or_else(|| String::from("missing_coupon_no_column"))?;
+    let default_date = input.settle_date.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if default_date.is_some_and(|d| settle_ymd(conn, d).as_deref() != Some(d)) {
+        return Err(String::from("invalid settle_date"));
+    }
+    let dry_run = input.dry_run.unwrap_or(true);
+    let statement_id = Uuid::new_v4().to_string();
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+    let mut total = Money::ZERO;
+    for (i, cells) in table.iter().enumerate().skip(header_at + 1) {
+        // Blank rows and the 合计 line at the bottom carry no coupon number.
+        if cols.get("coupon_no").and_then(|c| cells.get(*c)).is_none_or(|c| !c.chars().any(|ch| ch.is_ascii_digit())) {
+            continue;
+        }
+        let (mut r, settle_date) = import_settlement_row(&tx, &cols, cells, default_date, &mut seen)?;
+        r.row = i + 1;
+        if let (true, Some(no), Some(amount), Some(date)) = (r.action == "create", &r.coupon_no, r.amount, settle_date) {
+            tx.execute(
+                "INSERT INTO meituan_settlements(id, coupon_no, settle_date, amount, statement_id, created_by, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![Uuid::new_v4().to_string(), no, date, amount, statement_id, actor_id, now],
+            )
+            .map_err(|e| format!("insert settlement: {e}"))?;
+            total += amount;
+        }
+        rows.push(r);
+    }
+
+    let count = |action: &str| rows.iter().filter(|r| r.action == action).count();
+    let errors = count("error");
+    let applied = !dry_run && errors == 0;
+    if applied {
+        tx.commit().map_err(|e| format!("commit: {e}"))?;
+    }
+    Ok(MeituanSettlementImportReport {
+        dry_run,
+        applied,
+        statement_id,
+        created: count("create"),
+        skipped: count("skip"),
+        errors,
+        total,
+        rows,
+    })
+}
+
+#[tauri::command]
+pub fn meituan_settlements_import(app: AppHandle, input: MeituanSettlementImportInput) -> Result<MeituanSettlementImportReport, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+    import_settlements(&mut conn, &input, &actor_id)
+}
+
+fn map_settlement_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<MeituanSettlementRow> {
+    Ok(MeituanSettlementRow {
+        id: r.get(0)?,
+        coupon_no: r.get(1)?,
+        settle_date: r.get(2)?,
+        amount: r.get(3)?,
+        statement_id: r.get(4)?,
+        created_at: r.get(5)?,
+    })
+}
+
+/// Vouchers verified from `date_from` to `date_to` checked against the
+/// statements: not settled yet, settled for another amount, and statement
+/// rows in the range that were never verified here.
+pub fn meituan_reconcile(conn: &Connection, date_from: &str, date_to: &str) -> Result<MeituanReconciliation, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MEITUAN_ORDER_COLUMNS} FROM meituan_orders o \
+             WHERE date_ymd >= ?1 AND date_ymd <= ?2 AND coupon_no IS NOT NULL AND coupon_no != '' \
+               AND NOT EXISTS (SELECT 1 FROM meituan_settlements s WHERE s.coupon_no = o.coupon_no) \
+             ORDER BY date_ymd ASC, created_at ASC"
+        ))
+        .map_err(|e| format!("prepare unsettled: {e}"))?;
+    let unsettled = stmt
+        .query_map([date_from, date_to], map_meituan_order_row)
+        .map_err(|e| format!("query unsettled: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("row: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, coupon_no, settle_date, amount, statement_id, created_at FROM meituan_settlements s \
+             WHERE settle_date >= ?1 AND settle_date <= ?2 \
+               AND NOT EXISTS (SELECT 1 FROM meituan_orders o WHERE o.coupon_no = s.coupon_no) \
+             ORDER BY settle_date ASC, coupon_no ASC",
+        )
+        .map_err(|e| format!("prepare unrecorded: {e}"))?;
+    let unrecorded = stmt
+        .query_map([date_from, date_to], map_settlement_row)
+        .map_err(|e| format!("query unrecorded: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("row: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.coupon_no, o.date_ymd, s.settle_date, o.financial, s.amount \
+             FROM meituan_orders o JOIN meituan_settlements s ON s.coupon_no = o.coupon_no \
+             WHERE o.date_ymd >= ?1 AND o.date_ymd <= ?2 AND s.amount != o.financial \
+             ORDER BY o.date_ymd ASC, o.coupon_no ASC",
+        )
+        .map_err(|e| format!("prepare mismatched: {e}"))?;
+    let mismatched = stmt
+        .query_map([date_from, date_to], |r| {
+            let expected: Money = r.get(3)?;
+            let settled: Money = r.get(4)?;
+            Ok(MeituanSettlementMismatch {
+                coupon_no: r.get(0)?,
+                date_ymd: r.get(1)?,
+                settle_date: r.get(2)?,
+                expected,
+                settled,
+                difference: settled - expected,
+            })
+        })
+        .map_err(|e| format!("query mismatched: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("row: {e}"))?;
+
+    let (expected_total, settled_total): (Money, Money) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(o.financial), 0), COALESCE(SUM(s.amount), 0) \
+             FROM meituan_orders o LEFT JOIN meituan_settlements s ON s.coupon_no = o.coupon_no \
+             WHERE o.date_ymd >= ?1 AND o.date_ymd <= ?2 AND o.coupon_no IS NOT NULL AND o.coupon_no != ''",
+            [date_from, date_to],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .map_err(|e| format!("query totals: {e}"))?;
+
+    Ok(MeituanReconciliation {
+        date_from: date_from.to_string(),
+        date_to: date_to.to_string(),
+        unsettled_total: unsettled.iter().map(|o| o.financial).sum(),
+        unrecorded_total: unrecorded.iter().map(|s| s.amount).sum(),
+        unsettled,
+        unrecorded,
+        mismatched,
+        expected_total,
+        settled_total,
+    })
+}
+
+#[tauri::command]
+pub fn meituan_reconciliation(app: AppHandle, token: String, date_from: String, date_to: String) -> Result<MeituanReconciliation, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let (date_from, date_to) = (date_from.trim(), date_to.trim());
+    if date_from.is_empty() || date_to.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    meituan_reconcile(&conn, date_from, date_to)
+}
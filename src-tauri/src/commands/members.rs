@@ -0,0 +1,336 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts, now_ymd};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::constants::{MEMBER_TIERS, PAYMENT_MEMBER, PAYMENT_METHODS, WALLET_ENTRY_KINDS};
+use crate::models::*;
+use crate::commands::payments::Tender;
+use crate::commands::idempotency::{replayed_result, remember_result};
+use crate::money::Money;
+
+// Members carry a stored-value balance. Every change to it is a row in
+// member_wallet_entries (top-up, spend, refund, adjustment) holding the signed
+// amount and the balance after it, so the ledger always sums to `balance`.
+// Top-ups are a liability until spent: they never reach accounting_entries,
+// and revenue is booked by the sale that spends them like any other payment.
+
+const MEMBER_COLUMNS: &str = "id, phone, name, tier, balance, points, note, is_active, created_at, updated_at";
+
+fn member_from_row(r: &rusqlite::Row) -> rusqlite::Result<MemberRow> {
+    Ok(MemberRow {
+        id: r.get(0)?,
+        phone: r.get(1)?,
+        name: r.get(2)?,
+        tier: r.get(3)?,
+        balance: r.get(4)?,
+        points: r.get(5)?,
+        note: r.get(6)?,
+        is_active: r.get::<_, i64>(7)? != 0,
+        created_at: r.get(8)?,
+        updated_at: r.get(9)?,
+    })
+}
+
+pub fn load_member(conn: &Connection, id: &str) -> Result<MemberRow, String> {
+    conn.query_row(&format!("SELECT {MEMBER_COLUMNS} FROM members WHERE id = ?1"), [id], member_from_row)
+        .optional()
+        .map_err(|e| format!("query member: {e}"))?
+        .ok_or_else(|| String::from("member_not_found"))
+}
+
+/// The member a sale is rung up for; they must still be active.
+pub fn require_active_member(conn: &Connection, id: &str) -> Result<MemberRow, String> {
+    let member = load_member(conn, id)?;
+    if !member.is_active {
+        return Err(String::from("member_inactive"));
+    }
+    Ok(member)
+}
+
+/// What the tenders put on (positive) or give back to (negative) a member balance.
+pub fn member_paid(tenders: &[Tender]) -> Money {
+    tenders.iter().filter(|t| t.method == PAYMENT_MEMBER).map(|t| t.amount).sum()
+}
+
+/// A movement to book against a member's balance.
+pub struct WalletPosting<'a> {
+    pub kind: &'a str,
+    /// Signed: top-ups and refunds add, spends take off.
+    pub amount: Money,
+    pub method: Option<&'a str>,
+    pub order_id: Option<&'a str>,
+    pub date_ymd: &'a str,
+    pub shift: Option<&'a str>,
+    pub note: Option<&'a str>,
+    pub actor_id: &'a str,
+}
+
+/// Moves a member's balance and records the ledger entry. The balance can't go
+/// below zero; the check and the update are one statement, so two tills
+/// spending the same balance can't both succeed. Returns the entry id.
+pub fn post_wallet_entry(conn: &Connection, member_id: &str, p: &WalletPosting, now: i64) -> Result<String, String> {
+    let n = conn
+        .execute(
+            "UPDATE members SET balance = balance + ?2, updated_at = ?3 WHERE id = ?1 AND balance + ?2 >= 0",
+            params![member_id, p.amount, now],
+        )
+        .map_err(|e| format!("update member balance: {e}"))?;
+    if n == 0 {
+        load_member(conn, member_id)?;
+        return Err(String::from("insufficient_balance"));
+    }
+    let balance_after: Money = conn
+        .query_row("SELECT balance FROM members WHERE id = ?1", [member_id], |r| r.get(0))
+        .map_err(|e| format!("query member balance: {e}"))?;
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO member_wallet_entries(id, member_id, kind, amount, balance_after, method, order_id, date_ymd, shift, note, actor_id, created_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![id, member_id, p.kind, p.amount, balance_after, p.method, p.order_id, p.date_ymd, p.shift, p.note, p.actor_id, now],
+    )
+    .map_err(|e| format!("insert wallet entry: {e}"))?;
+    Ok(id)
+}
+
+fn load_wallet_entries(conn: &Connection, filter: &str, args: &[&dyn rusqlite::ToSql]) -> Result<Vec<WalletEntryRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, member_id, kind, amount, balance_after, method, order_id, date_ymd, shift, note, actor_id, created_at \
+             FROM member_wallet_entries WHERE {filter}"
+        ))
+        .map_err(|e| format!("prepare wallet entries: {e}"))?;
+    let rows = stmt
+        .query_map(args, |r| {
+            Ok(WalletEntryRow {
+                id: r.get(0)?,
+                member_id: r.get(1)?,
+                kind: r.get(2)?,
+                amount: r.get(3)?,
+                balance_after: r.get(4)?,
+                method: r.get(5)?,
+                order_id: r.get(6)?,
+                date_ymd: r.get(7)?,
+                shift: r.get(8)?,
+                note: r.get(9)?,
+                actor_id: r.get(10)?,
+                created_at: r.get(11)?,
+            })
+        })
+        .map_err(|e| format!("query wallet entries: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row wallet entries: {e}"))?);
+    }
+    Ok(out)
+}
+
+fn load_wallet_entry(conn: &Connection, id: &str) -> Result<WalletEntryRow, String> {
+    load_wallet_entries(conn, "id = ?1", &[&id])?
+        .pop()
+        .ok_or_else(|| String::from("not_found"))
+}
+
+/// Member balance sold in `[date_from, date_to]`.
+pub fn member_topups_between(conn: &Connection, date_from: &str, date_to: &str) -> Result<Money, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM member_wallet_entries WHERE kind = 'topup' AND date_ymd >= ?1 AND date_ymd <= ?2",
+        params![date_from, date_to],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("query member topups: {e}"))
+}
+
+/// Member balances outstanding at the end of `date_ymd`.
+pub fn member_liability_at(conn: &Connection, date_ymd: &str) -> Result<Money, String> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM member_wallet_entries WHERE date_ymd <= ?1",
+        [date_ymd],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("query member liability: {e}"))
+}
+
+/// Members whose phone or name contains `query`, or everyone, by name.
+#[tauri::command]
+pub fn members_list(app: AppHandle, token: String, query: Option<String>) -> Result<Vec<MemberRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let pattern = query.map(|q| q.trim().to_string()).filter(|q| !q.is_empty()).map(|q| format!("%{q}%"));
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {MEMBER_COLUMNS} FROM members WHERE ?1 IS NULL OR phone LIKE ?1 OR name LIKE ?1 ORDER BY name ASC, phone ASC"
+        ))
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt.query_map([pattern], member_from_row).map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn member_get(app: AppHandle, token: String, id: String) -> Result<MemberRow, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_member(&conn, id.trim())
+}
+
+/// Finds a member by the phone number they give at the till.
+#[tauri::command]
+pub fn member_lookup(app: AppHandle, token: String, phone: String) -> Result<MemberRow, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    conn.query_row(&format!("SELECT {MEMBER_COLUMNS} FROM members WHERE phone = ?1"), [phone.trim()], member_from_row)
+        .optional()
+        .map_err(|e| format!("query member: {e}"))?
+        .ok_or_else(|| String::from("member_not_found"))
+}
+
+/// Registers a member or updates one; the balance only moves through the ledger.
+#[tauri::command]
+pub fn member_upsert(app: AppHandle, input: MemberUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let phone = input.phone.trim().to_string();
+    let name = input.name.trim().to_string();
+    if phone.is_empty() || name.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let tier = input.tier.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).unwrap_or_else(|| MEMBER_TIERS[0].to_string());
+    if !MEMBER_TIERS.contains(&tier.as_str()) {
+        return Err(String::from("invalid"));
+    }
+    let note = input.note.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+    let map_unique = |e: rusqlite::Error| {
+        if e.to_string().contains("members.phone") { String::from("duplicate_phone") } else { format!("save member: {e}") }
+    };
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO members(id, phone, name, tier, balance, note, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8)",
+            params![id, phone, name, tier, note, is_active, now, now],
+        )
+        .map_err(map_unique)?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE members SET phone = ?2, name = ?3, tier = ?4, note = ?5, is_active = ?6, updated_at = ?7 WHERE id = ?1",
+            params![id, phone, name, tier, note, is_active, now],
+        )
+        .map_err(map_unique)?;
+    if n == 0 {
+        return Err(String::from("member_not_found"));
+    }
+    Ok(id)
+}
+
+/// A member's ledger, newest first, optionally only entries of `kind`.
+#[tauri::command]
+pub fn member_wallet_entries(app: AppHandle, token: String, member_id: String, kind: Option<String>, limit: Option<i64>) -> Result<Vec<WalletEntryRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    let kind = kind.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if kind.is_some_and(|k| !WALLET_ENTRY_KINDS.contains(&k)) {
+        return Err(String::from("invalid"));
+    }
+    load_wallet_entries(
+        &conn,
+        "member_id = ?1 AND (?2 IS NULL OR kind = ?2) ORDER BY created_at DESC, rowid DESC LIMIT ?3",
+        &[&member_id.trim(), &kind, &limit],
+    )
+}
+
+/// Sells stored value to a member. The money counts towards the shift's
+/// takings but not its sales.
+#[tauri::command]
+pub fn member_topup(app: AppHandle, input: MemberTopupInput) -> Result<WalletEntryRow, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let date_ymd = input.date_ymd.trim().to_string();
+    let shift = input.shift.trim().to_string();
+    if date_ymd.is_empty() || shift.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let method = input.method.trim();
+    if !PAYMENT_METHODS.contains(&method) || method == PAYMENT_MEMBER {
+        return Err(format!("invalid payment: {method}"));
+    }
+    if input.amount <= Money::ZERO {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| format!("tx: {e}"))?;
+    if let Some(id) = replayed_result(&tx, "member_topup", input.idempotency_key.as_deref(), now)? {
+        return load_wallet_entry(&tx, &id);
+    }
+    let member_id = input.member_id.trim();
+    require_active_member(&tx, member_id)?;
+    let note = input.note.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let id = post_wallet_entry(
+        &tx,
+        member_id,
+        &WalletPosting {
+            kind: "topup",
+            amount: input.amount,
+            method: Some(method),
+            order_id: None,
+            date_ymd: &date_ymd,
+            shift: Some(&shift),
+            note,
+            actor_id: &actor_id,
+        },
+        now,
+    )?;
+    remember_result(&tx, "member_topup", input.idempotency_key.as_deref(), &id, now)?;
+    let entry = load_wallet_entry(&tx, &id)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(entry)
+}
+
+/// Corrects a balance by hand, e.g. a top-up entered twice. Admins only, with a reason.
+#[tauri::command]
+pub fn member_adjust(app: AppHandle, input: MemberAdjustInput) -> Result<WalletEntryRow, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let reason = input.reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if input.amount.is_zero() {
+        return Err(String::from("invalid"));
+    }
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let id = post_wallet_entry(
+        &tx,
+        input.member_id.trim(),
+        &WalletPosting {
+            kind: "adjust",
+            amount: input.amount,
+            method: None,
+            order_id: None,
+            date_ymd: &now_ymd()?,
+            shift: None,
+            note: Some(&reason),
+            actor_id: &actor_id,
+        },
+        now,
+    )?;
+    let entry = load_wallet_entry(&tx, &id)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(entry)
+}
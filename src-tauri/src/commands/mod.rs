@@ -6,6 +6,27 @@ pub mod kv;
 pub mod finance;
 pub mod coupons;
 pub mod cloud;
+pub mod inventory;
+pub mod units;
+pub mod modifiers;
+pub mod bundles;
+pub mod recipes;
+pub mod categories;
+pub mod pricing;
+pub mod promotions;
+pub mod catalog_io;
+pub mod payments;
+pub mod refunds;
+pub mod idempotency;
+pub mod seats;
+pub mod carts;
+pub mod printing;
+pub mod receipts;
+pub mod receipt_links;
+pub mod display;
+pub mod members;
+pub mod loyalty;
+pub mod meituan;
 
 pub use system::*;
 pub use auth::*;
@@ -15,3 +36,23 @@ pub use kv::*;
 pub use finance::*;
 pub use coupons::*;
 pub use cloud::*;
+pub use inventory::*;
+pub use units::*;
+pub use modifiers::*;
+pub use bundles::*;
+pub use recipes::*;
+pub use categories::*;
+pub use pricing::*;
+pub use promotions::*;
+pub use catalog_io::*;
+pub use payments::*;
+pub use refunds::*;
+pub use seats::*;
+pub use carts::*;
+pub use printing::*;
+pub use receipts::*;
+pub use receipt_links::*;
+pub use display::*;
+pub use members::*;
+pub use loyalty::*;
+pub use meituan::*;
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::commands::products::load_sellable_product;
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::*;
+use crate::money::Money;
+
+// Modifier groups (规格 / 冰度 / 加料) are shared and attached to products.
+// 'single' groups take at most one option, 'multi' groups any number.
+const SELECTIONS: [&str; 2] = ["single", "multi"];
+
+/// An option picked for one sales line, resolved against the product.
+#[derive(Debug, Clone)]
+pub struct SelectedModifier {
+    pub option_id: String,
+    pub group_name: String,
+    pub option_name: String,
+    pub price_delta: Money,
+    pub stock_product_id: Option<String>,
+    pub stock_quantity: f64,
+}
+
+fn map_option_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ModifierOptionRow> {
+    Ok(ModifierOptionRow {
+        id: r.get(0)?,
+        group_id: r.get(1)?,
+        name: r.get(2)?,
+        price_delta: r.get(3)?,
+        stock_product_id: r.get(4)?,
+        stock_quantity: r.get(5)?,
+        sort_order: r.get(6)?,
+        is_active: r.get::<_, i64>(7)? != 0,
+    })
+}
+
+pub fn load_groups(conn: &Connection, product_id: Option<&str>) -> Result<Vec<ModifierGroupRow>, String> {
+    let sql = match product_id {
+        Some(_) => "SELECT g.id, g.name, g.selection, g.is_required, g.sort_order, g.is_active \
+                    FROM product_modifier_groups pg JOIN modifier_groups g ON g.id = pg.group_id \
+                    WHERE pg.product_id = ?1 AND g.is_active = 1 ORDER BY pg.sort_order ASC, g.sort_order ASC",
+        None => "SELECT id, name, selection, is_required, sort_order, is_active FROM modifier_groups ORDER BY sort_order ASC, name ASC",
+    };
+    let mut stmt = conn.prepare(sql).map_err(|e| format!("prepare groups: {e}"))?;
+    let map_group = |r: &rusqlite::Row<'_>| {
+        Ok(ModifierGroupRow {
+            id: r.get(0)?,
+            name: r.get(1)?,
+            selection: r.get(2)?,
+            is_required: r.get::<_, i64>(3)? != 0,
+            sort_order: r.get(4)?,
+            is_active: r.get::<_, i64>(5)? != 0,
+            options: Vec::new(),
+        })
+    };
+    let rows = match product_id {
+        Some(pid) => stmt.query_map([pid], map_group),
+        None => stmt.query_map([], map_group),
+    }
+    .map_err(|e| format!("query groups: {e}"))?;
+    let mut groups = Vec::new();
+    for r in rows {
+        groups.push(r.map_err(|e| format!("row groups: {e}"))?);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, group_id, name, price_delta, stock_product_id, stock_quantity, sort_order, is_active \
+             FROM modifier_options WHERE group_id = ?1 ORDER BY sort_order ASC, name ASC",
+        )
+        .map_err(|e| format!("prepare options: {e}"))?;
+    for g in groups.iter_mut() {
+        let rows = stmt.query_map([&g.id], map_option_row).map_err(|e| format!("query options: {e}"))?;
+        for r in rows {
+            let opt = r.map_err(|e| format!("row options: {e}"))?;
+            if product_id.is_none() || opt.is_active {
+                g.options.push(opt);
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// Validates the options picked for a product and returns them in group order.
+pub fn resolve_modifiers(conn: &Connection, product_id: &str, option_ids: &[String]) -> Result<Vec<SelectedModifier>, String> {
+    let groups = load_groups(conn, Some(product_id))?;
+    let mut picked: HashMap<&str, usize> = HashMap::new();
+    let mut out = Vec::new();
+
+    for g in groups.iter() {
+        for opt in g.options.iter().filter(|o| option_ids.iter().any(|id| id.trim() == o.id)) {
+            *picked.entry(g.id.as_str()).or_insert(0) += 1;
+            out.push(SelectedModifier {
+                option_id: opt.id.clone(),
+                group_name: g.name.clone(),
+                option_name: opt.name.clone(),
+                price_delta: opt.price_delta,
+                stock_product_id: opt.stock_product_id.clone(),
+                stock_quantity: opt.stock_quantity,
+            });
+        }
+        let n = picked.get(g.id.as_str()).copied().unwrap_or(0);
+        if g.selection == "single" && n > 1 {
+            return Err(format!("modifier_single_select: {}", g.name));
+        }
+        if g.is_required && n == 0 {
+            return Err(format!("modifier_required: {}", g.name));
+        }
+    }
+
+    let requested = option_ids.iter().filter(|id| !id.trim().is_empty()).count();
+    if out.len() != requested {
+        return Err(String::from("modifier_not_allowed"));
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn modifier_groups_list(app: AppHandle, token: String, product_id: Option<String>) -> Result<Vec<ModifierGroupRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let pid = product_id.unwrap_or_default().trim().to_string();
+    load_groups(&conn, if pid.is_empty() { None } else { Some(pid.as_str()) })
+}
+
+#[tauri::command]
+pub fn modifier_group_upsert(app: AppHandle, input: ModifierGroupUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let selection = input.selection.unwrap_or_default().trim().to_string();
+    let selection = if selection.is_empty() { String::from("single") } else { selection };
+    if name.is_empty() || !SELECTIONS.contains(&selection.as_str()) {
+        return Err(String::from("invalid"));
+    }
+    let is_required = if input.is_required.unwrap_or(false) { 1i64 } else { 0i64 };
+    let sort_order = input.sort_order.unwrap_or(0);
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO modifier_groups(id, name, selection, is_required, sort_order, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![id, name, selection, is_required, sort_order, is_active, now, now],
+        )
+        .map_err(|e| format!("insert modifier_groups: {e}"))?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE modifier_groups SET name = ?2, selection = ?3, is_required = ?4, sort_order = ?5, is_active = ?6, updated_at = ?7 WHERE id = ?1",
+            params![id, name, selection, is_required, sort_order, is_active, now],
+        )
+        .map_err(|e| format!("update modifier_groups: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn modifier_option_upsert(app: AppHandle, input: ModifierOptionUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let group_id = input.group_id.trim().to_string();
+    let name = input.name.trim().to_string();
+    if group_id.is_empty() || name.is_empty() {
+        return Err(String::from("invalid"));
+    }
+    let group_exists: Option<i64> = conn
+        .query_row("SELECT 1 FROM modifier_groups WHERE id = ?1", [&group_id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query group: {e}"))?;
+    if group_exists.is_none() {
+        return Err(String::from("group_not_found"));
+    }
+    let stock_product_id = input.stock_product_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    if let Some(pid) = stock_product_id.as_deref() {
+        if load_sellable_product(&conn, pid)?.is_none() {
+            return Err(format!("product_not_found: {pid}"));
+        }
+    }
+    let stock_quantity = if stock_product_id.is_some() { input.stock_quantity.unwrap_or(1.0) } else { 0.0 };
+    let sort_order = input.sort_order.unwrap_or(0);
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO modifier_options(id, group_id, name, price_delta, stock_product_id, stock_quantity, sort_order, is_active, created_at, updated_at) \
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![id, group_id, name, input.price_delta, stock_product_id, stock_quantity, sort_order, is_active, now, now],
+        )
+        .map_err(|e| format!("insert modifier_options: {e}"))?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE modifier_options SET group_id = ?2, name = ?3, price_delta = ?4, stock_product_id = ?5, stock_quantity = ?6, sort_order = ?7, is_active = ?8, updated_at = ?9 WHERE id = ?1",
+            params![id, group_id, name, input.price_delta, stock_product_id, stock_quantity, sort_order, is_active, now],
+        )
+        .map_err(|e| format!("update modifier_options: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn product_modifier_groups_set(app: AppHandle, input: ProductModifierGroupsSetInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let product_id = input.product_id.trim().to_string();
+    if product_id.is_empty() {
+        return Err(String::from("invalid"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    tx.execute("DELETE FROM product_modifier_groups WHERE product_id = ?1", [&product_id])
+        .map_err(|e| format!("clear product_modifier_groups: {e}"))?;
+    for (i, gid) in input.group_ids.iter().enumerate() {
+        let gid = gid.trim();
+        if gid.is_empty() { continue; }
+        tx.execute(
+            "INSERT OR IGNORE INTO product_modifier_groups(product_id, group_id, sort_order) VALUES(?1, ?2, ?3)",
+            params![product_id, gid, i as i64],
+        )
+        .map_err(|e| format!("insert product_modifier_groups: {e}"))?;
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn sales_item_modifiers_list(app: AppHandle, token: String, order_id: String) -> Result<Vec<SalesItemModifierRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, sales_item_id, order_id, option_id, group_name, option_name, price_delta, stock_quantity, created_at \
+             FROM sales_item_modifiers WHERE order_id = ?1 ORDER BY created_at ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([order_id.trim()], |r| {
+            Ok(SalesItemModifierRow {
+                id: r.get(0)?,
+                sales_item_id: r.get(1)?,
+                order_id: r.get(2)?,
+                option_id: r.get(3)?,
+                group_name: r.get(4)?,
+                option_name: r.get(5)?,
+                price_delta: r.get(6)?,
+                stock_quantity: r.get(7)?,
+                created_at: r.get(8)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
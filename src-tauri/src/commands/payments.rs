@@ -0,0 +1,259 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+use crate::db::open_db;
+use crate::state::auth_resolve_account_id;
+use crate::constants::{PAYMENT_CASH, PAYMENT_MEMBER, PAYMENT_METHODS};
+use crate::models::{PosPayment, PaymentRow, PaymentMethodStat, ShiftPaymentSummary};
+use crate::money::Money;
+
+/// A settled payment line: `amount` is what the order keeps, `tendered` what
+/// the customer handed over; only cash can exceed its share and give change.
+#[derive(Debug, Clone)]
+pub struct Tender {
+    pub method: String,
+    pub amount: Money,
+    pub tendered: Money,
+    pub change: Money,
+    pub reference: Option<String>,
+}
+
+/// 抹零: drops the fen ("jiao") or the jiao and fen ("yuan") from the total.
+/// Returns the amount due and the amount waived.
+pub fn apply_rounding(total: Money, mode: Option<&str>) -> Result<(Money, Money), String> {
+    let fen = total.fen();
+    let step = match mode.map(str::trim).unwrap_or("") {
+        "" | "none" => 1,
+        "jiao" => 10,
+        "yuan" => 100,
+        _ => return Err(String::from("invalid rounding")),
+    };
+    let due = if fen > 0 { fen - fen % step } else { fen };
+    Ok((Money::from_fen(due), Money::from_fen(fen - due)))
+}
+
+/// Checks the payments against the amount due and works out the change.
+/// Without any payments the order is taken as paid exactly in cash.
+pub fn settle_tenders(due: Money, payments: Option<&[PosPayment]>) -> Result<Vec<Tender>, String> {
+    let payments = match payments.filter(|p| !p.is_empty()) {
+        Some(p) => p,
+        None => {
+            return Ok(vec![Tender {
+                method: PAYMENT_CASH.to_string(),
+                amount: due,
+                tendered: due,
+                change: Money::ZERO,
+                reference: None,
+            }])
+        }
+    };
+
+    let mut paid = Money::ZERO;
+    let mut non_cash = Money::ZERO;
+    let mut cash = Money::ZERO;
+    for p in payments.iter() {
+        let method = p.method.trim();
+        if !PAYMENT_METHODS.contains(&method) || p.amount <= Money::ZERO {
+            return Err(format!("invalid payment: {method}"));
+        }
+        paid += p.amount;
+        if method == PAYMENT_CASH { cash += p.amount } else { non_cash += p.amount }
+    }
+    if non_cash > due {
+        return Err(String::from("overpaid"));
+    }
+    if paid < due {
+        return Err(format!("underpaid: {}", due - paid));
+    }
+    let mut change_left = paid - due;
+    if change_left > cash {
+        return Err(String::from("overpaid"));
+    }
+
+    // Change comes out of the cash lines, last one first.
+    let mut out: Vec<Tender> = Vec::with_capacity(payments.len());
+    for p in payments.iter().rev() {
+        let change = if p.method.trim() == PAYMENT_CASH { change_left.min(p.amount) } else { Money::ZERO };
+        change_left -= change;
+        out.push(Tender {
+            method: p.method.trim().to_string(),
+            amount: p.amount - change,
+            tendered: p.amount,
+            change,
+            reference: p.reference.as_ref().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+        });
+    }
+    out.reverse();
+    Ok(out)
+}
+
+pub fn record_payments(conn: &Connection, order_id: &str, tenders: &[Tender], now: i64) -> Result<(), String> {
+    for t in tenders.iter() {
+        conn.execute(
+            "INSERT INTO payments(id, order_id, method, amount, tendered, change_amount, reference, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![Uuid::new_v4().to_string(), order_id, t.method, t.amount, t.tendered, t.change, t.reference, now],
+        )
+        .map_err(|e| format!("insert payment: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Payments taken during one shift, split into cash (what the drawer should
+/// hold) and electronic, plus member top-ups sold, which are takings but not sales.
+pub fn load_payment_summary(conn: &Connection, date_ymd: &str, shift: &str) -> Result<ShiftPaymentSummary, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.method, COALESCE(SUM(p.amount), 0), COUNT(1) FROM payments p JOIN sales_orders o ON o.id = p.order_id \
+             WHERE o.date_ymd = ?1 AND o.shift = ?2 GROUP BY p.method ORDER BY 2 DESC",
+        )
+        .map_err(|e| format!("prepare payments: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_ymd, shift], |r| {
+            Ok(PaymentMethodStat {
+                method: r.get(0)?,
+                amount: r.get(1)?,
+                count: r.get(2)?,
+            })
+        })
+        .map_err(|e| format!("query payments: {e}"))?;
+    let mut by_method = Vec::new();
+    for r in rows {
+        by_method.push(r.map_err(|e| format!("row payments: {e}"))?);
+    }
+    let rounding: Money = conn
+        .query_row(
+            "SELECT COALESCE(SUM(rounding), 0) FROM sales_orders WHERE date_ymd = ?1 AND shift = ?2",
+            params![date_ymd, shift],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("query rounding: {e}"))?;
+    let (member_topups, member_topups_cash): (Money, Money) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount), 0), COALESCE(SUM(CASE WHEN method = ?3 THEN amount ELSE 0 END), 0) \
+             FROM member_wallet_entries WHERE kind = 'topup' AND date_ymd = ?1 AND shift = ?2",
+            params![date_ymd, shift, PAYMENT_CASH],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .map_err(|e| format!("query member topups: {e}"))?;
+    let cash = by_method.iter().filter(|m| m.method == PAYMENT_CASH).map(|m| m.amount).sum::<Money>();
+    // Spending a member balance brings no money in; it came in with the top-up.
+    let electronic = by_method
+        .iter()
+        .filter(|m| m.method != PAYMENT_CASH && m.method != PAYMENT_MEMBER)
+        .map(|m| m.amount)
+        .sum::<Money>();
+    Ok(ShiftPaymentSummary {
+        date_ymd: date_ymd.to_string(),
+        shift: shift.to_string(),
+        cash,
+        electronic,
+        rounding,
+        by_method,
+        member_topups,
+        member_topups_cash,
+    })
+}
+
+#[tauri::command]
+pub fn payments_list(app: AppHandle, token: String, order_id: String) -> Result<Vec<PaymentRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, order_id, method, amount, tendered, change_amount, reference, created_at \
+             FROM payments WHERE order_id = ?1 ORDER BY created_at ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([order_id.trim()], |r| {
+            Ok(PaymentRow {
+                id: r.get(0)?,
+                order_id: r.get(1)?,
+                method: r.get(2)?,
+                amount: r.get(3)?,
+                tendered: r.get(4)?,
+                change_amount: r.get(5)?,
+                reference: r.get(6)?,
+                created_at: r.get(7)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn shift_payment_summary(app: AppHandle, token: String, date_ymd: String, shift: String) -> Result<ShiftPaymentSummary, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_payment_summary(&conn, date_ymd.trim(), shift.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yuan(s: &str) -> Money {
+        Money::parse_yuan(s).unwrap()
+    }
+
+    fn pay(method: &str, amount: &str) -> PosPayment {
+        PosPayment { method: method.to_string(), amount: yuan(amount), reference: None }
+    }
+
+    #[test]
+    fn rounding_drops_fen_or_jiao() {
+        assert_eq!(apply_rounding(yuan("12.35"), Some("jiao")).unwrap(), (yuan("12.30"), yuan("0.05")));
+        assert_eq!(apply_rounding(yuan("12.35"), Some("yuan")).unwrap(), (yuan("12"), yuan("0.35")));
+        assert_eq!(apply_rounding(yuan("12.35"), None).unwrap(), (yuan("12.35"), Money::ZERO));
+        assert!(apply_rounding(yuan("12.35"), Some("fen")).is_err());
+    }
+
+    #[test]
+    fn rounding_never_rounds_a_half_up() {
+        assert_eq!(apply_rounding(yuan("9.50"), Some("yuan")).unwrap(), (yuan("9"), yuan("0.50")));
+        assert_eq!(apply_rounding(yuan("9.95"), Some("jiao")).unwrap(), (yuan("9.90"), yuan("0.05")));
+        assert_eq!(apply_rounding(yuan("0.50"), Some("yuan")).unwrap(), (Money::ZERO, yuan("0.50")));
+    }
+
+    #[test]
+    fn negative_totals_are_not_rounded() {
+        assert_eq!(apply_rounding(yuan("-12.35"), Some("yuan")).unwrap(), (yuan("-12.35"), Money::ZERO));
+        assert_eq!(apply_rounding(Money::ZERO, Some("jiao")).unwrap(), (Money::ZERO, Money::ZERO));
+    }
+
+    #[test]
+    fn no_payments_is_exact_cash() {
+        let t = settle_tenders(yuan("18"), None).unwrap();
+        assert_eq!(t.len(), 1);
+        assert_eq!((t[0].method.as_str(), t[0].amount, t[0].change), ("cash", yuan("18"), Money::ZERO));
+    }
+
+    #[test]
+    fn split_tenders_give_change_from_the_last_cash_line() {
+        let payments = [pay("cash", "10"), pay("wechat", "10.50"), pay("cash", "3")];
+        let t = settle_tenders(yuan("15.50"), Some(&payments)).unwrap();
+        let got: Vec<(&str, Money, Money, Money)> = t.iter().map(|t| (t.method.as_str(), t.amount, t.tendered, t.change)).collect();
+        assert_eq!(
+            got,
+            [
+                ("cash", yuan("5"), yuan("10"), yuan("5")),
+                ("wechat", yuan("10.50"), yuan("10.50"), Money::ZERO),
+                ("cash", Money::ZERO, yuan("3"), yuan("3")),
+            ]
+        );
+        assert_eq!(t.iter().map(|t| t.amount).sum::<Money>(), yuan("15.50"));
+    }
+
+    #[test]
+    fn tenders_must_cover_the_total_without_electronic_change() {
+        assert_eq!(settle_tenders(yuan("20"), Some(&[pay("cash", "5"), pay("alipay", "10")])).unwrap_err(), "underpaid: 5.00");
+        assert_eq!(settle_tenders(yuan("20"), Some(&[pay("wechat", "25")])).unwrap_err(), "overpaid");
+        assert_eq!(settle_tenders(yuan("20"), Some(&[pay("cash", "2"), pay("wechat", "20.01")])).unwrap_err(), "overpaid");
+        assert!(settle_tenders(yuan("20"), Some(&[pay("card", "20")])).is_err());
+        assert!(settle_tenders(yuan("20"), Some(&[pay("cash", "-20")])).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{ProductPriceRow, ProductPriceScheduleInput};
+use crate::money::Money;
+
+// `product_prices` is the price history of each product. A row applies from
+// `effective_from` until the next row; rows in the future are scheduled
+// changes. `products.unit_price` is kept equal to the price currently in force.
+
+/// The price of a product in force at `ts`, if it has any history.
+pub fn price_at(conn: &Connection, product_id: &str, ts: i64) -> Result<Option<Money>, String> {
+    conn.query_row(
+        "SELECT unit_price FROM product_prices WHERE product_id = ?1 AND effective_from <= ?2 \
+         ORDER BY effective_from DESC, created_at DESC LIMIT 1",
+        params![product_id, ts],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("query price: {e}"))
+}
+
+/// Appends a price to the history, skipping no-op changes.
+pub fn record_price(
+    conn: &Connection,
+    product_id: &str,
+    unit_price: Money,
+    effective_from: i64,
+    note: Option<&str>,
+    actor_id: Option<&str>,
+) -> Result<(), String> {
+    if price_at(conn, product_id, effective_from)? == Some(unit_price) {
+        return Ok(());
+    }
+    let now = now_ts()?;
+    conn.execute(
+        "INSERT INTO product_prices(id, product_id, unit_price, effective_from, note, actor_id, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![Uuid::new_v4().to_string(), product_id, unit_price, effective_from, note, actor_id, now],
+    )
+    .map_err(|e| format!("insert product_prices: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn product_prices_list(app: AppHandle, token: String, product_id: String) -> Result<Vec<ProductPriceRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, product_id, unit_price, effective_from, note, actor_id, created_at \
+             FROM product_prices WHERE product_id = ?1 ORDER BY effective_from DESC, created_at DESC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([product_id.trim()], |r| {
+            Ok(ProductPriceRow {
+                id: r.get(0)?,
+                product_id: r.get(1)?,
+                unit_price: r.get(2)?,
+                effective_from: r.get(3)?,
+                note: r.get(4)?,
+                actor_id: r.get(5)?,
+                created_at: r.get(6)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Schedules a price change. A past or present `effective_from` applies at once.
+#[tauri::command]
+pub fn product_price_schedule(app: AppHandle, input: ProductPriceScheduleInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let product_id = input.product_id.trim().to_string();
+    if product_id.is_empty() || input.unit_price < Money::ZERO || input.effective_from <= 0 {
+        return Err(String::from("invalid"));
+    }
+    let note = input.note.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let exists: Option<i64> = tx
+        .query_row("SELECT 1 FROM products WHERE id = ?1", [&product_id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query product: {e}"))?;
+    if exists.is_none() {
+        return Err(format!("product_not_found: {product_id}"));
+    }
+    record_price(&tx, &product_id, input.unit_price, input.effective_from, note.as_deref(), Some(&actor_id))?;
+    if let Some(current) = price_at(&tx, &product_id, now)? {
+        tx.execute(
+            "UPDATE products SET unit_price = ?2, updated_at = ?3 WHERE id = ?1",
+            params![product_id, current, now],
+        )
+        .map_err(|e| format!("update price: {e}"))?;
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+/// Withdraws a scheduled change that has not taken effect yet.
+#[tauri::command]
+pub fn product_price_cancel(app: AppHandle, token: String, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    let now = now_ts()?;
+
+    let n = conn
+        .execute(
+            "DELETE FROM product_prices WHERE id = ?1 AND effective_from > ?2",
+            params![id.trim(), now],
+        )
+        .map_err(|e| format!("delete product_prices: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(())
+}
@@ -0,0 +1,340 @@
+use tauri::{AppHandle, Emitter};
+use rusqlite::{params, Connection};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin, log_to_system};
+use crate::constants::{PRINTER_PORT_DEFAULT, PRINT_MAX_ATTEMPTS, PRINT_RETRY_BASE_SECS};
+use crate::models::*;
+use crate::commands::kv::load_kv_json;
+
+// Receipts go to a raw network printer (TCP 9100) through a queue: a job is
+// saved first and a background worker sends it, so a printer that is off or
+// out of paper never holds up the till. Failed sends are retried with a
+// doubling delay until PRINT_MAX_ATTEMPTS, then the job is marked failed and
+// can be retried by hand.
+
+const PRINTER_SETTINGS_KEY: &str = "settings.printer";
+const PRINT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const PRINTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn load_printer_settings(conn: &Connection) -> Result<Option<PrinterSettings>, String> {
+    Ok(load_kv_json(conn, PRINTER_SETTINGS_KEY)?.and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// The printer to send to, if printing is switched on and an address is set.
+pub fn configured_printer(conn: &Connection) -> Result<Option<PrinterSettings>, String> {
+    Ok(load_printer_settings(conn)?.filter(|s| s.enabled && !s.host.trim().is_empty()))
+}
+
+/// Characters per line: 48 on 80 mm paper, 32 on 58 mm.
+pub fn line_width(settings: &PrinterSettings) -> usize {
+    if settings.paper_width == Some(80) { 48 } else { 32 }
+}
+
+pub fn enqueue_print_job(conn: &Connection, order_id: Option<&str>, kind: &str, payload: &[u8], now: i64) -> Result<String, String> {
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO print_jobs(id, order_id, kind, payload, status, attempts, next_attempt_at, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, 'queued', 0, ?5, ?6, ?7)",
+        params![id, order_id, kind, payload, now, now, now],
+    )
+    .map_err(|e| format!("insert print_jobs: {e}"))?;
+    Ok(id)
+}
+
+/// Sends raw bytes to a printer listening on `host:port`.
+pub fn send_to_printer(host: &str, port: u16, payload: &[u8]) -> Result<(), String> {
+    let addr = (host.trim(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("resolve printer: {e}"))?
+        .next()
+        .ok_or_else(|| String::from("resolve printer: no address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, PRINTER_TIMEOUT).map_err(|e| format!("connect printer: {e}"))?;
+    stream.set_write_timeout(Some(PRINTER_TIMEOUT)).map_err(|e| format!("printer timeout: {e}"))?;
+    stream.write_all(payload).map_err(|e| format!("write printer: {e}"))?;
+    stream.flush().map_err(|e| format!("flush printer: {e}"))?;
+    Ok(())
+}
+
+/// Sends every job that is due, oldest first, and returns how many printed.
+pub fn process_print_queue(conn: &Connection, settings: &PrinterSettings, now: i64) -> Result<usize, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, payload, attempts FROM print_jobs WHERE status = 'queued' AND next_attempt_at <= ?1 ORDER BY created_at ASC")
+        .map_err(|e| format!("prepare print_jobs: {e}"))?;
+    let jobs = stmt
+        .query_map([now], |r| Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?, r.get::<_, i64>(2)?)))
+        .map_err(|e| format!("query print_jobs: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("row print_jobs: {e}"))?;
+
+    let port = settings.port.unwrap_or(PRINTER_PORT_DEFAULT);
+    let mut printed = 0;
+    for (id, payload, attempts) in jobs {
+        let attempts = attempts + 1;
+        match send_to_printer(&settings.host, port, &payload) {
+            Ok(()) => {
+                conn.execute(
+                    "UPDATE print_jobs SET status = 'done', attempts = ?2, last_error = NULL, updated_at = ?3 WHERE id = ?1",
+                    params![id, attempts, now],
+                )
+                .map_err(|e| format!("update print_jobs: {e}"))?;
+                printed += 1;
+            }
+            Err(err) => {
+                let status = if attempts >= PRINT_MAX_ATTEMPTS { "failed" } else { "queued" };
+                let next_attempt_at = now + PRINT_RETRY_BASE_SECS * (1 << (attempts - 1).min(10));
+                conn.execute(
+                    "UPDATE print_jobs SET status = ?2, attempts = ?3, last_error = ?4, next_attempt_at = ?5, updated_at = ?6 WHERE id = ?1",
+                    params![id, status, attempts, err, next_attempt_at, now],
+                )
+                .map_err(|e| format!("update print_jobs: {e}"))?;
+                if status == "failed" {
+                    log_to_system("warn", "printing", &format!("打印失败: {id}"), Some(&err));
+                }
+            }
+        }
+    }
+    Ok(printed)
+}
+
+/// Runs the print queue in the background for the life of the app.
+pub fn start_print_worker(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(PRINT_POLL_INTERVAL);
+        let run = || -> Result<usize, String> {
+            let conn = open_db(&app)?;
+            match configured_printer(&conn)? {
+                Some(settings) => process_print_queue(&conn, &settings, now_ts()?),
+                None => Ok(0),
+            }
+        };
+        match run() {
+            Ok(0) => {}
+            Ok(_) => {
+                let _ = app.emit("print-jobs-changed", ());
+            }
+            Err(e) => log_to_system("error", "printing", "打印队列出错", Some(&e)),
+        }
+    });
+}
+
+#[tauri::command]
+pub fn printer_settings_get(app: AppHandle, token: String) -> Result<Option<PrinterSettings>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_printer_settings(&conn)
+}
+
+#[tauri::command]
+pub fn settings_save_printer(app: AppHandle, input: PrinterSettingsInput) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let mut settings = input.settings;
+    settings.host = settings.host.trim().to_string();
+    if settings.enabled && settings.host.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    if settings.paper_width.is_some_and(|w| w != 58 && w != 80) || settings.port == Some(0) {
+        return Err(String::from("invalid"));
+    }
+    let now = now_ts()?;
+    let v = serde_json::to_string(&settings).map_err(|e| format!("serialize: {e}"))?;
+    conn.execute(
+        "INSERT INTO kv(k, v, updated_at) VALUES(?1, ?2, ?3) \
+         ON CONFLICT(k) DO UPDATE SET v=excluded.v, updated_at=excluded.updated_at",
+        params![PRINTER_SETTINGS_KEY, v, now],
+    )
+    .map_err(|e| format!("save printer settings: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn print_jobs_list(app: AppHandle, token: String, status: Option<String>, limit: Option<i64>) -> Result<Vec<PrintJobRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    let status = status.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, order_id, kind, status, attempts, last_error, next_attempt_at, created_at, updated_at \
+             FROM print_jobs WHERE ?1 IS NULL OR status = ?1 ORDER BY created_at DESC LIMIT ?2",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(params![status, limit], |r| {
+            Ok(PrintJobRow {
+                id: r.get(0)?,
+                order_id: r.get(1)?,
+                kind: r.get(2)?,
+                status: r.get(3)?,
+                attempts: r.get(4)?,
+                last_error: r.get(5)?,
+                next_attempt_at: r.get(6)?,
+                created_at: r.get(7)?,
+                updated_at: r.get(8)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Puts a failed job back in the queue with a fresh set of attempts.
+#[tauri::command]
+pub fn print_job_retry(app: AppHandle, token: String, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let n = conn
+        .execute(
+            "UPDATE print_jobs SET status = 'queued', attempts = 0, next_attempt_at = ?2, updated_at = ?2 WHERE id = ?1 AND status = 'failed'",
+            params![id.trim(), now],
+        )
+        .map_err(|e| format!("update print_jobs: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use crate::commands::receipts::render_escpos;
+    use crate::money::Money;
+
+    fn queue_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE print_jobs (id TEXT PRIMARY KEY NOT NULL, order_id TEXT, kind TEXT NOT NULL DEFAULT 'receipt', \
+             payload BLOB NOT NULL, status TEXT NOT NULL DEFAULT 'queued', attempts INTEGER NOT NULL DEFAULT 0, last_error TEXT, \
+             next_attempt_at INTEGER NOT NULL, created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn printer(port: u16) -> PrinterSettings {
+        PrinterSettings { enabled: true, host: String::from("127.0.0.1"), port: Some(port), paper_width: None, auto_print: None }
+    }
+
+    fn job(conn: &Connection, id: &str) -> (String, i64, Option<String>, i64) {
+        conn.query_row("SELECT status, attempts, last_error, next_attempt_at FROM print_jobs WHERE id = ?1", [id], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn queued_job_is_sent_and_marked_done() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).unwrap();
+            buf
+        });
+
+        let conn = queue_db();
+        let id = enqueue_print_job(&conn, Some("order-1"), "receipt", b"\x1b@hello\n", 1000).unwrap();
+        assert_eq!(process_print_queue(&conn, &printer(port), 1000).unwrap(), 1);
+        assert_eq!(received.join().unwrap(), b"\x1b@hello\n");
+        assert_eq!(job(&conn, &id), (String::from("done"), 1, None, 1000));
+
+        // Nothing left to send.
+        assert_eq!(process_print_queue(&conn, &printer(port), 1001).unwrap(), 0);
+    }
+
+    #[test]
+    fn unreachable_printer_backs_off_then_fails() {
+        // A port nothing listens on any more refuses the connection.
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let conn = queue_db();
+        let id = enqueue_print_job(&conn, None, "receipt", b"x", 1000).unwrap();
+
+        let mut now = 1000;
+        for attempt in 1..=PRINT_MAX_ATTEMPTS {
+            // Not due yet: left alone.
+            if attempt > 1 {
+                assert_eq!(process_print_queue(&conn, &printer(port), now - 1).unwrap(), 0);
+                assert_eq!(job(&conn, &id).1, attempt - 1);
+            }
+            assert_eq!(process_print_queue(&conn, &printer(port), now).unwrap(), 0);
+            let (status, attempts, last_error, next_attempt_at) = job(&conn, &id);
+            assert_eq!(attempts, attempt);
+            assert!(last_error.unwrap().starts_with("connect printer"));
+            assert_eq!(next_attempt_at, now + PRINT_RETRY_BASE_SECS * (1 << (attempt - 1)));
+            assert_eq!(status, if attempt < PRINT_MAX_ATTEMPTS { "queued" } else { "failed" });
+            now = next_attempt_at;
+        }
+
+        // A failed job is not picked up again.
+        assert_eq!(process_print_queue(&conn, &printer(port), now + 3600).unwrap(), 0);
+        assert_eq!(job(&conn, &id).1, PRINT_MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn escpos_receipt_layout() {
+        let receipt = ReceiptView {
+            brand_name: String::from("测试网咖"),
+            store_name: String::from("一号店"),
+            order_id: String::from("o1"),
+            kind: String::from("sale"),
+            ref_order_id: None,
+            date_ymd: String::from("2025-10-09"),
+            shift: String::from("白班"),
+            employee: String::from("小王"),
+            ordered_at: String::from("2025-10-09 14:30"),
+            lines: vec![ReceiptLine {
+                product_name: String::from("可乐"),
+                bundle_name: None,
+                quantity: 2.0,
+                unit_price: Money::from_fen(350),
+                amount: Money::from_fen(700),
+                modifiers: Vec::new(),
+                promotion_name: None,
+                discount: Money::ZERO,
+            }],
+            subtotal: Money::from_fen(700),
+            discount: Money::ZERO,
+            coupon: Money::ZERO,
+            rounding: Money::ZERO,
+            total: Money::from_fen(700),
+            payments: vec![ReceiptPayment {
+                method: String::from("cash"),
+                label: String::from("现金"),
+                amount: Money::from_fen(700),
+                tendered: Money::from_fen(1000),
+                change_amount: Money::from_fen(300),
+            }],
+            reprint: false,
+        };
+        let bytes = render_escpos(&receipt, 32);
+        let gbk = |s: &str| encoding_rs::GBK.encode(s).0.into_owned();
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+
+        // Reset and Chinese mode first, partial cut last.
+        assert_eq!(&bytes[..4], &[0x1b, b'@', 0x1c, b'&']);
+        assert_eq!(&bytes[bytes.len() - 4..], &[0x1d, b'V', 66, 3]);
+        // Title centred at double size, back to normal after.
+        let title = [&[0x1b, b'a', 1, 0x1d, b'!', 0x11][..], &gbk("测试网咖"), &[b'\n', 0x1d, b'!', 0, 0x1b, b'a', 0]].concat();
+        assert!(contains(&title));
+        // Pairs fill the line, wide characters counting twice; the total is bold.
+        assert!(contains(&gbk(&format!("  2 x 3.50{}7.00\n", " ".repeat(18)))));
+        let total = [&[0x1b, b'E', 1][..], &gbk(&format!("合计{}7.00", " ".repeat(24))), &[b'\n', 0x1b, b'E', 0]].concat();
+        assert!(contains(&total));
+        assert!(contains(&gbk(&format!("  找零{}3.00\n", " ".repeat(22)))));
+        assert!(contains(&[&b"-".repeat(32)[..], b"\n"].concat()));
+    }
+}
@@ -1,11 +1,120 @@
 use tauri::AppHandle;
-use rusqlite::params;
+use rusqlite::{params, Connection, OptionalExtension};
 use uuid::Uuid;
 use crate::db::{open_db, now_ts};
-use crate::models::{ProductRow, ProductUpsertInput, MigrationStats};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{ProductRow, ProductUpsertInput, MigrationStats, ProductLookup, ProductBarcodeRow, ProductBarcodeAddInput};
 use crate::commands::kv::load_kv_json;
+use crate::commands::inventory::seed_product_stock;
+use crate::commands::units::spec_conflict;
+use crate::commands::pricing::record_price;
+use crate::commands::categories::{default_category, ensure_category, resolve_category};
+use crate::money::Money;
 use serde_json::Value;
 
+pub const PRODUCT_COLUMNS: &str = "id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, base_unit, sku, kind";
+
+pub fn map_product_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ProductRow> {
+    Ok(ProductRow {
+        id: r.get(0)?,
+        name: r.get(1)?,
+        category: r.get(2)?,
+        unit_price: r.get(3)?,
+        cost_price: r.get(4)?,
+        spec: r.get(5)?,
+        on_shelf: r.get(6)?,
+        stock: r.get(7)?,
+        is_active: r.get::<_, i64>(8)? != 0,
+        base_unit: r.get(9)?,
+        sku: r.get(10)?,
+        kind: r.get(11)?,
+    })
+}
+
+/// Products linked to `key` through a quantity table such as
+/// product_bundle_items or product_recipe_items, in the table's sort order,
+/// each with the link's quantity.
+pub fn load_linked_products(
+    conn: &Connection,
+    table: &str,
+    key_column: &str,
+    product_column: &str,
+    key: &str,
+) -> Result<Vec<(ProductRow, f64)>, String> {
+    let cols = PRODUCT_COLUMNS
+        .split(", ")
+        .map(|c| format!("p.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {cols}, l.quantity AS link_quantity FROM {table} l JOIN products p ON p.id = l.{product_column} \
+             WHERE l.{key_column} = ?1 ORDER BY l.sort_order ASC"
+        ))
+        .map_err(|e| format!("prepare {table}: {e}"))?;
+    let rows = stmt
+        .query_map([key], |r| Ok((map_product_row(r)?, r.get("link_quantity")?)))
+        .map_err(|e| format!("query {table}: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row {table}: {e}"))?);
+    }
+    Ok(out)
+}
+
+pub fn load_product(conn: &Connection, id: &str) -> Result<Option<ProductRow>, String> {
+    conn.query_row(
+        &format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?1"),
+        [id],
+        map_product_row,
+    )
+    .optional()
+    .map_err(|e| format!("query product: {e}"))
+}
+
+/// Like `load_product`, but skips deleted products; used wherever a product
+/// is about to be sold or linked into a bundle or recipe.
+pub fn load_sellable_product(conn: &Connection, id: &str) -> Result<Option<ProductRow>, String> {
+    conn.query_row(
+        &format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?1 AND deleted_at IS NULL"),
+        [id],
+        map_product_row,
+    )
+    .optional()
+    .map_err(|e| format!("query product: {e}"))
+}
+
+/// Resolves a scanned barcode or an internal SKU to a product. Barcodes that
+/// belong to a pack unit (e.g. the case barcode) also return that unit.
+pub fn lookup_product_by_code(conn: &Connection, code: &str) -> Result<Option<ProductLookup>, String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Ok(None);
+    }
+    let hit: Option<(String, Option<String>)> = conn
+        .query_row(
+            "SELECT product_id, unit FROM product_barcodes WHERE code = ?1",
+            [code],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("query barcode: {e}"))?;
+    let (product_id, unit) = match hit {
+        Some(h) => h,
+        None => {
+            let by_sku: Option<String> = conn
+                .query_row("SELECT id FROM products WHERE sku = ?1 AND deleted_at IS NULL", [code], |r| r.get(0))
+                .optional()
+                .map_err(|e| format!("query sku: {e}"))?;
+            match by_sku {
+                Some(id) => (id, None),
+                None => return Ok(None),
+            }
+        }
+    };
+    Ok(load_sellable_product(conn, &product_id)?.map(|product| ProductLookup { product, unit }))
+}
+
 #[tauri::command]
 pub fn products_list(app: AppHandle, q: Option<String>, include_inactive: Option<bool>) -> Result<Vec<ProductRow>, String> {
     products_list_internal(app, q, include_inactive)
@@ -16,30 +125,15 @@ pub fn products_list_internal(app: AppHandle, q: Option<String>, include_inactiv
     let qn = q.unwrap_or_default().trim().to_lowercase();
     let include_inactive = include_inactive.unwrap_or(false);
 
-    fn map_product_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ProductRow> {
-        Ok(ProductRow {
-            id: r.get(0)?,
-            name: r.get(1)?,
-            category: r.get(2)?,
-            unit_price: r.get(3)?,
-            cost_price: r.get(4)?,
-            spec: r.get(5)?,
-            on_shelf: r.get(6)?,
-            stock: r.get(7)?,
-            is_active: r.get::<_, i64>(8)? != 0,
-        })
-    }
-
-    let mut sql = String::from(
-        "SELECT id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active \
-         FROM products",
-    );
-    let mut clauses: Vec<String> = Vec::new();
+    let mut sql = format!("SELECT {PRODUCT_COLUMNS} FROM products");
+    let mut clauses: Vec<String> = vec![String::from("deleted_at IS NULL")];
     if !include_inactive {
         clauses.push(String::from("is_active = 1"));
     }
     if !qn.is_empty() {
-        clauses.push(String::from("lower(name) LIKE '%' || ?1 || '%'") );
+        clauses.push(String::from(
+            "(lower(name) LIKE '%' || ?1 || '%' OR lower(sku) = ?1 OR id IN (SELECT product_id FROM product_barcodes WHERE code = ?1))",
+        ));
     }
     if !clauses.is_empty() {
         sql.push_str(" WHERE ");
@@ -68,77 +162,163 @@ pub fn products_list_internal(app: AppHandle, q: Option<String>, include_inactiv
     Ok(out)
 }
 
+/// Whether `sku` is already a barcode of a product other than `product_id`.
+pub fn sku_is_barcode(conn: &Connection, sku: &str, product_id: Option<&str>) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM product_barcodes WHERE code = ?1 AND product_id IS NOT ?2)",
+        params![sku, product_id],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("query barcode: {e}"))
+}
+
 #[tauri::command]
 pub fn product_upsert(app: AppHandle, input: ProductUpsertInput) -> Result<ProductRow, String> {
     let conn = open_db(&app)?;
     let now = now_ts()?;
-    let id = input
-        .id
-        .filter(|s| !s.trim().is_empty())
-        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let input_id = input.id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
 
     let name = input.name.trim().to_string();
     if name.is_empty() {
         return Err(String::from("name is empty"));
     }
 
-    let category = input.category.unwrap_or_default().trim().to_string();
-    let category = if category.is_empty() { crate::constants::DEFAULT_CATEGORY.to_string() } else { category };
+    let category = resolve_category(&conn, &input.category.unwrap_or_default())?;
+    let base_unit = input.base_unit.unwrap_or_default().trim().to_string();
+    let base_unit = if base_unit.is_empty() { crate::constants::DEFAULT_BASE_UNIT.to_string() } else { base_unit };
+    let sku = input.sku.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let map_conflict = |e: rusqlite::Error| {
+        let msg = e.to_string();
+        if msg.contains("products.sku") {
+            String::from("duplicate_sku")
+        } else if msg.contains("products.name") {
+            String::from("duplicate_name")
+        } else {
+            format!("execute: {e}")
+        }
+    };
 
-    conn.execute(
-        "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at) 
-         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11) 
-         ON CONFLICT(name) DO UPDATE SET 
-            category=excluded.category, 
-            unit_price=excluded.unit_price, 
-            cost_price=excluded.cost_price, 
-            spec=excluded.spec, 
-            on_shelf=excluded.on_shelf, 
-            stock=excluded.stock, 
-            is_active=excluded.is_active, 
-            updated_at=excluded.updated_at",
-        params![
-            id,
-            name,
-            category,
-            input.unit_price,
-            input.cost_price,
-            input.spec,
-            input.on_shelf,
-            input.stock,
-            if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 },
-            now,
-            now
-        ],
-    )
-    .map_err(|e| format!("execute: {e}"))?;
+    // The id is the stable key, so a rename keeps the product's history.
+    // Callers without an id (older frontends) still match on the name.
+    let existing: Option<String> = match input_id.as_deref() {
+        Some(id) => conn.query_row("SELECT id FROM products WHERE id = ?1", [id], |r| r.get(0)),
+        None => conn.query_row("SELECT id FROM products WHERE name = ?1", [&name], |r| r.get(0)),
+    }
+    .optional()
+    .map_err(|e| format!("query product: {e}"))?;
 
-    let mut stmt = conn
-        .prepare("SELECT id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active FROM products WHERE name = ?1")
-        .map_err(|e| format!("prepare: {e}"))?;
-    let row = stmt
-        .query_row([name], |r| {
-            Ok(ProductRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                category: r.get(2)?,
-                unit_price: r.get(3)?,
-                cost_price: r.get(4)?,
-                spec: r.get(5)?,
-                on_shelf: r.get(6)?,
-                stock: r.get(7)?,
-                is_active: r.get::<_, i64>(8)? != 0,
-            })
-        })
-        .map_err(|e| format!("query_row: {e}"))?;
-    Ok(row)
+    if let Some(e) = existing.as_deref().map(|id| spec_conflict(&conn, id, input.spec)).transpose()?.flatten() {
+        return Err(e);
+    }
+    // Scans try barcodes first, so a SKU that is another product's barcode
+    // could never be scanned.
+    if let Some(code) = sku.as_deref() {
+        if sku_is_barcode(&conn, code, existing.as_deref().or(input_id.as_deref()))? {
+            return Err(String::from("duplicate_code"));
+        }
+    }
+
+    let product_id = match existing {
+        Some(id) => {
+            conn.execute(
+                "UPDATE products SET name = ?2, category = ?3, unit_price = ?4, cost_price = ?5, spec = ?6, is_active = ?7, \
+                 updated_at = ?8, base_unit = ?9, sku = ?10, deleted_at = NULL WHERE id = ?1",
+                params![id, name, category, input.unit_price, input.cost_price, input.spec, is_active, now, base_unit, sku],
+            )
+            .map_err(map_conflict)?;
+            id
+        }
+        None => {
+            let id = input_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+            conn.execute(
+                "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at, base_unit, sku) \
+                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    id,
+                    name,
+                    category,
+                    input.unit_price,
+                    input.cost_price,
+                    input.spec,
+                    input.on_shelf,
+                    input.stock,
+                    is_active,
+                    now,
+                    now,
+                    base_unit,
+                    sku
+                ],
+            )
+            .map_err(map_conflict)?;
+            id
+        }
+    };
+
+    // Quantities only seed a new product; afterwards they change through
+    // transfers, sales and stock_adjust.
+    seed_product_stock(&conn, &product_id, input.on_shelf, input.stock, now)?;
+    record_price(&conn, &product_id, input.unit_price, now, None, None)?;
+
+    load_product(&conn, &product_id)?.ok_or_else(|| String::from("not_found"))
 }
 
+/// Products that were ever sold, moved or put in a cart are only marked
+/// deleted, so those lines keep pointing at them; others are removed outright.
 #[tauri::command]
 pub fn product_delete(app: AppHandle, id: String) -> Result<(), String> {
-    let conn = open_db(&app)?;
-    conn.execute("DELETE FROM products WHERE id = ?1", [id])
+    let mut conn = open_db(&app)?;
+    let now = now_ts()?;
+    let id = id.trim().to_string();
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    // Other products' bundles, recipes and options would lose stock tracking
+    // for this product; they have to be edited first.
+    let in_use: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM product_bundle_items WHERE component_id = ?1 AND bundle_id != ?1) \
+                 OR EXISTS(SELECT 1 FROM product_recipe_items WHERE ingredient_id = ?1 AND product_id != ?1) \
+                 OR EXISTS(SELECT 1 FROM modifier_options WHERE stock_product_id = ?1)",
+            [&id],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("query usage: {e}"))?;
+    if in_use {
+        return Err(String::from("in_use"));
+    }
+    let has_history: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sales_items WHERE product_id = ?1) \
+                 OR EXISTS(SELECT 1 FROM stock_movements WHERE product_id = ?1 AND reason != 'init') \
+                 OR EXISTS(SELECT 1 FROM seat_order_items WHERE product_id = ?1) \
+                 OR EXISTS(SELECT 1 FROM cart_items WHERE product_id = ?1)",
+            [&id],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("query history: {e}"))?;
+    tx.execute("DELETE FROM product_barcodes WHERE product_id = ?1", [&id])
+        .map_err(|e| format!("delete barcodes: {e}"))?;
+    if has_history {
+        tx.execute(
+            "UPDATE products SET is_active = 0, sku = NULL, deleted_at = ?2, updated_at = ?2 WHERE id = ?1",
+            params![id, now],
+        )
         .map_err(|e| format!("execute: {e}"))?;
+    } else {
+        for sql in [
+            "DELETE FROM product_stock WHERE product_id = ?1",
+            "DELETE FROM stock_movements WHERE product_id = ?1",
+            "DELETE FROM product_units WHERE product_id = ?1",
+            "DELETE FROM product_prices WHERE product_id = ?1",
+            "DELETE FROM product_bundle_items WHERE bundle_id = ?1",
+            "DELETE FROM product_recipe_items WHERE product_id = ?1",
+            "DELETE FROM product_modifier_groups WHERE product_id = ?1",
+            "DELETE FROM products WHERE id = ?1",
+        ] {
+            tx.execute(sql, [&id]).map_err(|e| format!("execute: {e}"))?;
+        }
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
     Ok(())
 }
 
@@ -184,17 +364,19 @@ pub fn migrate_products_from_kv(app: AppHandle) -> Result<MigrationStats, String
             continue;
         }
 
-        let unit_price = obj.get("unitPrice").and_then(|v| v.as_f64()).unwrap_or(0.0);
-        let cost_price = obj.get("costPrice").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let unit_price = Money::from_yuan(obj.get("unitPrice").and_then(|v| v.as_f64()).unwrap_or(0.0));
+        let cost_price = Money::from_yuan(obj.get("costPrice").and_then(|v| v.as_f64()).unwrap_or(0.0));
         let spec = obj.get("spec").and_then(|v| v.as_f64()).unwrap_or(0.0);
         let on_shelf = obj.get("onShelf").and_then(|v| v.as_f64()).unwrap_or(1.0);
         let stock = obj.get("stock").and_then(|v| v.as_f64()).unwrap_or(0.0);
         let category = obj
             .get("category")
             .and_then(|v| v.as_str())
-            .unwrap_or("饮品")
+            .unwrap_or("")
             .trim()
             .to_string();
+        let category = if category.is_empty() { default_category(&tx)? } else { category };
+        ensure_category(&tx, &category, now)?;
         let is_active = true;
 
         let id = Uuid::new_v4().to_string();
@@ -206,8 +388,6 @@ pub fn migrate_products_from_kv(app: AppHandle) -> Result<MigrationStats, String
                 unit_price=excluded.unit_price, 
                 cost_price=excluded.cost_price, 
                 spec=excluded.spec, 
-                on_shelf=excluded.on_shelf, 
-                stock=excluded.stock, 
                 is_active=excluded.is_active, 
                 updated_at=excluded.updated_at",
             params![
@@ -241,3 +421,81 @@ pub fn migrate_products_from_kv(app: AppHandle) -> Result<MigrationStats, String
         skipped,
     })
 }
+
+#[tauri::command]
+pub fn product_lookup_by_code(app: AppHandle, code: String) -> Result<Option<ProductLookup>, String> {
+    let conn = open_db(&app)?;
+    lookup_product_by_code(&conn, &code)
+}
+
+#[tauri::command]
+pub fn product_barcodes_list(app: AppHandle, token: String, product_id: String) -> Result<Vec<ProductBarcodeRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT code, product_id, unit, created_at FROM product_barcodes WHERE product_id = ?1 ORDER BY created_at ASC")
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([product_id.trim()], |r| {
+            Ok(ProductBarcodeRow {
+                code: r.get(0)?,
+                product_id: r.get(1)?,
+                unit: r.get(2)?,
+                created_at: r.get(3)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn product_barcode_add(app: AppHandle, input: ProductBarcodeAddInput) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let code = input.code.trim().to_string();
+    let product_id = input.product_id.trim().to_string();
+    if code.is_empty() || product_id.is_empty() {
+        return Err(String::from("invalid"));
+    }
+    let product = load_product(&conn, &product_id)?.ok_or_else(|| format!("product_not_found: {product_id}"))?;
+    let unit = input.unit.map(|u| u.trim().to_string()).filter(|u| !u.is_empty() && *u != product.base_unit);
+    if let Some(u) = unit.as_deref() {
+        let _ = crate::commands::units::unit_factor(&conn, &product_id, &product.base_unit, Some(u))?;
+    }
+
+    let taken: Option<String> = conn
+        .query_row("SELECT id FROM products WHERE sku = ?1", [&code], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query sku: {e}"))?;
+    if taken.is_some() {
+        return Err(String::from("duplicate_code"));
+    }
+    conn.execute(
+        "INSERT INTO product_barcodes(code, product_id, unit, created_at) VALUES(?1, ?2, ?3, ?4)",
+        params![code, product_id, unit, now],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("UNIQUE") { String::from("duplicate_code") } else { format!("insert barcode: {e}") }
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn product_barcode_remove(app: AppHandle, token: String, code: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    let n = conn
+        .execute("DELETE FROM product_barcodes WHERE code = ?1", [code.trim()])
+        .map_err(|e| format!("delete barcode: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(())
+}
@@ -0,0 +1,503 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{PromotionRow, PromotionUpsertInput, PromotionStat, SalesItemPromotionRow};
+use crate::money::Money;
+
+// Rule types:
+//   percent       `value` percent off the line
+//   fixed         `amount` off each unit
+//   buy_x_get_y   every `buy_qty` + `get_qty` units, `get_qty` are free
+//   nth_discount  every `buy_qty`-th unit is `value` percent off (第二件半价)
+// `value` is only ever a percentage; money is kept in `amount`, in fen.
+// Scope picks the lines: 'all', a 'category' name or a 'product' id in `target`.
+// buy_x_get_y and nth_discount count units across lines: the same product on
+// several lines, or every product of the category for a category promotion,
+// and the cheapest units are the ones discounted.
+// Weekdays are "1,2,..,7" (Monday = 1); times are zero-padded "HH:MM", and a
+// window whose end is before its start runs past midnight. Each line gets at
+// most one promotion, the one saving the customer the most.
+const RULE_TYPES: [&str; 4] = ["percent", "fixed", "buy_x_get_y", "nth_discount"];
+const SCOPES: [&str; 3] = ["all", "category", "product"];
+
+const PROMOTION_COLUMNS: &str = "id, name, rule_type, scope, target, value, buy_qty, get_qty, weekdays, start_time, end_time, starts_at, ends_at, priority, is_active, amount";
+
+/// One priced checkout line, before promotions. `quantity` is in base units
+/// and `unit_price` per base unit; options are charged once per unit sold
+/// (`units`, in the unit the item was rung up in), so a case of 24 with a
+/// ¥2 option pays ¥2, not ¥48.
+#[derive(Debug, Clone)]
+pub struct PriceLine {
+    pub product_id: String,
+    pub category: String,
+    pub quantity: f64,
+    pub unit_price: Money,
+    pub units: f64,
+    pub options: Money,
+    pub promotion: Option<AppliedPromotion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppliedPromotion {
+    pub promotion_id: String,
+    pub name: String,
+    pub discount: Money,
+}
+
+impl PriceLine {
+    pub fn gross(&self) -> Money {
+        self.unit_price.mul_f64(self.quantity) + self.options.mul_f64(self.units)
+    }
+
+    /// What one unit sold costs, options included.
+    pub fn sold_unit_price(&self) -> Money {
+        if self.units.abs() < 1e-9 {
+            return self.unit_price + self.options;
+        }
+        self.unit_price.mul_f64(self.quantity / self.units) + self.options
+    }
+
+    pub fn net(&self) -> Money {
+        self.gross() - self.promotion.as_ref().map(|p| p.discount).unwrap_or_default()
+    }
+}
+
+fn map_promotion_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<PromotionRow> {
+    Ok(PromotionRow {
+        id: r.get(0)?,
+        name: r.get(1)?,
+        rule_type: r.get(2)?,
+        scope: r.get(3)?,
+        target: r.get(4)?,
+        value: r.get(5)?,
+        buy_qty: r.get(6)?,
+        get_qty: r.get(7)?,
+        weekdays: r.get(8)?,
+        start_time: r.get(9)?,
+        end_time: r.get(10)?,
+        starts_at: r.get(11)?,
+        ends_at: r.get(12)?,
+        priority: r.get(13)?,
+        is_active: r.get::<_, i64>(14)? != 0,
+        amount: r.get(15)?,
+    })
+}
+
+fn in_window(p: &PromotionRow, weekday: i64, hhmm: &str) -> bool {
+    if let Some(days) = p.weekdays.as_deref().filter(|d| !d.trim().is_empty()) {
+        if !days.split(',').any(|d| d.trim().parse::<i64>().ok() == Some(weekday)) {
+            return false;
+        }
+    }
+    let start = p.start_time.as_deref().unwrap_or("").trim();
+    let end = p.end_time.as_deref().unwrap_or("").trim();
+    match (start.is_empty(), end.is_empty()) {
+        (true, true) => true,
+        (false, true) => hhmm >= start,
+        (true, false) => hhmm < end,
+        (false, false) if start <= end => hhmm >= start && hhmm < end,
+        (false, false) => hhmm >= start || hhmm < end,
+    }
+}
+
+/// Promotions that apply at `ts`, local time.
+pub fn active_promotions(conn: &Connection, ts: i64) -> Result<Vec<PromotionRow>, String> {
+    let (weekday, hhmm): (i64, String) = conn
+        .query_row(
+            "SELECT CAST(strftime('%w', ?1, 'unixepoch', 'localtime') AS INTEGER), strftime('%H:%M', ?1, 'unixepoch', 'localtime')",
+            [ts],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .map_err(|e| format!("query local time: {e}"))?;
+    let weekday = if weekday == 0 { 7 } else { weekday };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {PROMOTION_COLUMNS} FROM promotions \
+             WHERE is_active = 1 AND (starts_at IS NULL OR starts_at <= ?1) AND (ends_at IS NULL OR ends_at > ?1)"
+        ))
+        .map_err(|e| format!("prepare promotions: {e}"))?;
+    let rows = stmt
+        .query_map([ts], map_promotion_row)
+        .map_err(|e| format!("query promotions: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        let p = r.map_err(|e| format!("row promotions: {e}"))?;
+        if in_window(&p, weekday, &hhmm) {
+            out.push(p);
+        }
+    }
+    Ok(out)
+}
+
+fn is_hhmm(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() != 5 || b[2] != b':' || ![0, 1, 3, 4].iter().all(|&i| b[i].is_ascii_digit()) {
+        return false;
+    }
+    let hh = (b[0] - b'0') * 10 + (b[1] - b'0');
+    let mm = (b[3] - b'0') * 10 + (b[4] - b'0');
+    hh < 24 && mm < 60
+}
+
+fn in_scope(p: &PromotionRow, line: &PriceLine) -> bool {
+    let target = p.target.as_deref().unwrap_or("");
+    match p.scope.as_str() {
+        "category" => target == line.category,
+        "product" => target == line.product_id,
+        _ => true,
+    }
+}
+
+fn cap(discount: Money, line: &PriceLine) -> Money {
+    discount.max(Money::ZERO).min(line.gross().max(Money::ZERO))
+}
+
+/// Discount of a per-line rule ("percent", "fixed"); the unit-counting rules
+/// need the whole cart and go through `promotion_discounts`.
+fn line_discount(p: &PromotionRow, line: &PriceLine) -> Money {
+    if !in_scope(p, line) {
+        return Money::ZERO;
+    }
+    // Percentages round to the fen.
+    let discount = match p.rule_type.as_str() {
+        "percent" => line.gross().mul_f64(p.value / 100.0),
+        "fixed" => p.amount.mul_f64(line.quantity),
+        _ => Money::ZERO,
+    };
+    cap(discount, line)
+}
+
+/// What `p` takes off each line of the cart. For buy_x_get_y and
+/// nth_discount the units of a group (one product, or one category for a
+/// category promotion) are added up and the free or discounted units are
+/// taken from the cheapest lines first.
+fn promotion_discounts(p: &PromotionRow, lines: &[PriceLine]) -> Vec<Money> {
+    // Every `every` units of a group, `free` of them are `rate` off.
+    let (every, free, rate) = match p.rule_type.as_str() {
+        "buy_x_get_y" if p.buy_qty > 0.0 && p.get_qty > 0.0 => (p.buy_qty + p.get_qty, p.get_qty, 1.0),
+        "nth_discount" if p.buy_qty >= 1.0 => (p.buy_qty, 1.0, p.value / 100.0),
+        "buy_x_get_y" | "nth_discount" => return vec![Money::ZERO; lines.len()],
+        _ => return lines.iter().map(|l| line_discount(p, l)).collect(),
+    };
+
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if !in_scope(p, line) || line.quantity <= 0.0 {
+            continue;
+        }
+        let key = if p.scope == "category" { line.category.as_str() } else { line.product_id.as_str() };
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, idx)) => idx.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+
+    let mut out = vec![Money::ZERO; lines.len()];
+    for (_, mut idx) in groups {
+        let total: f64 = idx.iter().map(|&i| lines[i].quantity).sum();
+        let mut remaining = (total / every).floor() * free;
+        idx.sort_by_key(|&i| lines[i].unit_price);
+        for i in idx {
+            if remaining <= 0.0 {
+                break;
+            }
+            let take = remaining.min(lines[i].quantity);
+            remaining -= take;
+            out[i] = cap(lines[i].unit_price.mul_f64(take * rate), &lines[i]);
+        }
+    }
+    out
+}
+
+/// Picks the best promotion for every line; ties go to the higher priority.
+pub fn apply_promotions(promotions: &[PromotionRow], lines: &mut [PriceLine]) {
+    let table: Vec<Vec<Money>> = promotions.iter().map(|p| promotion_discounts(p, lines)).collect();
+    for (li, line) in lines.iter_mut().enumerate() {
+        let mut best: Option<(&PromotionRow, Money)> = None;
+        for (p, discounts) in promotions.iter().zip(table.iter()) {
+            let d = discounts[li];
+            if d <= Money::ZERO {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((b, bd)) => d > bd || (d == bd && p.priority > b.priority),
+            };
+            if better {
+                best = Some((p, d));
+            }
+        }
+        line.promotion = best.map(|(p, d)| AppliedPromotion {
+            promotion_id: p.id.clone(),
+            name: p.name.clone(),
+            discount: d,
+        });
+    }
+}
+
+#[tauri::command]
+pub fn promotions_list(app: AppHandle, token: String, include_inactive: Option<bool>) -> Result<Vec<PromotionRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut sql = format!("SELECT {PROMOTION_COLUMNS} FROM promotions");
+    if !include_inactive.unwrap_or(false) {
+        sql.push_str(" WHERE is_active = 1");
+    }
+    sql.push_str(" ORDER BY priority DESC, name ASC");
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt.query_map([], map_promotion_row).map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn promotion_upsert(app: AppHandle, input: PromotionUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let name = input.name.trim().to_string();
+    let rule_type = input.rule_type.trim().to_string();
+    let scope = input.scope.unwrap_or_default().trim().to_string();
+    let scope = if scope.is_empty() { String::from("all") } else { scope };
+    let target = input.target.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let value = input.value.unwrap_or(0.0);
+    let amount = input.amount.unwrap_or_default();
+    let buy_qty = input.buy_qty.unwrap_or(0.0);
+    let get_qty = input.get_qty.unwrap_or(0.0);
+    if name.is_empty() || !RULE_TYPES.contains(&rule_type.as_str()) || !SCOPES.contains(&scope.as_str()) {
+        return Err(String::from("invalid"));
+    }
+    if (scope != "all" && target.is_none()) || !value.is_finite() || value < 0.0 || !buy_qty.is_finite() || !get_qty.is_finite() {
+        return Err(String::from("invalid"));
+    }
+    if (rule_type == "fixed") != (amount > Money::ZERO) {
+        return Err(String::from("invalid"));
+    }
+    let rule_ok = match rule_type.as_str() {
+        "percent" => value > 0.0 && value <= 100.0,
+        "fixed" => value == 0.0,
+        "buy_x_get_y" => buy_qty > 0.0 && get_qty > 0.0,
+        _ => buy_qty >= 1.0 && value > 0.0 && value <= 100.0,
+    };
+    if !rule_ok {
+        return Err(String::from("invalid"));
+    }
+    let weekdays = input.weekdays.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let start_time = input.start_time.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let end_time = input.end_time.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    // in_window compares times as strings, so "9:00" would sort after "18:00".
+    if start_time.iter().chain(end_time.iter()).any(|t| !is_hhmm(t)) {
+        return Err(String::from("invalid"));
+    }
+    let priority = input.priority.unwrap_or(0);
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO promotions(id, name, rule_type, scope, target, value, buy_qty, get_qty, weekdays, start_time, end_time, starts_at, ends_at, priority, is_active, created_at, updated_at, amount) \
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            params![
+                id, name, rule_type, scope, target, value, buy_qty, get_qty, weekdays, start_time, end_time,
+                input.starts_at, input.ends_at, priority, is_active, now, now, amount
+            ],
+        )
+        .map_err(|e| format!("insert promotions: {e}"))?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE promotions SET name = ?2, rule_type = ?3, scope = ?4, target = ?5, value = ?6, buy_qty = ?7, get_qty = ?8, weekdays = ?9, \
+             start_time = ?10, end_time = ?11, starts_at = ?12, ends_at = ?13, priority = ?14, is_active = ?15, updated_at = ?16, amount = ?17 WHERE id = ?1",
+            params![
+                id, name, rule_type, scope, target, value, buy_qty, get_qty, weekdays, start_time, end_time,
+                input.starts_at, input.ends_at, priority, is_active, now, amount
+            ],
+        )
+        .map_err(|e| format!("update promotions: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn sales_item_promotions_list(app: AppHandle, order_id: String) -> Result<Vec<SalesItemPromotionRow>, String> {
+    let conn = open_db(&app)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, sales_item_id, order_id, promotion_id, promotion_name, discount, created_at \
+             FROM sales_item_promotions WHERE order_id = ?1 ORDER BY created_at ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([order_id.trim()], |r| {
+            Ok(SalesItemPromotionRow {
+                id: r.get(0)?,
+                sales_item_id: r.get(1)?,
+                order_id: r.get(2)?,
+                promotion_id: r.get(3)?,
+                promotion_name: r.get(4)?,
+                discount: r.get(5)?,
+                created_at: r.get(6)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Promotion cost over a date range, by promotion.
+#[tauri::command]
+pub fn promotions_report(app: AppHandle, token: String, date_from: String, date_to: String) -> Result<Vec<PromotionStat>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT sp.promotion_id, MAX(sp.promotion_name), COUNT(1), COALESCE(SUM(sp.discount), 0) \
+             FROM sales_item_promotions sp JOIN sales_orders o ON o.id = sp.order_id \
+             WHERE o.date_ymd >= ?1 AND o.date_ymd <= ?2 \
+             GROUP BY sp.promotion_id ORDER BY 4 DESC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_from.trim(), date_to.trim()], |r| {
+            Ok(PromotionStat {
+                promotion_id: r.get(0)?,
+                promotion_name: r.get(1)?,
+                lines: r.get(2)?,
+                discount: r.get(3)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn promo(rule_type: &str, scope: &str, target: Option<&str>, value: f64, buy_qty: f64, get_qty: f64) -> PromotionRow {
+        PromotionRow {
+            id: format!("{rule_type}-{scope}"),
+            name: rule_type.to_string(),
+            rule_type: rule_type.to_string(),
+            scope: scope.to_string(),
+            target: target.map(str::to_string),
+            value,
+            buy_qty,
+            get_qty,
+            weekdays: None,
+            start_time: None,
+            end_time: None,
+            starts_at: None,
+            ends_at: None,
+            priority: 0,
+            is_active: true,
+            amount: Money::ZERO,
+        }
+    }
+
+    fn line(product_id: &str, category: &str, quantity: f64, price: &str) -> PriceLine {
+        PriceLine {
+            product_id: product_id.to_string(),
+            category: category.to_string(),
+            quantity,
+            unit_price: Money::parse_yuan(price).unwrap(),
+            units: quantity,
+            options: Money::ZERO,
+            promotion: None,
+        }
+    }
+
+    fn discounts(lines: &[PriceLine]) -> Vec<String> {
+        lines.iter().map(|l| l.promotion.as_ref().map(|p| p.discount).unwrap_or_default().to_string()).collect()
+    }
+
+    #[test]
+    fn buy_x_get_y_counts_the_same_product_across_lines() {
+        let p = promo("buy_x_get_y", "product", Some("cola"), 0.0, 2.0, 1.0);
+        let mut lines = vec![line("cola", "饮料", 1.0, "3"), line("cola", "饮料", 1.0, "3"), line("cola", "饮料", 1.0, "3")];
+        apply_promotions(&[p], &mut lines);
+        assert_eq!(discounts(&lines), ["3.00", "0.00", "0.00"]);
+    }
+
+    #[test]
+    fn category_nth_discount_takes_the_cheapest_units() {
+        let p = promo("nth_discount", "category", Some("饮料"), 50.0, 2.0, 0.0);
+        let mut lines = vec![line("coffee", "饮料", 1.0, "12"), line("tea", "饮料", 1.0, "8"), line("chips", "零食", 2.0, "5")];
+        apply_promotions(&[p], &mut lines);
+        assert_eq!(discounts(&lines), ["0.00", "4.00", "0.00"]);
+    }
+
+    #[test]
+    fn nth_discount_groups_by_product_outside_category_scope() {
+        let p = promo("nth_discount", "all", None, 50.0, 2.0, 0.0);
+        let mut lines = vec![line("coffee", "饮料", 1.0, "12"), line("tea", "饮料", 1.0, "8"), line("tea", "饮料", 1.0, "8")];
+        apply_promotions(&[p], &mut lines);
+        assert_eq!(discounts(&lines), ["0.00", "4.00", "0.00"]);
+    }
+
+    #[test]
+    fn line_discount_percent_and_fixed() {
+        let mut l = line("coffee", "饮料", 3.0, "12.50");
+        assert_eq!(line_discount(&promo("percent", "all", None, 15.0, 0.0, 0.0), &l).to_string(), "5.63");
+        let fixed = |scope: &str, target: Option<&str>, yuan: &str| PromotionRow {
+            amount: Money::parse_yuan(yuan).unwrap(),
+            ..promo("fixed", scope, target, 0.0, 0.0, 0.0)
+        };
+        assert_eq!(line_discount(&fixed("product", Some("coffee"), "2"), &l).to_string(), "6.00");
+        // Never more than the line, options included.
+        l.options = Money::parse_yuan("1").unwrap();
+        assert_eq!(line_discount(&fixed("all", None, "20"), &l).to_string(), "40.50");
+        assert!(line_discount(&promo("percent", "category", Some("零食"), 15.0, 0.0, 0.0), &l).is_zero());
+    }
+
+    #[test]
+    fn in_window_checks_weekdays_and_times() {
+        let mut p = promo("percent", "all", None, 10.0, 0.0, 0.0);
+        p.weekdays = Some(String::from("6,7"));
+        p.start_time = Some(String::from("14:00"));
+        p.end_time = Some(String::from("17:00"));
+        assert!(in_window(&p, 6, "14:00"));
+        assert!(!in_window(&p, 6, "17:00"));
+        assert!(!in_window(&p, 5, "15:00"));
+
+        // Past midnight.
+        p.weekdays = None;
+        p.start_time = Some(String::from("22:00"));
+        p.end_time = Some(String::from("02:00"));
+        assert!(in_window(&p, 1, "23:30"));
+        assert!(in_window(&p, 1, "01:59"));
+        assert!(!in_window(&p, 1, "12:00"));
+    }
+
+    #[test]
+    fn times_must_be_zero_padded() {
+        assert!(is_hhmm("09:00"));
+        assert!(is_hhmm("23:59"));
+        assert!(!is_hhmm("9:00"));
+        assert!(!is_hhmm("24:00"));
+        assert!(!is_hhmm("12:60"));
+        assert!(!is_hhmm("12-30"));
+    }
+}
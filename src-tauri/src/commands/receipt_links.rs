@@ -0,0 +1,154 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::constants::{RECEIPT_LINK_TTL_HOURS_DEFAULT, RECEIPT_LINK_TTL_HOURS_MAX};
+use crate::models::*;
+use crate::commands::kv::load_kv_json;
+use crate::commands::receipts::{load_receipt, render_html};
+use crate::http_server::lan_base_url;
+
+// Electronic receipts: the till shows a QR code, the customer's phone opens
+// /receipt/<token> on the LAN. The token is `<order id>.<expiry>.<signature>`;
+// the signature is an HMAC over the first two parts with a secret that never
+// leaves this machine, so links can't be guessed or stretched past expiry.
+
+const RECEIPT_LINK_SECRET_NAME: &str = "receipt_link";
+const RECEIPT_LINK_SETTINGS_KEY: &str = "settings.receipt_links";
+
+/// Signature length in bytes (128 bits, 32 hex characters in the link).
+const SIGNATURE_BYTES: usize = 16;
+
+/// The signing secret, created on first use. It lives in `app_secrets`
+/// rather than `kv`, which is readable over the HTTP bridge.
+fn link_secret(conn: &Connection, now: i64) -> Result<String, String> {
+    let query = || {
+        conn.query_row("SELECT value FROM app_secrets WHERE name = ?1", [RECEIPT_LINK_SECRET_NAME], |r| r.get(0))
+            .optional()
+            .map_err(|e| format!("query link secret: {e}"))
+    };
+    if let Some(secret) = query()? {
+        return Ok(secret);
+    }
+    // Two tills racing to create it keep whichever secret was written first.
+    conn.execute(
+        "INSERT OR IGNORE INTO app_secrets(name, value, created_at) VALUES(?1, ?2, ?3)",
+        params![RECEIPT_LINK_SECRET_NAME, format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()), now],
+    )
+    .map_err(|e| format!("save link secret: {e}"))?;
+    query()?.ok_or_else(|| String::from("link secret missing"))
+}
+
+fn signer(secret: &str, order_id: &str, expires_at: i64) -> Result<Hmac<Sha256>, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| format!("hmac: {e}"))?;
+    mac.update(format!("{order_id}.{expires_at}").as_bytes());
+    Ok(mac)
+}
+
+pub fn load_receipt_link_settings(conn: &Connection) -> Result<ReceiptLinkSettings, String> {
+    Ok(load_kv_json(conn, RECEIPT_LINK_SETTINGS_KEY)?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or(ReceiptLinkSettings { ttl_hours: None, base_url: None }))
+}
+
+/// A signed token for `order_id` that stops working at `expires_at`.
+pub fn sign_receipt_token(conn: &Connection, order_id: &str, expires_at: i64, now: i64) -> Result<String, String> {
+    let secret = link_secret(conn, now)?;
+    let tag = signer(&secret, order_id, expires_at)?.finalize().into_bytes();
+    Ok(format!("{order_id}.{expires_at}.{}", hex::encode(&tag[..SIGNATURE_BYTES])))
+}
+
+/// Checks a receipt token and returns the order it opens.
+pub fn verify_receipt_token(conn: &Connection, token: &str, now: i64) -> Result<String, String> {
+    let mut parts = token.rsplitn(3, '.');
+    let (Some(sig), Some(expires_at), Some(order_id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(String::from("not_found"));
+    };
+    let expires_at: i64 = expires_at.parse().map_err(|_| String::from("not_found"))?;
+    let sig = hex::decode(sig).map_err(|_| String::from("not_found"))?;
+    if sig.len() != SIGNATURE_BYTES {
+        return Err(String::from("not_found"));
+    }
+    let secret = link_secret(conn, now)?;
+    signer(&secret, order_id, expires_at)?
+        .verify_truncated_left(&sig)
+        .map_err(|_| String::from("not_found"))?;
+    if now >= expires_at {
+        return Err(String::from("link_expired"));
+    }
+    Ok(order_id.to_string())
+}
+
+pub fn qr_svg(text: &str) -> Result<String, String> {
+    let code = QrCode::new(text.as_bytes()).map_err(|e| format!("qr: {e}"))?;
+    Ok(code.render::<svg::Color>().min_dimensions(240, 240).quiet_zone(true).build())
+}
+
+pub fn create_receipt_link(conn: &Connection, order_id: &str, now: i64) -> Result<ReceiptLink, String> {
+    // Make sure the order exists before handing out a link to it.
+    load_receipt(conn, order_id)?;
+    let settings = load_receipt_link_settings(conn)?;
+    let expires_at = settings
+        .ttl_hours
+        .unwrap_or(RECEIPT_LINK_TTL_HOURS_DEFAULT)
+        .checked_mul(60 * 60)
+        .and_then(|ttl| now.checked_add(ttl))
+        .ok_or_else(|| String::from("invalid ttl_hours"))?;
+    let base_url = settings
+        .base_url
+        .map(|s| s.trim().trim_end_matches('/').to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(lan_base_url);
+    let url = format!("{base_url}/receipt/{}", sign_receipt_token(conn, order_id, expires_at, now)?);
+    Ok(ReceiptLink { qr_svg: qr_svg(&url)?, url, expires_at })
+}
+
+/// The receipt page a link opens; errors are `not_found` or `link_expired`.
+pub fn receipt_page(conn: &Connection, token: &str, now: i64) -> Result<String, String> {
+    let order_id = verify_receipt_token(conn, token, now)?;
+    Ok(render_html(&load_receipt(conn, &order_id)?))
+}
+
+#[tauri::command]
+pub fn receipt_link_create(app: AppHandle, token: String, order_id: String) -> Result<ReceiptLink, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+    create_receipt_link(&conn, order_id.trim(), now)
+}
+
+#[tauri::command]
+pub fn receipt_link_settings_get(app: AppHandle, token: String) -> Result<ReceiptLinkSettings, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_receipt_link_settings(&conn)
+}
+
+#[tauri::command]
+pub fn settings_save_receipt_links(app: AppHandle, input: ReceiptLinkSettingsInput) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let mut settings = input.settings;
+    if settings.ttl_hours.is_some_and(|h| !(1..=RECEIPT_LINK_TTL_HOURS_MAX).contains(&h)) {
+        return Err(String::from("invalid"));
+    }
+    settings.base_url = settings.base_url.map(|s| s.trim().trim_end_matches('/').to_string()).filter(|s| !s.is_empty());
+    if settings.base_url.as_deref().is_some_and(|u| !u.starts_with("http://") && !u.starts_with("https://")) {
+        return Err(String::from("invalid"));
+    }
+    let now = now_ts()?;
+    let v = serde_json::to_string(&settings).map_err(|e| format!("serialize: {e}"))?;
+    conn.execute(
+        "INSERT INTO kv(k, v, updated_at) VALUES(?1, ?2, ?3) \
+         ON CONFLICT(k) DO UPDATE SET v=excluded.v, updated_at=excluded.updated_at",
+        params![RECEIPT_LINK_SETTINGS_KEY, v, now],
+    )
+    .map_err(|e| format!("save receipt link settings: {e}"))?;
+    Ok(())
+}
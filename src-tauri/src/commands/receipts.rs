@@ -0,0 +1,380 @@
+use tauri::AppHandle;
+use rusqlite::{Connection, OptionalExtension};
+use crate::db::{open_db, now_ts};
+use crate::state::auth_resolve_account_id;
+use crate::constants::{ORDER_KIND_REFUND, ORDER_KIND_VOID};
+use crate::models::*;
+use crate::commands::auth::load_brand_settings;
+use crate::commands::printing::{configured_printer, enqueue_print_job, line_width};
+use crate::money::Money;
+
+// A receipt is read back from what the checkout wrote (lines, options,
+// promotions, rounding, tenders), so a reprint always matches the sale. It is
+// laid out once as rows and rendered as plain text for previews, as ESC/POS
+// bytes for the printer, or as an HTML page for a browser.
+
+pub fn payment_label(method: &str) -> &str {
+    match method {
+        "cash" => "现金",
+        "wechat" => "微信",
+        "alipay" => "支付宝",
+        "member" => "会员",
+        "meituan" => "美团",
+        other => other,
+    }
+}
+
+pub fn load_receipt(conn: &Connection, order_id: &str) -> Result<ReceiptView, String> {
+    let brand = load_brand_settings(conn)?;
+    let mut receipt = conn
+        .query_row(
+            "SELECT id, kind, ref_order_id, date_ymd, shift, employee, total_revenue, rounding, \
+                    strftime('%Y-%m-%d %H:%M', created_at, 'unixepoch', 'localtime'), \
+                    EXISTS(SELECT 1 FROM print_jobs p WHERE p.order_id = sales_orders.id AND p.kind = 'receipt' AND p.status = 'done'), \
+                    coupon_discount \
+             FROM sales_orders WHERE id = ?1",
+            [order_id],
+            |r| {
+                Ok(ReceiptView {
+                    brand_name: brand.brand_name.clone(),
+                    store_name: brand.store_name.clone(),
+                    order_id: r.get(0)?,
+                    kind: r.get(1)?,
+                    ref_order_id: r.get(2)?,
+                    date_ymd: r.get(3)?,
+                    shift: r.get(4)?,
+                    employee: r.get(5)?,
+                    ordered_at: r.get(8)?,
+                    lines: Vec::new(),
+                    subtotal: Money::ZERO,
+                    discount: Money::ZERO,
+                    coupon: r.get(10)?,
+                    rounding: r.get(7)?,
+                    total: r.get(6)?,
+                    payments: Vec::new(),
+                    reprint: r.get::<_, i64>(9)? != 0,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("query order: {e}"))?
+        .ok_or_else(|| String::from("not_found"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.id, i.product_name, COALESCE(i.sales, 0), COALESCE(i.revenue, 0), \
+                    (SELECT name FROM products WHERE id = i.bundle_id), \
+                    (SELECT promotion_name FROM sales_item_promotions WHERE sales_item_id = i.id), \
+                    (SELECT COALESCE(SUM(discount), 0) FROM sales_item_promotions WHERE sales_item_id = i.id), \
+                    COALESCE(i.order_discount, 0) \
+             FROM sales_items i WHERE i.order_id = ?1 ORDER BY i.created_at ASC, i.rowid ASC",
+        )
+        .map_err(|e| format!("prepare sales_items: {e}"))?;
+    let rows = stmt
+        .query_map([order_id], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, f64>(2)?,
+                r.get::<_, Money>(3)?,
+                r.get::<_, Option<String>>(4)?,
+                r.get::<_, Option<String>>(5)?,
+                r.get::<_, Money>(6)?,
+                r.get::<_, Money>(7)?,
+            ))
+        })
+        .map_err(|e| format!("query sales_items: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("row sales_items: {e}"))?;
+
+    let mut modifiers_stmt = conn
+        .prepare("SELECT option_name FROM sales_item_modifiers WHERE sales_item_id = ?1 ORDER BY created_at ASC, rowid ASC")
+        .map_err(|e| format!("prepare modifiers: {e}"))?;
+    for (item_id, product_name, quantity, revenue, bundle_name, promotion_name, discount, order_discount) in rows {
+        let modifiers = modifiers_stmt
+            .query_map([&item_id], |r| r.get::<_, String>(0))
+            .map_err(|e| format!("query modifiers: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("row modifiers: {e}"))?;
+        // Coupon and 抹零 shares are shown once, on the order, not per line.
+        let amount = revenue + order_discount;
+        let gross = amount + discount;
+        receipt.subtotal += gross;
+        receipt.discount += discount;
+        receipt.lines.push(ReceiptLine {
+            product_name,
+            bundle_name,
+            quantity,
+            unit_price: if quantity.abs() > 1e-9 { gross.mul_f64(1.0 / quantity) } else { gross },
+            amount,
+            modifiers,
+            promotion_name,
+            discount,
+        });
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT method, amount, tendered, change_amount FROM payments WHERE order_id = ?1 ORDER BY created_at ASC, rowid ASC")
+        .map_err(|e| format!("prepare payments: {e}"))?;
+    let rows = stmt
+        .query_map([order_id], |r| {
+            let method: String = r.get(0)?;
+            Ok(ReceiptPayment {
+                label: payment_label(&method).to_string(),
+                method,
+                amount: r.get(1)?,
+                tendered: r.get(2)?,
+                change_amount: r.get(3)?,
+            })
+        })
+        .map_err(|e| format!("query payments: {e}"))?;
+    for r in rows {
+        receipt.payments.push(r.map_err(|e| format!("row payments: {e}"))?);
+    }
+    Ok(receipt)
+}
+
+enum Row {
+    Title(String),
+    Center(String),
+    Text(String),
+    Pair(String, String),
+    Total(String, String),
+    Rule,
+}
+
+fn format_quantity(q: f64) -> String {
+    if (q - q.round()).abs() < 1e-9 {
+        format!("{}", q.round() as i64)
+    } else {
+        format!("{}", (q * 1000.0).round() / 1000.0)
+    }
+}
+
+fn layout(receipt: &ReceiptView) -> Vec<Row> {
+    let mut rows = vec![Row::Title(receipt.brand_name.clone()), Row::Center(receipt.store_name.clone())];
+    match receipt.kind.as_str() {
+        ORDER_KIND_REFUND => rows.push(Row::Center(String::from("退款单"))),
+        ORDER_KIND_VOID => rows.push(Row::Center(String::from("作废单"))),
+        _ => {}
+    }
+    rows.push(Row::Rule);
+    rows.push(Row::Text(format!("单号: {}", receipt.order_id)));
+    if let Some(ref_id) = receipt.ref_order_id.as_deref() {
+        rows.push(Row::Text(format!("原单: {ref_id}")));
+    }
+    rows.push(Row::Text(format!("时间: {}", receipt.ordered_at)));
+    rows.push(Row::Text(format!("班次: {} {}  收银: {}", receipt.date_ymd, receipt.shift, receipt.employee)));
+    rows.push(Row::Rule);
+
+    let mut bundle: Option<&str> = None;
+    for line in receipt.lines.iter() {
+        if line.bundle_name.as_deref() != bundle {
+            bundle = line.bundle_name.as_deref();
+            if let Some(name) = bundle {
+                rows.push(Row::Text(format!("【{name}】")));
+            }
+        }
+        let indent = if bundle.is_some() { "  " } else { "" };
+        rows.push(Row::Text(format!("{indent}{}", line.product_name)));
+        rows.push(Row::Pair(
+            format!("{indent}  {} x {}", format_quantity(line.quantity), line.unit_price),
+            (line.amount + line.discount).to_string(),
+        ));
+        for m in line.modifiers.iter() {
+            rows.push(Row::Text(format!("{indent}  + {m}")));
+        }
+        if !line.discount.is_zero() {
+            let name = line.promotion_name.as_deref().unwrap_or("优惠");
+            rows.push(Row::Pair(format!("{indent}  {name}"), (-line.discount).to_string()));
+        }
+    }
+    rows.push(Row::Rule);
+
+    rows.push(Row::Pair(String::from("小计"), receipt.subtotal.to_string()));
+    if !receipt.discount.is_zero() {
+        rows.push(Row::Pair(String::from("优惠"), (-receipt.discount).to_string()));
+    }
+    if !receipt.coupon.is_zero() {
+        rows.push(Row::Pair(String::from("优惠券"), (-receipt.coupon).to_string()));
+    }
+    if !receipt.rounding.is_zero() {
+        rows.push(Row::Pair(String::from("抹零"), (-receipt.rounding).to_string()));
+    }
+    rows.push(Row::Total(String::from("合计"), receipt.total.to_string()));
+    for p in receipt.payments.iter() {
+        rows.push(Row::Pair(p.label.clone(), p.amount.to_string()));
+        if p.change_amount > Money::ZERO {
+            rows.push(Row::Pair(String::from("  实收"), p.tendered.to_string()));
+            rows.push(Row::Pair(String::from("  找零"), p.change_amount.to_string()));
+        }
+    }
+    rows.push(Row::Rule);
+    rows.push(Row::Center(String::from("谢谢惠顾，欢迎再来")));
+    if receipt.reprint {
+        rows.push(Row::Center(String::from("** 重印 **")));
+    }
+    rows
+}
+
+/// Printed width of `s`: CJK and other wide characters take two columns.
+fn text_width(s: &str) -> usize {
+    s.chars().map(|c| if (c as u32) < 0x1100 { 1 } else { 2 }).sum()
+}
+
+fn pad_center(s: &str, width: usize) -> String {
+    let pad = width.saturating_sub(text_width(s)) / 2;
+    format!("{}{s}", " ".repeat(pad))
+}
+
+/// `left` and `right` on one line, or `right` on its own line when they don't fit.
+fn pad_pair(left: &str, right: &str, width: usize) -> String {
+    let used = text_width(left) + text_width(right);
+    if used < width {
+        format!("{left}{}{right}", " ".repeat(width - used))
+    } else {
+        format!("{left}\n{}{right}", " ".repeat(width.saturating_sub(text_width(right))))
+    }
+}
+
+pub fn render_text(receipt: &ReceiptView, width: usize) -> String {
+    let mut out = String::new();
+    for row in layout(receipt) {
+        let line = match row {
+            Row::Title(s) | Row::Center(s) => pad_center(&s, width),
+            Row::Text(s) => s,
+            Row::Pair(l, r) | Row::Total(l, r) => pad_pair(&l, &r, width),
+            Row::Rule => "-".repeat(width),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const FS: u8 = 0x1c;
+
+/// ESC/POS bytes for a receipt, text in GBK as Chinese printers expect.
+pub fn render_escpos(receipt: &ReceiptView, width: usize) -> Vec<u8> {
+    let gbk = |s: &str| encoding_rs::GBK.encode(s).0.into_owned();
+    // Initialise, then switch on Chinese character mode.
+    let mut out = vec![ESC, b'@', FS, b'&'];
+    for row in layout(receipt) {
+        match row {
+            Row::Title(s) => {
+                out.extend_from_slice(&[ESC, b'a', 1, GS, b'!', 0x11]);
+                out.extend(gbk(&s));
+                out.extend_from_slice(&[b'\n', GS, b'!', 0, ESC, b'a', 0]);
+            }
+            Row::Center(s) => {
+                out.extend_from_slice(&[ESC, b'a', 1]);
+                out.extend(gbk(&s));
+                out.extend_from_slice(&[b'\n', ESC, b'a', 0]);
+            }
+            Row::Text(s) => {
+                out.extend(gbk(&s));
+                out.push(b'\n');
+            }
+            Row::Pair(l, r) => {
+                out.extend(gbk(&pad_pair(&l, &r, width)));
+                out.push(b'\n');
+            }
+            Row::Total(l, r) => {
+                out.extend_from_slice(&[ESC, b'E', 1]);
+                out.extend(gbk(&pad_pair(&l, &r, width)));
+                out.extend_from_slice(&[b'\n', ESC, b'E', 0]);
+            }
+            Row::Rule => {
+                out.extend("-".repeat(width).into_bytes());
+                out.push(b'\n');
+            }
+        }
+    }
+    // Feed past the cutter and make a partial cut.
+    out.extend_from_slice(&[GS, b'V', 66, 3]);
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A self-contained, read-only HTML page for the receipt.
+pub fn render_html(receipt: &ReceiptView) -> String {
+    let mut body = String::new();
+    for row in layout(receipt) {
+        let html = match row {
+            Row::Title(s) => format!("<h1>{}</h1>", escape_html(&s)),
+            Row::Center(s) => format!("<p class=\"c\">{}</p>", escape_html(&s)),
+            Row::Text(s) => format!("<p>{}</p>", escape_html(&s)),
+            Row::Pair(l, r) => format!("<p class=\"p\"><span>{}</span><span>{}</span></p>", escape_html(&l), escape_html(&r)),
+            Row::Total(l, r) => format!("<p class=\"p t\"><span>{}</span><span>{}</span></p>", escape_html(&l), escape_html(&r)),
+            Row::Rule => String::from("<hr>"),
+        };
+        body.push_str(&html);
+        body.push('\n');
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n\
+         <style>body{{margin:0;background:#f2f2f2;font-family:ui-monospace,Menlo,Consolas,monospace}}\
+         main{{max-width:420px;margin:16px auto;padding:16px 20px;background:#fff;box-shadow:0 1px 4px rgba(0,0,0,.15)}}\
+         h1{{font-size:1.4em;text-align:center;margin:.2em 0}}p{{margin:.25em 0;white-space:pre-wrap}}\
+         .c{{text-align:center}}.p{{display:flex;justify-content:space-between;gap:1em}}.t{{font-weight:bold;font-size:1.15em}}\
+         hr{{border:0;border-top:1px dashed #999}}</style>\n</head>\n<body>\n<main>\n{body}</main>\n</body>\n</html>\n",
+        escape_html(&receipt.brand_name)
+    )
+}
+
+/// Queues a printed receipt for `order_id` on the configured printer.
+pub fn enqueue_receipt(conn: &Connection, order_id: &str, now: i64) -> Result<String, String> {
+    let settings = configured_printer(conn)?.ok_or_else(|| String::from("printer_not_configured"))?;
+    let receipt = load_receipt(conn, order_id)?;
+    enqueue_print_job(conn, Some(order_id), "receipt", &render_escpos(&receipt, line_width(&settings)), now)
+}
+
+/// Queues the receipt of a new sale when the printer is set to print every one.
+pub fn auto_print_receipt(conn: &Connection, order_id: &str, now: i64) -> Result<(), String> {
+    if configured_printer(conn)?.is_some_and(|s| s.auto_print.unwrap_or(false)) {
+        enqueue_receipt(conn, order_id, now)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn receipt_get(app: AppHandle, token: String, order_id: String) -> Result<ReceiptView, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    load_receipt(&conn, order_id.trim())
+}
+
+/// The receipt as `text` (the default, laid out like the printed one) or `html`.
+#[tauri::command]
+pub fn receipt_preview(app: AppHandle, token: String, order_id: String, format: Option<String>) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let receipt = load_receipt(&conn, order_id.trim())?;
+    match format.as_deref().map(str::trim).unwrap_or("text") {
+        "text" => {
+            let width = configured_printer(&conn)?.map(|s| line_width(&s)).unwrap_or(32);
+            Ok(render_text(&receipt, width))
+        }
+        "html" => Ok(render_html(&receipt)),
+        _ => Err(String::from("invalid")),
+    }
+}
+
+/// Prints (or reprints) the receipt of any order from the sales list.
+#[tauri::command]
+pub fn receipt_print(app: AppHandle, token: String, order_id: String) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+    enqueue_receipt(&conn, order_id.trim(), now)
+}
@@ -0,0 +1,149 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection};
+use crate::db::open_db;
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{ProductRow, RecipeItemRow, RecipeSetInput, RecipeUsageRow};
+use crate::commands::products::{load_linked_products, load_product, load_sellable_product};
+use crate::commands::units::{base_unit_cost, base_units_cost};
+use crate::money::Money;
+
+// A recipe (配方) lists the ingredients one base unit of a made-to-order
+// product consumes, in each ingredient's base unit. Selling the product moves
+// the ingredients instead of the product itself.
+
+#[derive(Debug, Clone)]
+pub struct RecipeIngredient {
+    pub product: ProductRow,
+    pub quantity: f64,
+}
+
+pub fn load_recipe(conn: &Connection, product_id: &str) -> Result<Vec<RecipeIngredient>, String> {
+    Ok(load_linked_products(conn, "product_recipe_items", "product_id", "ingredient_id", product_id)?
+        .into_iter()
+        .map(|(product, quantity)| RecipeIngredient { product, quantity })
+        .collect())
+}
+
+/// Ingredient cost of one base unit of the recipe's product.
+pub fn recipe_unit_cost(ingredients: &[RecipeIngredient]) -> Money {
+    ingredients
+        .iter()
+        .map(|i| base_units_cost(i.product.cost_price, i.product.spec, i.quantity))
+        .sum()
+}
+
+#[tauri::command]
+pub fn recipe_get(app: AppHandle, token: String, product_id: String) -> Result<Vec<RecipeItemRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+    Ok(load_recipe(&conn, product_id.trim())?
+        .into_iter()
+        .map(|i| RecipeItemRow {
+            unit_cost: base_unit_cost(i.product.cost_price, i.product.spec),
+            ingredient_id: i.product.id,
+            ingredient_name: i.product.name,
+            base_unit: i.product.base_unit,
+            quantity: i.quantity,
+        })
+        .collect())
+}
+
+/// Replaces a product's recipe; an empty list makes it a stocked item again.
+#[tauri::command]
+pub fn recipe_set(app: AppHandle, input: RecipeSetInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let product_id = input.product_id.trim().to_string();
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let product = load_product(&tx, &product_id)?.ok_or_else(|| format!("product_not_found: {product_id}"))?;
+    if product.kind == "bundle" && !input.items.is_empty() {
+        return Err(String::from("bundle_recipe"));
+    }
+    if !input.items.is_empty() {
+        let used_in: i64 = tx
+            .query_row("SELECT COUNT(1) FROM product_recipe_items WHERE ingredient_id = ?1", [&product_id], |r| r.get(0))
+            .map_err(|e| format!("query recipe usage: {e}"))?;
+        if used_in > 0 {
+            return Err(String::from("nested_recipe"));
+        }
+    }
+
+    tx.execute("DELETE FROM product_recipe_items WHERE product_id = ?1", [&product_id])
+        .map_err(|e| format!("clear recipe: {e}"))?;
+    for (i, it) in input.items.iter().enumerate() {
+        let ingredient_id = it.ingredient_id.trim();
+        if ingredient_id == product_id || !it.quantity.is_finite() || it.quantity <= 0.0 {
+            return Err(String::from("invalid"));
+        }
+        let ingredient = load_sellable_product(&tx, ingredient_id)?.ok_or_else(|| format!("product_not_found: {ingredient_id}"))?;
+        if ingredient.kind == "bundle" || !load_recipe(&tx, ingredient_id)?.is_empty() {
+            return Err(format!("nested_recipe: {}", ingredient.name));
+        }
+        tx.execute(
+            "INSERT INTO product_recipe_items(product_id, ingredient_id, quantity, sort_order) VALUES(?1, ?2, ?3, ?4) \
+             ON CONFLICT(product_id, ingredient_id) DO UPDATE SET quantity = quantity + excluded.quantity",
+            params![product_id, ingredient_id, it.quantity, i as i64],
+        )
+        .map_err(|e| format!("insert recipe item: {e}"))?;
+    }
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+/// Theoretical usage is what sold recipes should have consumed, net of
+/// refunds; actual usage adds the stock found missing at stocktakes (negative
+/// adjustments), so the variance is waste, over-pouring or missing stock.
+/// Direct sales of an ingredient and stock found in surplus are left out.
+#[tauri::command]
+pub fn recipe_usage_report(app: AppHandle, token: String, date_from: String, date_to: String) -> Result<Vec<RecipeUsageRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let date_from = date_from.trim().to_string();
+    let date_to = date_to.trim().to_string();
+    if date_from.is_empty() || date_to.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+
+    // A refund counts when it puts back what the refunded line's recipe took,
+    // not a direct sale or add-on of the ingredient.
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, p.name, p.base_unit, p.cost_price, p.spec, \
+                    -COALESCE(SUM(CASE WHEN m.in_recipe THEN m.delta END), 0), \
+                    -COALESCE(SUM(CASE WHEN m.in_recipe OR (m.reason = 'adjust' AND m.delta < 0) THEN m.delta END), 0) \
+             FROM products p \
+             JOIN (SELECT s.*, (s.reason = 'recipe' OR (s.reason = 'refund' AND EXISTS \
+                       (SELECT 1 FROM sales_items i JOIN stock_movements o ON o.ref_id = i.ref_item_id \
+                        WHERE i.id = s.ref_id AND o.product_id = s.product_id AND o.reason = 'recipe'))) AS in_recipe \
+                   FROM stock_movements s) m ON m.product_id = p.id \
+             WHERE p.id IN (SELECT ingredient_id FROM product_recipe_items) \
+               AND date(m.created_at, 'unixepoch', 'localtime') BETWEEN ?1 AND ?2 \
+             GROUP BY p.id ORDER BY p.name ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map(params![date_from, date_to], |r| {
+            let cost_price: Money = r.get(3)?;
+            let spec: f64 = r.get(4)?;
+            let theoretical: f64 = r.get(5)?;
+            let actual: f64 = r.get(6)?;
+            Ok(RecipeUsageRow {
+                ingredient_id: r.get(0)?,
+                ingredient_name: r.get(1)?,
+                base_unit: r.get(2)?,
+                theoretical,
+                actual,
+                variance: actual - theoretical,
+                variance_cost: base_units_cost(cost_price, spec, actual - theoretical),
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
@@ -0,0 +1,631 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::require_admin;
+use crate::constants::{ORDER_KIND_SALE, ORDER_KIND_REFUND, ORDER_KIND_VOID, PAYMENT_CASH, PAYMENT_METHODS};
+use crate::models::OrderRefundInput;
+use crate::commands::inventory::{adjust_location_stock, sync_product_totals};
+use crate::commands::units::base_units_cost;
+use crate::commands::payments::{record_payments, Tender};
+use crate::commands::members::{member_paid, post_wallet_entry, WalletPosting};
+use crate::commands::loyalty::{expire_points, post_points, PointsPosting};
+use crate::money::Money;
+
+// A completed sale is never edited or deleted. A refund or void is an order of
+// its own kind pointing back at the sale (`ref_order_id`), with negative lines
+// (`ref_item_id`), negative payments and stock movements that put back what the
+// sale took, so shift and finance totals net out by plain summing.
+
+struct SoldOrder {
+    id: String,
+    date_ymd: String,
+    shift: String,
+    employee: String,
+    kind: String,
+    status: String,
+    total_revenue: Money,
+    total_profit: Money,
+    rounding: Money,
+}
+
+struct SoldLine {
+    id: String,
+    product_id: Option<String>,
+    product_name: String,
+    sales: f64,
+    revenue: Money,
+    unit_price: Option<Money>,
+    cost_price: Money,
+    spec: f64,
+    bundle_id: Option<String>,
+    /// Points the line was given away for, if it was.
+    redeem_points: Option<i64>,
+    refunded: f64,
+    refunded_revenue: Money,
+}
+
+impl SoldLine {
+    fn remaining(&self) -> f64 {
+        self.sales - self.refunded
+    }
+}
+
+/// Where and why a reversal is booked.
+struct Reversal {
+    kind: &'static str,
+    date_ymd: String,
+    shift: String,
+    employee: String,
+    reason: String,
+    method: Option<String>,
+}
+
+fn load_order(conn: &Connection, id: &str) -> Result<SoldOrder, String> {
+    conn.query_row(
+        "SELECT id, date_ymd, shift, employee, kind, status, total_revenue, total_profit, rounding FROM sales_orders WHERE id = ?1",
+        [id],
+        |r| {
+            Ok(SoldOrder {
+                id: r.get(0)?,
+                date_ymd: r.get(1)?,
+                shift: r.get(2)?,
+                employee: r.get(3)?,
+                kind: r.get(4)?,
+                status: r.get(5)?,
+                total_revenue: r.get(6)?,
+                total_profit: r.get(7)?,
+                rounding: r.get(8)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| format!("query order: {e}"))?
+    .ok_or_else(|| String::from("not_found"))
+}
+
+fn load_lines(conn: &Connection, order_id: &str) -> Result<Vec<SoldLine>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.id, i.product_id, i.product_name, COALESCE(i.sales, 0), COALESCE(i.revenue, 0), i.unit_price, \
+                    COALESCE(i.cost_price, 0), COALESCE(i.spec, 1), i.bundle_id, i.redeem_points, \
+                    COALESCE((SELECT -SUM(r.sales) FROM sales_items r WHERE r.ref_item_id = i.id), 0), \
+                    COALESCE((SELECT -SUM(r.revenue) FROM sales_items r WHERE r.ref_item_id = i.id), 0) \
+             FROM sales_items i WHERE i.order_id = ?1 ORDER BY i.created_at ASC",
+        )
+        .map_err(|e| format!("prepare lines: {e}"))?;
+    let rows = stmt
+        .query_map([order_id], |r| {
+            Ok(SoldLine {
+                id: r.get(0)?,
+                product_id: r.get(1)?,
+                product_name: r.get(2)?,
+                sales: r.get(3)?,
+                revenue: r.get(4)?,
+                unit_price: r.get(5)?,
+                cost_price: r.get(6)?,
+                spec: r.get(7)?,
+                bundle_id: r.get(8)?,
+                redeem_points: r.get(9)?,
+                refunded: r.get(10)?,
+                refunded_revenue: r.get(11)?,
+            })
+        })
+        .map_err(|e| format!("query lines: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row lines: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// Puts back `ratio` of everything the sold line drew from stock: the product
+/// itself or its recipe ingredients, plus add-ons booked on the line.
+fn restore_stock(
+    conn: &Connection,
+    order_id: &str,
+    line: &SoldLine,
+    ratio: f64,
+    refund_item_id: &str,
+    actor_id: &str,
+    now: i64,
+) -> Result<(), String> {
+    let mut taken: Vec<(String, String, f64)> = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare("SELECT product_id, location_id, SUM(delta) FROM stock_movements WHERE ref_id = ?1 GROUP BY product_id, location_id")
+            .map_err(|e| format!("prepare movements: {e}"))?;
+        let rows = stmt
+            .query_map([&line.id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .map_err(|e| format!("query movements: {e}"))?;
+        for r in rows {
+            taken.push(r.map_err(|e| format!("row movements: {e}"))?);
+        }
+    }
+    // Older sales booked their movements against the order; fall back to the
+    // product's own sale movement there.
+    if taken.is_empty() {
+        if let Some(product_id) = line.product_id.as_deref() {
+            let location: Option<String> = conn
+                .query_row(
+                    "SELECT location_id FROM stock_movements WHERE ref_id = ?1 AND product_id = ?2 AND reason = 'sale' LIMIT 1",
+                    params![order_id, product_id],
+                    |r| r.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("query movements: {e}"))?;
+            if let Some(location_id) = location {
+                taken.push((product_id.to_string(), location_id, -line.sales));
+            }
+        }
+    }
+
+    for (product_id, location_id, delta) in taken.iter() {
+        let back = -delta * ratio;
+        if back.abs() < 1e-9 {
+            continue;
+        }
+        adjust_location_stock(conn, product_id, location_id, back, "refund", Some(refund_item_id), Some(actor_id))?;
+        sync_product_totals(conn, product_id, now)?;
+    }
+    Ok(())
+}
+
+fn refund_tender(method: &str, amount: Money) -> Tender {
+    Tender {
+        method: method.to_string(),
+        amount: -amount,
+        tendered: -amount,
+        change: Money::ZERO,
+        reference: None,
+    }
+}
+
+/// Pays `amount` back, by default through the methods the order was paid
+/// with, latest first and never more than each method still holds.
+fn refund_tenders(conn: &Connection, order_id: &str, amount: Money, method: Option<&str>) -> Result<Vec<Tender>, String> {
+    let mut left = amount;
+    if left <= Money::ZERO {
+        return Ok(Vec::new());
+    }
+    if let Some(m) = method {
+        return Ok(vec![refund_tender(m, left)]);
+    }
+
+    let mut held: Vec<(String, Money)> = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT method, SUM(amount) FROM payments \
+                 WHERE order_id = ?1 OR order_id IN (SELECT id FROM sales_orders WHERE ref_order_id = ?1) \
+                 GROUP BY method ORDER BY MIN(rowid) DESC",
+            )
+            .map_err(|e| format!("prepare payments: {e}"))?;
+        let rows = stmt
+            .query_map([order_id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .map_err(|e| format!("query payments: {e}"))?;
+        for r in rows {
+            held.push(r.map_err(|e| format!("row payments: {e}"))?);
+        }
+    }
+
+    let mut out: Vec<Tender> = Vec::new();
+    for (m, amount) in held.iter() {
+        let take = left.min(*amount);
+        if take <= Money::ZERO {
+            continue;
+        }
+        out.push(refund_tender(m, take));
+        left -= take;
+    }
+    // Orders settled before payments were recorded are paid back in cash.
+    if left > Money::ZERO {
+        match out.iter_mut().find(|t| t.method == PAYMENT_CASH) {
+            Some(t) => {
+                t.amount -= left;
+                t.tendered -= left;
+            }
+            None => out.push(refund_tender(PAYMENT_CASH, left)),
+        }
+    }
+    Ok(out)
+}
+
+/// Gives back the points the returned lines were redeemed for and takes back
+/// the points the returned revenue earned. The reversal that empties the order
+/// settles whatever is left, so a sale returned in parts nets to nothing. A
+/// member who has already spent the earned points keeps what they spent.
+fn reverse_points(
+    conn: &Connection,
+    order: &SoldOrder,
+    refund_id: &str,
+    completes: bool,
+    rev: &Reversal,
+    actor_id: &str,
+    now: i64,
+) -> Result<(), String> {
+    let member_id: Option<String> = conn
+        .query_row("SELECT member_id FROM sales_orders WHERE id = ?1", [&order.id], |r| r.get(0))
+        .map_err(|e| format!("query order member: {e}"))?;
+    let Some(member_id) = member_id else {
+        return Ok(());
+    };
+    let points_of = |kind: &str, order_filter: &str| -> Result<i64, String> {
+        conn.query_row(
+            &format!("SELECT COALESCE(SUM(ABS(points)), 0) FROM member_points_entries WHERE kind = ?1 AND order_id {order_filter}"),
+            params![kind, order.id],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("query order points: {e}"))
+    };
+    // The reversal's own lines carry the redeemed points and revenue they return.
+    let (points_back, revenue): (i64, Money) = conn
+        .query_row(
+            "SELECT -COALESCE(SUM(redeem_points), 0), -COALESCE(SUM(revenue), 0) FROM sales_items WHERE order_id = ?1",
+            [refund_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .map_err(|e| format!("query refund lines: {e}"))?;
+    let prior = "IN (SELECT id FROM sales_orders WHERE ref_order_id = ?2)";
+    let redeemed_left = points_of("redeem", "= ?2")? - points_of("return", prior)?;
+    let earned = points_of("earn", "= ?2")?;
+    let earned_left = earned - points_of("clawback", prior)?;
+
+    let points_back = if completes { redeemed_left } else { points_back.min(redeemed_left) };
+    if points_back > 0 {
+        // Points given back keep the expiry of the lots they were redeemed
+        // from; if that has passed they lapse at the next sweep.
+        let expires_at: Option<i64> = conn
+            .query_row(
+                "SELECT MAX(expires_at) FROM member_points_entries WHERE kind = 'redeem' AND order_id = ?1",
+                [&order.id],
+                |r| r.get(0),
+            )
+            .map_err(|e| format!("query redeemed points expiry: {e}"))?;
+        post_points(
+            conn,
+            &member_id,
+            &PointsPosting {
+                kind: "return",
+                points: points_back,
+                expires_at: expires_at.map(|t| t.max(now)),
+                order_id: Some(refund_id),
+                date_ymd: &rev.date_ymd,
+                note: Some(&rev.reason),
+                actor_id,
+            },
+            now,
+        )?;
+    }
+
+    let clawback = if completes {
+        earned_left
+    } else if order.total_revenue > Money::ZERO {
+        let share = revenue.fen() as f64 / order.total_revenue.fen() as f64;
+        (earned as f64 * share).round() as i64
+    } else {
+        0
+    }
+    .min(earned_left);
+    if clawback > 0 {
+        expire_points(conn, Some(&member_id), now)?;
+        let available: i64 = conn
+            .query_row("SELECT points FROM members WHERE id = ?1", [&member_id], |r| r.get(0))
+            .map_err(|e| format!("query member points: {e}"))?;
+        let clawback = clawback.min(available);
+        if clawback > 0 {
+            post_points(
+                conn,
+                &member_id,
+                &PointsPosting {
+                    kind: "clawback",
+                    points: -clawback,
+                    expires_at: None,
+                    order_id: Some(refund_id),
+                    date_ymd: &rev.date_ymd,
+                    note: Some(&rev.reason),
+                    actor_id,
+                },
+                now,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Books the reversal of `picks[i]` base units of each line of `order` and
+/// returns the id of the reversing order.
+fn reverse_order(
+    conn: &Connection,
+    order: &SoldOrder,
+    lines: &[SoldLine],
+    picks: &[f64],
+    rev: &Reversal,
+    actor_id: &str,
+) -> Result<String, String> {
+    let now = now_ts()?;
+    let refund_id = Uuid::new_v4().to_string();
+    let mut revenue = Money::ZERO;
+    let mut profit = Money::ZERO;
+
+    for (line, qty) in lines.iter().zip(picks.iter()) {
+        if *qty <= 1e-9 {
+            continue;
+        }
+        let ratio = qty / line.sales;
+        // Line revenue is net of the order's coupon and 抹零 shares. Rounding
+        // each part must not pay back more than the line has left, and the
+        // part that empties the line pays back exactly what is left.
+        let left = line.revenue - line.refunded_revenue;
+        let line_revenue = if line.remaining() - qty <= 1e-9 { left } else { line.revenue.mul_f64(ratio).min(left) };
+        revenue += line_revenue;
+        let redeem_points = line.redeem_points.map(|p| -((p as f64 * ratio).round() as i64));
+        profit += line_revenue - base_units_cost(line.cost_price, line.spec, *qty);
+
+        let item_id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO sales_items(id, order_id, product_id, product_name, sales, revenue, unit_price, cost_price, spec, bundle_id, redeem_points, ref_item_id, created_at) \
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![item_id, refund_id, line.product_id, line.product_name, -qty, -line_revenue, line.unit_price, line.cost_price, line.spec, line.bundle_id, redeem_points, line.id, now],
+        )
+        .map_err(|e| format!("insert refund item: {e}"))?;
+        restore_stock(conn, &order.id, line, ratio, &item_id, actor_id, now)?;
+
+        let promotion: Option<(String, String, Money)> = conn
+            .query_row(
+                "SELECT promotion_id, promotion_name, discount FROM sales_item_promotions WHERE sales_item_id = ?1",
+                [&line.id],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| format!("query promotion: {e}"))?;
+        if let Some((promotion_id, promotion_name, discount)) = promotion {
+            conn.execute(
+                "INSERT INTO sales_item_promotions(id, sales_item_id, order_id, promotion_id, promotion_name, discount, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![Uuid::new_v4().to_string(), item_id, refund_id, promotion_id, promotion_name, -discount.mul_f64(ratio), now],
+            )
+            .map_err(|e| format!("insert refund promotion: {e}"))?;
+        }
+    }
+
+    // The reversal that empties the order takes back whatever is left of it,
+    // so rounding and add-on costs come out exact once everything is returned.
+    let completes = lines.iter().zip(picks.iter()).all(|(l, q)| l.remaining() - q <= 1e-9);
+    let (prior_revenue, prior_profit, prior_rounding): (Money, Money, Money) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(total_revenue), 0), COALESCE(SUM(total_profit), 0), COALESCE(SUM(rounding), 0) FROM sales_orders WHERE ref_order_id = ?1",
+            [&order.id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )
+        .map_err(|e| format!("query prior refunds: {e}"))?;
+    let (total_revenue, total_profit, rounding) = if completes {
+        (
+            -(order.total_revenue + prior_revenue),
+            -(order.total_profit + prior_profit),
+            -(order.rounding + prior_rounding),
+        )
+    } else {
+        // Sales from before discounts were spread over the lines have lines
+        // worth more than was paid; never pay back more than the order has left.
+        (-revenue.min(order.total_revenue + prior_revenue), -profit, Money::ZERO)
+    };
+
+    conn.execute(
+        "INSERT INTO sales_orders(id, date_ymd, shift, employee, total_revenue, total_profit, rounding, kind, status, ref_order_id, reason, actor_id, member_id, created_at, updated_at) \
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 'completed', ?9, ?10, ?11, (SELECT member_id FROM sales_orders WHERE id = ?9), ?12, ?13)",
+        params![refund_id, rev.date_ymd, rev.shift, rev.employee, total_revenue, total_profit, rounding, rev.kind, order.id, rev.reason, actor_id, now, now],
+    )
+    .map_err(|e| format!("insert refund order: {e}"))?;
+    let tenders = refund_tenders(conn, &order.id, -total_revenue, rev.method.as_deref())?;
+    record_payments(conn, &refund_id, &tenders, now)?;
+
+    // Whatever was paid from a member balance goes back onto it.
+    let to_balance = -member_paid(&tenders);
+    if !to_balance.is_zero() {
+        let member_id: Option<String> = conn
+            .query_row("SELECT member_id FROM sales_orders WHERE id = ?1", [&order.id], |r| r.get(0))
+            .map_err(|e| format!("query order member: {e}"))?;
+        let member_id = member_id.ok_or_else(|| String::from("member_required"))?;
+        post_wallet_entry(
+            conn,
+            &member_id,
+            &WalletPosting {
+                kind: "refund",
+                amount: to_balance,
+                method: None,
+                order_id: Some(&refund_id),
+                date_ymd: &rev.date_ymd,
+                shift: Some(&rev.shift),
+                note: Some(&rev.reason),
+                actor_id,
+            },
+            now,
+        )?;
+    }
+    reverse_points(conn, order, &refund_id, completes, rev, actor_id, now)?;
+
+    let status = if rev.kind == ORDER_KIND_VOID {
+        "voided"
+    } else if completes {
+        "refunded"
+    } else {
+        "partially_refunded"
+    };
+    conn.execute(
+        "UPDATE sales_orders SET status = ?2, updated_at = ?3 WHERE id = ?1",
+        params![order.id, status, now],
+    )
+    .map_err(|e| format!("update order: {e}"))?;
+    Ok(refund_id)
+}
+
+/// Cancels a sale outright, booked in the shift it was rung up in. Only a sale
+/// with nothing refunded yet can be voided.
+#[tauri::command]
+pub fn order_void(app: AppHandle, token: String, order_id: String, reason: String) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, token.trim())?;
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let order = load_order(&tx, order_id.trim())?;
+    if order.kind != ORDER_KIND_SALE || order.status != "completed" {
+        return Err(String::from("order_closed"));
+    }
+    let lines = load_lines(&tx, &order.id)?;
+    let picks: Vec<f64> = lines.iter().map(|l| l.remaining().max(0.0)).collect();
+    let rev = Reversal {
+        kind: ORDER_KIND_VOID,
+        date_ymd: order.date_ymd.clone(),
+        shift: order.shift.clone(),
+        employee: order.employee.clone(),
+        reason,
+        method: None,
+    };
+    let id = reverse_order(&tx, &order, &lines, &picks, &rev, &actor_id)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(id)
+}
+
+/// Returns some or all of a sale, booked in the current shift.
+#[tauri::command]
+pub fn order_refund(app: AppHandle, input: OrderRefundInput) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = require_admin(&conn, input.token.trim())?;
+
+    let date_ymd = input.date_ymd.trim().to_string();
+    let shift = input.shift.trim().to_string();
+    let employee = input.employee.trim().to_string();
+    let reason = input.reason.trim().to_string();
+    if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() || reason.is_empty() {
+        return Err(String::from("missing fields"));
+    }
+    let method = input.method.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    if let Some(m) = method.as_deref() {
+        if !PAYMENT_METHODS.contains(&m) {
+            return Err(format!("invalid payment: {m}"));
+        }
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let order = load_order(&tx, input.order_id.trim())?;
+    if order.kind != ORDER_KIND_SALE || (order.status != "completed" && order.status != "partially_refunded") {
+        return Err(String::from("order_closed"));
+    }
+    let lines = load_lines(&tx, &order.id)?;
+    let picks: Vec<f64> = match input.lines {
+        None => lines.iter().map(|l| l.remaining().max(0.0)).collect(),
+        Some(requested) => {
+            let mut picks = vec![0.0; lines.len()];
+            for r in requested.iter() {
+                let idx = lines
+                    .iter()
+                    .position(|l| l.id == r.item_id.trim())
+                    .ok_or_else(|| format!("item_not_found: {}", r.item_id.trim()))?;
+                if !r.quantity.is_finite() || r.quantity <= 0.0 {
+                    return Err(String::from("invalid"));
+                }
+                picks[idx] += r.quantity;
+                if picks[idx] > lines[idx].remaining() + 1e-9 {
+                    return Err(format!("over_refund: {}", lines[idx].product_name));
+                }
+            }
+            picks
+        }
+    };
+    if picks.iter().all(|q| *q <= 1e-9) {
+        return Err(String::from("nothing_to_refund"));
+    }
+
+    let rev = Reversal {
+        kind: ORDER_KIND_REFUND,
+        date_ymd,
+        shift,
+        employee,
+        reason,
+        method,
+    };
+    let id = reverse_order(&tx, &order, &lines, &picks, &rev, &actor_id)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use crate::models::{PosCheckoutInput, PosItem};
+    use crate::commands::sales::checkout;
+
+    fn item(product_id: &str, quantity: f64) -> PosItem {
+        PosItem { product_id: product_id.to_string(), barcode: None, quantity, unit: None, modifiers: None, redeem: false }
+    }
+
+    fn refund(conn: &Connection, order_id: &str, picks: &[f64]) -> Money {
+        let order = load_order(conn, order_id).unwrap();
+        let lines = load_lines(conn, order_id).unwrap();
+        let rev = Reversal {
+            kind: ORDER_KIND_REFUND,
+            date_ymd: String::from("2025-10-10"),
+            shift: String::from("白班"),
+            employee: String::from("小王"),
+            reason: String::from("退货"),
+            method: None,
+        };
+        let id = reverse_order(conn, &order, &lines, picks, &rev, "admin").unwrap();
+        let paid_back: Money = conn
+            .query_row("SELECT COALESCE(SUM(amount), 0) FROM payments WHERE order_id = ?1", [&id], |r| r.get(0))
+            .unwrap();
+        assert_eq!(paid_back, load_order(conn, &id).unwrap().total_revenue);
+        -paid_back
+    }
+
+    #[test]
+    fn coupon_order_refunds_what_was_paid() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products(id, name, category, unit_price, cost_price, created_at, updated_at) VALUES('cola', '可乐', '饮品', 1235, 500, 0, 0), ('chips', '薯片', '零食', 805, 300, 0, 0);\
+             INSERT INTO coupons(id, code, name, face_value, created_at, updated_at) VALUES('c1', 'SAVE5', '立减5元', 500, 0, 0);",
+        )
+        .unwrap();
+
+        // ¥32.75 less the ¥5 coupon is ¥27.75, rounded down to ¥27.70.
+        let input = PosCheckoutInput {
+            token: String::new(),
+            date_ymd: String::from("2025-10-09"),
+            shift: String::from("白班"),
+            employee: String::from("小王"),
+            items: vec![item("cola", 2.0), item("chips", 1.0)],
+            location_id: None,
+            payments: None,
+            rounding: Some(String::from("jiao")),
+            order_id: None,
+            idempotency_key: None,
+            member_id: None,
+            coupon_code: Some(String::from("SAVE5")),
+            terminal: None,
+        };
+        checkout(&conn, "o1", &input, "admin", now_ts().unwrap()).unwrap();
+        let order = load_order(&conn, "o1").unwrap();
+        assert_eq!(order.total_revenue, Money::from_fen(2770));
+        let lines = load_lines(&conn, "o1").unwrap();
+        let revenues: Vec<Money> = lines.iter().map(|l| l.revenue).collect();
+        assert_eq!(revenues, [Money::from_fen(2089), Money::from_fen(681)]);
+
+        // One cola, then the other: the halves add up to the line exactly.
+        assert_eq!(refund(&conn, "o1", &[1.0, 0.0]), Money::from_fen(1045));
+        assert_eq!(refund(&conn, "o1", &[1.0, 0.0]), Money::from_fen(1044));
+        assert_eq!(refund(&conn, "o1", &[0.0, 1.0]), Money::from_fen(681));
+
+        let status: String = conn.query_row("SELECT status FROM sales_orders WHERE id = 'o1'", [], |r| r.get(0)).unwrap();
+        assert_eq!(status, "refunded");
+        let net: Money = conn
+            .query_row("SELECT SUM(amount) FROM payments p JOIN sales_orders o ON o.id = p.order_id WHERE o.id = 'o1' OR o.ref_order_id = 'o1'", [], |r| r.get(0))
+            .unwrap();
+        assert!(net.is_zero());
+    }
+}
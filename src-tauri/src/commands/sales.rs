@@ -1,17 +1,39 @@
 use tauri::AppHandle;
-use rusqlite::{params, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use uuid::Uuid;
 use sha2::{Digest, Sha256};
 use crate::db::{open_db, now_ts, now_ymd};
-use crate::state::{auth_resolve_account_id};
+use crate::state::{auth_resolve_account_id, log_to_system};
 use crate::models::*;
+use crate::commands::inventory::{adjust_location_stock, sync_product_totals, default_front_location, sale_location, load_restock_summary};
+use crate::commands::units::{base_units_cost, unit_factor};
+use crate::commands::products::{load_product, load_sellable_product, lookup_product_by_code};
+use crate::commands::modifiers::{resolve_modifiers, SelectedModifier};
+use crate::commands::bundles::{load_bundle_components, split_revenue};
+use crate::commands::recipes::{load_recipe, recipe_unit_cost};
+use crate::commands::pricing::price_at;
+use crate::commands::promotions::{active_promotions, apply_promotions, PriceLine};
+use crate::commands::payments::{apply_rounding, settle_tenders, record_payments};
+use crate::commands::idempotency::{replayed_result, remember_result};
+use crate::commands::receipts::auto_print_receipt;
+use crate::commands::members::{member_paid, post_wallet_entry, require_active_member, WalletPosting};
+use crate::commands::loyalty::{award_points, points_value, redeem_cost, redeem_points};
+use crate::commands::coupons::redeem_coupon;
+use crate::commands::categories::category_account_item;
+use crate::commands::display::show_paid;
+use crate::constants::REDEEM_MODE_POINTS;
+use crate::money::Money;
 
 #[tauri::command]
 pub fn shift_record_insert(app: AppHandle, input: ShiftRecordInsertInput) -> Result<String, String> {
-    let conn = open_db(&app)?;
+    let mut conn = open_db(&app)?;
     let now = now_ts()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| format!("tx: {e}"))?;
+    if let Some(id) = replayed_result(&tx, "shift_record_insert", input.idempotency_key.as_deref(), now)? {
+        return Ok(id);
+    }
     let id = Uuid::new_v4().to_string();
-    conn.execute(
+    tx.execute(
         "INSERT INTO shift_records(id, date_ymd, shift, employee, wangfei, shouhuo, meituan, zhichu, income, yingjiao, created_at)
          VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
         params![
@@ -20,6 +42,8 @@ pub fn shift_record_insert(app: AppHandle, input: ShiftRecordInsertInput) -> Res
             input.income, input.yingjiao, now
         ]
     ).map_err(|e| format!("insert: {e}"))?;
+    remember_result(&tx, "shift_record_insert", input.idempotency_key.as_deref(), &id, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
     Ok(id)
 }
 
@@ -209,7 +233,8 @@ pub fn sales_orders_list(
     let e = employee.unwrap_or_default().trim().to_string();
 
     let mut sql = String::from(
-        "SELECT id, date_ymd, shift, employee, total_revenue, total_profit, created_at \
+        "SELECT id, date_ymd, shift, employee, total_revenue, total_profit, kind, status, ref_order_id, reason, created_at, \
+                (SELECT COUNT(1) FROM print_jobs p WHERE p.order_id = sales_orders.id AND p.kind = 'receipt' AND p.status = 'done') \
          FROM sales_orders",
     );
     let mut where_parts: Vec<&str> = Vec::new();
@@ -245,7 +270,12 @@ pub fn sales_orders_list(
             employee: r.get(3)?,
             total_revenue: r.get(4)?,
             total_profit: r.get(5)?,
-            created_at: r.get(6)?,
+            kind: r.get(6)?,
+            status: r.get(7)?,
+            ref_order_id: r.get(8)?,
+            reason: r.get(9)?,
+            print_count: r.get(11)?,
+            created_at: r.get(10)?,
         })
     };
 
@@ -272,7 +302,7 @@ pub fn sales_items_list(app: AppHandle, order_id: String) -> Result<Vec<SalesIte
     }
     let mut stmt = conn
         .prepare(
-            "SELECT id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at \
+            "SELECT id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at, bundle_id, product_id, ref_item_id \
              FROM sales_items WHERE order_id = ?1 ORDER BY created_at ASC",
         )
         .map_err(|x| format!("prepare: {x}"))?;
@@ -297,6 +327,9 @@ pub fn sales_items_list(app: AppHandle, order_id: String) -> Result<Vec<SalesIte
                 cost_price: r.get(15)?,
                 spec: r.get(16)?,
                 created_at: r.get(17)?,
+                bundle_id: r.get(18)?,
+                product_id: r.get(19)?,
+                ref_item_id: r.get(20)?,
             })
         })
         .map_err(|x| format!("query_map: {x}"))?;
@@ -380,6 +413,25 @@ pub fn accounting_entries_list(
     Ok(out)
 }
 
+pub const MEITUAN_ORDER_COLUMNS: &str = "id, date_ymd, shift, employee, coupon_no, raw_text, amount, discount, financial, bar_total, created_at, bundle_id";
+
+pub fn map_meituan_order_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<MeituanOrderRow> {
+    Ok(MeituanOrderRow {
+        id: r.get(0)?,
+        date_ymd: r.get(1)?,
+        shift: r.get(2)?,
+        employee: r.get(3)?,
+        coupon_no: r.get(4)?,
+        raw_text: r.get(5)?,
+        amount: r.get(6)?,
+        discount: r.get(7)?,
+        financial: r.get(8)?,
+        bar_total: r.get(9)?,
+        created_at: r.get(10)?,
+        bundle_id: r.get(11)?,
+    })
+}
+
 #[tauri::command]
 pub fn meituan_orders_list(
     app: AppHandle,
@@ -394,10 +446,7 @@ pub fn meituan_orders_list(
     let s = shift.unwrap_or_default().trim().to_string();
     let e = employee.unwrap_or_default().trim().to_string();
 
-    let mut sql = String::from(
-        "SELECT id, date_ymd, shift, employee, coupon_no, raw_text, amount, discount, financial, bar_total, created_at \
-         FROM meituan_orders",
-    );
+    let mut sql = format!("SELECT {MEITUAN_ORDER_COLUMNS} FROM meituan_orders");
     let mut where_parts: Vec<&str> = Vec::new();
     let mut params_v: Vec<String> = Vec::new();
 
@@ -424,23 +473,7 @@ pub fn meituan_orders_list(
     let mut stmt = conn.prepare(&sql).map_err(|x| format!("prepare: {x}"))?;
     let mut out = Vec::new();
 
-    let map_meituan_row = |r: &rusqlite::Row<'_>| {
-        Ok(MeituanOrderRow {
-            id: r.get(0)?,
-            date_ymd: r.get(1)?,
-            shift: r.get(2)?,
-            employee: r.get(3)?,
-            coupon_no: r.get(4)?,
-            raw_text: r.get(5)?,
-            amount: r.get(6)?,
-            discount: r.get(7)?,
-            financial: r.get(8)?,
-            bar_total: r.get(9)?,
-            created_at: r.get(10)?,
-        })
-    };
-
-    let rows = stmt.query_map(rusqlite::params_from_iter(params_v), map_meituan_row).map_err(|x| format!("query_map: {x}"))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params_v), map_meituan_order_row).map_err(|x| format!("query_map: {x}"))?;
 
     for r in rows {
         out.push(r.map_err(|x| format!("row: {x}"))?);
@@ -448,6 +481,15 @@ pub fn meituan_orders_list(
     Ok(out)
 }
 
+/// The item an entry is booked under: as typed, or its category's default.
+fn entry_item(conn: &Connection, it: &AccountingEntryInput) -> Result<String, String> {
+    let item = it.item.trim();
+    match it.category.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(category) if item.is_empty() => category_account_item(conn, category),
+        _ => Ok(item.to_string()),
+    }
+}
+
 #[tauri::command]
 pub fn accounting_entries_create_from_shift(
     app: AppHandle,
@@ -463,11 +505,15 @@ pub fn accounting_entries_create_from_shift(
         return Err(String::from("missing fields"));
     }
 
-    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| format!("tx: {e}"))?;
+    let key = input.idempotency_key.clone();
+    if let Some(count) = replayed_result(&tx, "accounting_entries_create_from_shift", key.as_deref(), now)? {
+        return count.parse::<usize>().map_err(|e| format!("replayed result: {e}"));
+    }
     let mut inserted: usize = 0;
 
     for it in input.expenses.into_iter() {
-        let item = it.item.trim().to_string();
+        let item = entry_item(&tx, &it)?;
         if item.is_empty() { continue; }
         let id = Uuid::new_v4().to_string();
         tx.execute(
@@ -478,16 +524,17 @@ pub fn accounting_entries_create_from_shift(
     }
 
     for it in input.incomes.into_iter() {
-        let item = it.item.trim().to_string();
+        let item = entry_item(&tx, &it)?;
         if item.is_empty() { continue; }
         let id = Uuid::new_v4().to_string();
         tx.execute(
-            "INSERT INTO accounting_entries(id, date_ymd, shift, employee, entry_type, item, amount, bar_pay, finance_pay, created_at) VALUES(?1, ?2, ?3, ?4, 'income', ?5, ?6, 0.0, 0.0, ?7)",
+            "INSERT INTO accounting_entries(id, date_ymd, shift, employee, entry_type, item, amount, bar_pay, finance_pay, created_at) VALUES(?1, ?2, ?3, ?4, 'income', ?5, ?6, 0, 0, ?7)",
             params![id, date_ymd, shift, employee, item, it.amount, now],
         ).map_err(|e| format!("insert income: {e}"))?;
         inserted += 1;
     }
 
+    remember_result(&tx, "accounting_entries_create_from_shift", key.as_deref(), &inserted.to_string(), now)?;
     tx.commit().map_err(|e| format!("commit: {e}"))?;
     Ok(inserted)
 }
@@ -510,20 +557,23 @@ pub fn sales_order_create_from_shift(
     let order_id = Uuid::new_v4().to_string();
     let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
 
-    let mut total_revenue: f64 = 0.0;
-    let mut total_profit: f64 = 0.0;
+    // A product whose 补货 was recorded as stock transfers this shift takes
+    // those figures over the hand-typed restock column; the rest keep what was typed.
+    let recorded_restock = load_restock_summary(&tx, &date_ymd, &shift)?;
+
+    let mut total_revenue = Money::ZERO;
+    let mut total_profit = Money::ZERO;
 
     for it in input.items.iter() {
         let name = it.product_name.trim();
         if name.is_empty() { continue; }
-        let revenue = it.revenue.unwrap_or(0.0);
+        let revenue = it.revenue.unwrap_or_default();
         total_revenue += revenue;
 
         let sales_qty = it.sales.unwrap_or(0.0);
         let spec = it.spec.unwrap_or(1.0);
-        let cost_price = it.cost_price.unwrap_or(0.0);
-        let qty = if spec.abs() < 1e-9 { sales_qty } else { sales_qty / spec };
-        total_profit += revenue - (qty * cost_price);
+        let cost_price = it.cost_price.unwrap_or_default();
+        total_profit += revenue - base_units_cost(cost_price, spec, sales_qty);
     }
 
     tx.execute(
@@ -534,14 +584,26 @@ pub fn sales_order_create_from_shift(
     for it in input.items.into_iter() {
         let product_name = it.product_name.trim().to_string();
         if product_name.is_empty() { continue; }
+        let product_id: Option<String> = match it.product_id.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(id) => Some(id.to_string()),
+            None => tx
+                .query_row("SELECT id FROM products WHERE name = ?1", [&product_name], |r| r.get(0))
+                .optional()
+                .map_err(|e| format!("query product: {e}"))?,
+        };
+        let transferred = recorded_restock.iter().find(|r| Some(&r.product_id) == product_id.as_ref());
+        let restock = match transferred {
+            Some(r) => Some(r.quantity),
+            None => it.restock,
+        };
         let id = Uuid::new_v4().to_string();
         tx.execute(
-            "INSERT INTO sales_items(id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at)
-             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            "INSERT INTO sales_items(id, order_id, product_name, original, restock, remaining, redeem, redeem_mode, loss, purchase, stock_prev, stock, sales, revenue, unit_price, cost_price, spec, created_at, product_id)
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
             params![
-                id, order_id, product_name, it.original, it.restock, it.remaining, 
+                id, order_id, product_name, it.original, restock, it.remaining, 
                 it.redeem, it.redeem_mode, it.loss, it.purchase, it.stock_prev, 
-                it.stock, it.sales, it.revenue, it.unit_price, it.cost_price, it.spec, now
+                it.stock, it.sales, it.revenue, it.unit_price, it.cost_price, it.spec, now, product_id
             ],
         ).map_err(|e| format!("insert item: {e}"))?;
     }
@@ -550,55 +612,315 @@ pub fn sales_order_create_from_shift(
     Ok(order_id)
 }
 
-#[tauri::command]
-pub fn pos_checkout(app: AppHandle, input: PosCheckoutInput) -> Result<String, String> {
-    let mut conn = open_db(&app)?;
-    let now = now_ts()?;
-    let _actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+/// A checkout item resolved to its product and selected options.
+pub type ResolvedItem = (ProductRow, Vec<SelectedModifier>);
+
+/// Resolves `items` to products and prices them the way a checkout would:
+/// today's list price plus modifiers, then the best promotion per line. Lines
+/// come back in input order with their quantity in base units; option deltas
+/// count once per unit rung up.
+pub fn price_items(
+    conn: &Connection,
+    items: &[PosItem],
+    now: i64,
+) -> Result<(Vec<ResolvedItem>, Vec<PriceLine>), String> {
+    // Resolve and price every line first so promotions see the whole order.
+    let mut resolved: Vec<ResolvedItem> = Vec::new();
+    let mut priced: Vec<PriceLine> = Vec::new();
+    for it in items.iter() {
+        let (product, scanned_unit) = match it.barcode.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+            Some(code) if it.product_id.trim().is_empty() => {
+                let hit = lookup_product_by_code(conn, code)?.ok_or_else(|| format!("barcode_not_found: {code}"))?;
+                (hit.product, hit.unit)
+            }
+            _ => {
+                let product = load_sellable_product(conn, &it.product_id)?.ok_or_else(|| format!("product_not_found: {}", it.product_id))?;
+                (product, None)
+            }
+        };
+        let unit = it.unit.as_deref().or(scanned_unit.as_deref());
+
+        let quantity = it.quantity * unit_factor(conn, &product.id, &product.base_unit, unit)?;
+        let modifiers = resolve_modifiers(conn, &product.id, it.modifiers.as_deref().unwrap_or(&[]))?;
+        let list_price = price_at(conn, &product.id, now)?.unwrap_or(product.unit_price);
+        priced.push(PriceLine {
+            product_id: product.id.clone(),
+            category: product.category.clone(),
+            quantity,
+            unit_price: list_price,
+            units: it.quantity,
+            options: modifiers.iter().map(|m| m.price_delta).sum::<Money>(),
+            promotion: None,
+        });
+        resolved.push((product, modifiers));
+    }
+    apply_promotions(&active_promotions(conn, now)?, &mut priced);
+    Ok((resolved, priced))
+}
 
-    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
-    let order_id = Uuid::new_v4().to_string();
-    let mut total_revenue: f64 = 0.0;
-    let mut total_profit: f64 = 0.0;
+/// Takes `quantity` base units of a sold product from `location_id`, booked
+/// against `ref_id`. Made-to-order products consume their recipe's
+/// ingredients instead and are costed from them. Returns the cost price and
+/// spec the sale should record, per base unit.
+pub fn take_sold_stock(
+    conn: &Connection,
+    product: &ProductRow,
+    quantity: f64,
+    location_id: &str,
+    ref_id: &str,
+    actor_id: Option<&str>,
+    now: i64,
+) -> Result<(Money, f64), String> {
+    let recipe = load_recipe(conn, &product.id)?;
+    if recipe.is_empty() {
+        adjust_location_stock(conn, &product.id, location_id, -quantity, "sale", Some(ref_id), actor_id)?;
+        sync_product_totals(conn, &product.id, now)?;
+        return Ok((product.cost_price, product.spec));
+    }
+    for ing in recipe.iter() {
+        adjust_location_stock(conn, &ing.product.id, location_id, -(ing.quantity * quantity), "recipe", Some(ref_id), actor_id)?;
+        sync_product_totals(conn, &ing.product.id, now)?;
+    }
+    Ok((recipe_unit_cost(&recipe), 1.0))
+}
 
-    for it in input.items.iter() {
-        let product: ProductRow = tx.query_row(
-            "SELECT id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active FROM products WHERE id = ?1",
-            params![it.product_id],
-            |r| Ok(ProductRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                category: r.get(2)?,
-                unit_price: r.get(3)?,
-                cost_price: r.get(4)?,
-                spec: r.get(5)?,
-                on_shelf: r.get(6)?,
-                stock: r.get(7)?,
-                is_active: r.get::<_, i64>(8)? != 0,
-            }),
-        ).map_err(|_| format!("product_not_found: {}", it.product_id))?;
-
-        let revenue = product.unit_price * it.quantity;
-        let cost = (it.quantity / product.spec) * product.cost_price;
-        total_revenue += revenue;
-        total_profit += revenue - cost;
+/// What ringing up a set of items booked.
+pub struct RungUp {
+    pub revenue: Money,
+    pub profit: Money,
+    /// Points the redeemed lines cost.
+    pub points: i64,
+    /// Category and net revenue of each paid line, to earn points on.
+    pub earning: Vec<(String, Money)>,
+    /// Id, revenue and quantity of each sales line that brought money in, so
+    /// order-level discounts can be spread over them.
+    pub lines: Vec<(String, Money, f64)>,
+}
 
-        tx.execute("UPDATE products SET stock = stock - ?1, updated_at = ?2 WHERE id = ?3", params![it.quantity, now, it.product_id])
-            .map_err(|e| format!("update stock: {e}"))?;
+/// Prices `items` (list price, modifiers, promotions), takes their stock and
+/// writes them as lines of `order_id`. Returns what the lines add up to; the
+/// caller writes the order row itself.
+pub fn ring_up_items(
+    conn: &Connection,
+    order_id: &str,
+    items: &[PosItem],
+    location_id: &str,
+    actor_id: &str,
+    now: i64,
+) -> Result<RungUp, String> {
+    let mut rung = RungUp { revenue: Money::ZERO, profit: Money::ZERO, points: 0, earning: Vec::new(), lines: Vec::new() };
+
+    let (resolved, priced) = price_items(conn, items, now)?;
+    for ((item, (product, modifiers)), line) in items.iter().zip(resolved).zip(priced.iter()) {
+        let quantity = line.quantity;
+        // A line given for points takes stock and cost like a sale but brings
+        // in nothing; its points are booked on the first line.
+        let (revenue, mut redeem_points) = if item.redeem {
+            (Money::ZERO, Some(redeem_cost(conn, &product, quantity)?))
+        } else {
+            rung.earning.push((product.category.clone(), line.net()));
+            (line.net(), None)
+        };
+        rung.points += redeem_points.unwrap_or(0);
+
+        // A bundle is priced as one unit but sells its components: each
+        // component gets its own line with a share of the bundle revenue.
+        let (lines, bundle_id) = if product.kind == "bundle" {
+            let components = load_bundle_components(conn, &product.id)?;
+            if components.is_empty() {
+                return Err(format!("bundle_empty: {}", product.name));
+            }
+            let shares = split_revenue(revenue, &components);
+            let lines: Vec<(ProductRow, f64, Money)> = components
+                .into_iter()
+                .zip(shares)
+                .map(|(c, share)| (c.product, c.quantity * quantity, share))
+                .collect();
+            (lines, Some(product.id.clone()))
+        } else {
+            (vec![(product, quantity, revenue)], None)
+        };
+
+        let mut item_ids: Vec<String> = Vec::new();
+        for (line_product, line_qty, line_revenue) in lines.iter() {
+            // Stock movements point at the line so a refund can put back exactly
+            // what this line took.
+            let item_id = Uuid::new_v4().to_string();
+            let (cost_price, spec) = take_sold_stock(conn, line_product, *line_qty, location_id, &item_id, Some(actor_id), now)?;
+            let cost = base_units_cost(cost_price, spec, *line_qty);
+            rung.revenue += *line_revenue;
+            rung.profit += *line_revenue - cost;
+
+            // The average price actually charged, rounded to the fen; `revenue`
+            // stays the exact amount of the line.
+            let line_price = if line_qty.abs() > 1e-9 { line_revenue.mul_f64(1.0 / line_qty) } else { line_product.unit_price };
+            let (redeem, redeem_mode) = if item.redeem { (Some(*line_qty), Some(REDEEM_MODE_POINTS)) } else { (None, None) };
+            conn.execute(
+                "INSERT INTO sales_items(id, order_id, product_id, product_name, sales, revenue, unit_price, cost_price, spec, bundle_id, redeem, redeem_mode, redeem_points, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![item_id, order_id, line_product.id, line_product.name, line_qty, line_revenue, line_price, cost_price, spec, bundle_id, redeem, redeem_mode, redeem_points.take(), now],
+            ).map_err(|e| format!("insert_item: {e}"))?;
+            if *line_revenue > Money::ZERO {
+                rung.lines.push((item_id.clone(), *line_revenue, *line_qty));
+            }
+            item_ids.push(item_id);
+        }
 
-        let item_id = Uuid::new_v4().to_string();
-        tx.execute(
-            "INSERT INTO sales_items(id, order_id, product_name, sales, revenue, unit_price, cost_price, spec, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![item_id, order_id, product.name, it.quantity, revenue, product.unit_price, product.cost_price, product.spec, now],
-        ).map_err(|e| format!("insert_item: {e}"))?;
+        // Add-ons like 加蛋 draw their own product from the same location, once
+        // per unit rung up; their cost is booked against the first line of the sale.
+        for m in modifiers.iter() {
+            let used = if m.stock_product_id.is_some() { m.stock_quantity * line.units } else { 0.0 };
+            if let Some(stock_product_id) = m.stock_product_id.as_deref() {
+                let extra = load_product(conn, stock_product_id)?.ok_or_else(|| format!("product_not_found: {stock_product_id}"))?;
+                rung.profit -= base_units_cost(extra.cost_price, extra.spec, used);
+                adjust_location_stock(conn, stock_product_id, location_id, -used, "sale", Some(&item_ids[0]), Some(actor_id))?;
+                sync_product_totals(conn, stock_product_id, now)?;
+            }
+            conn.execute(
+                "INSERT INTO sales_item_modifiers(id, sales_item_id, order_id, option_id, group_name, option_name, price_delta, stock_quantity, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![Uuid::new_v4().to_string(), item_ids[0], order_id, m.option_id, m.group_name, m.option_name, m.price_delta, used, now],
+            ).map_err(|e| format!("insert_modifier: {e}"))?;
+        }
+        if let Some(promo) = line.promotion.as_ref().filter(|_| !item.redeem) {
+            conn.execute(
+                "INSERT INTO sales_item_promotions(id, sales_item_id, order_id, promotion_id, promotion_name, discount, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![Uuid::new_v4().to_string(), item_ids[0], order_id, promo.promotion_id, promo.name, promo.discount, now],
+            ).map_err(|e| format!("insert_promotion: {e}"))?;
+        }
     }
+    Ok(rung)
+}
 
-    tx.execute(
-        "INSERT INTO sales_orders(id, date_ymd, shift, employee, total_revenue, total_profit, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        params![order_id, input.date_ymd, input.shift, input.employee, total_revenue, total_profit, now, now],
+/// Takes an order-level discount (抹零, a coupon) off the lines in proportion
+/// to their revenue, so the lines add up to the order total and a refund of
+/// one line gives back what was actually paid for it. `order_discount` keeps
+/// each line's share for the receipt.
+pub fn spread_order_discount(conn: &Connection, lines: &mut [(String, Money, f64)], discount: Money) -> Result<(), String> {
+    if discount.is_zero() || !lines.iter().any(|(_, revenue, _)| *revenue > Money::ZERO) {
+        return Ok(());
+    }
+    let weights: Vec<f64> = lines.iter().map(|(_, revenue, _)| revenue.fen() as f64).collect();
+    for ((item_id, revenue, quantity), share) in lines.iter_mut().zip(discount.allocate(&weights)) {
+        *revenue -= share;
+        let unit_price = if quantity.abs() > 1e-9 { Some(revenue.mul_f64(1.0 / *quantity)) } else { None };
+        conn.execute(
+            "UPDATE sales_items SET revenue = ?2, unit_price = COALESCE(?3, unit_price), order_discount = order_discount + ?4 WHERE id = ?1",
+            params![item_id.as_str(), *revenue, unit_price, share],
+        )
+        .map_err(|e| format!("update sales_items discount: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Rings up `input` as sales order `order_id`: stock, rounding, tenders and
+/// the order row. Shared by `pos_checkout` and cart checkout; the caller owns
+/// the transaction and any replay handling.
+pub fn checkout(conn: &Connection, order_id: &str, input: &PosCheckoutInput, actor_id: &str, now: i64) -> Result<(), String> {
+    let location_id = sale_location(conn, input.location_id.as_deref())?;
+    let member_id = input.member_id.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if let Some(m) = member_id {
+        require_active_member(conn, m)?;
+    }
+    let mut rung = ring_up_items(conn, order_id, &input.items, &location_id, actor_id, now)?;
+    let mut total_profit = rung.profit;
+
+    // A coupon, like 抹零 below, is a discount on the order as a whole.
+    let coupon_code = input.coupon_code.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let coupon_discount = match coupon_code {
+        Some(code) => redeem_coupon(conn, code, order_id, rung.revenue, &input.date_ymd, actor_id, now)?,
+        None => Money::ZERO,
+    };
+    if !coupon_discount.is_zero() {
+        // Points are earned on what the member actually pays.
+        let share = 1.0 - coupon_discount.fen() as f64 / rung.revenue.fen() as f64;
+        for (_, net) in rung.earning.iter_mut() {
+            *net = net.mul_f64(share);
+        }
+    }
+    total_profit -= coupon_discount;
+    spread_order_discount(conn, &mut rung.lines, coupon_discount)?;
+
+    // 抹零 is a discount on the order as a whole: revenue is what was collected.
+    let (due, rounding) = apply_rounding(rung.revenue - coupon_discount, input.rounding.as_deref())?;
+    let tenders = settle_tenders(due, input.payments.as_deref())?;
+    total_profit -= rounding;
+    spread_order_discount(conn, &mut rung.lines, rounding)?;
+
+    conn.execute(
+        "INSERT INTO sales_orders(id, date_ymd, shift, employee, total_revenue, total_profit, rounding, member_id, coupon_discount, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![order_id, input.date_ymd, input.shift, input.employee, due, total_profit, rounding, member_id, coupon_discount, now, now],
     ).map_err(|e| format!("insert_order: {e}"))?;
+    record_payments(conn, order_id, &tenders, now)?;
+
+    // Paying from a member balance is a payment like any other; the top-up
+    // that funded it was never income.
+    let from_balance = member_paid(&tenders);
+    if !from_balance.is_zero() {
+        let member_id = member_id.ok_or_else(|| String::from("member_required"))?;
+        post_wallet_entry(
+            conn,
+            member_id,
+            &WalletPosting {
+                kind: "spend",
+                amount: -from_balance,
+                method: None,
+                order_id: Some(order_id),
+                date_ymd: &input.date_ymd,
+                shift: Some(&input.shift),
+                note: None,
+                actor_id,
+            },
+            now,
+        )?;
+    }
+
+    if rung.points > 0 {
+        let member_id = member_id.ok_or_else(|| String::from("member_required"))?;
+        redeem_points(conn, member_id, rung.points, order_id, &input.date_ymd, actor_id, now)?;
+    }
+    if let Some(m) = member_id {
+        award_points(conn, m, &rung.earning, order_id, &input.date_ymd, actor_id, now)?;
+    }
+
+    // A receipt that can't be queued must not lose the sale.
+    if let Err(e) = auto_print_receipt(conn, order_id, now) {
+        log_to_system("warn", "printing", &format!("小票未加入打印队列: {order_id}"), Some(&e));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn pos_checkout(app: AppHandle, input: PosCheckoutInput) -> Result<String, String> {
+    let mut conn = open_db(&app)?;
+    let now = now_ts()?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| format!("tx: {e}"))?;
+
+    // A retried checkout returns the order the first attempt created.
+    let client_order_id = input.order_id.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if let Some(id) = client_order_id {
+        // Only a sale can be the earlier attempt; a refund or void id is not
+        // the client's to reuse.
+        let existing: Option<String> = tx
+            .query_row("SELECT kind FROM sales_orders WHERE id = ?1", [id], |r| r.get(0))
+            .optional()
+            .map_err(|e| format!("query order: {e}"))?;
+        match existing.as_deref() {
+            Some("sale") => return Ok(id.to_string()),
+            Some(_) => return Err(String::from("invalid")),
+            None => {}
+        }
+    }
+    if let Some(id) = replayed_result(&tx, "pos_checkout", input.idempotency_key.as_deref(), now)? {
+        return Ok(id);
+    }
+
+    let order_id = client_order_id.map(str::to_string).unwrap_or_else(|| Uuid::new_v4().to_string());
+    checkout(&tx, &order_id, &input, &actor_id, now)?;
+    remember_result(&tx, "pos_checkout", input.idempotency_key.as_deref(), &order_id, now)?;
 
     tx.commit().map_err(|e| format!("commit: {e}"))?;
+    show_paid(&conn, input.terminal.as_deref().map(str::trim).filter(|t| !t.is_empty()), &order_id, now);
     Ok(order_id)
 }
 
@@ -607,25 +929,53 @@ pub fn meituan_orders_import(app: AppHandle, input: MeituanOrdersImportInput) ->
     let mut conn = open_db(&app)?;
     let now = now_ts()?;
     let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
-    let mut inserted: usize = 0;
+    let inserted = insert_meituan_orders(&tx, input.items, now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(inserted)
+}
 
-    for it in input.items.into_iter() {
+/// Saves verified vouchers, skipping incomplete ones and ones already saved
+/// (same coupon number, or same text on the same day). A voucher for a bundle
+/// takes the bundle's components from the front location. Returns how many were new.
+pub fn insert_meituan_orders(conn: &Connection, items: Vec<MeituanOrderImportItem>, now: i64) -> Result<usize, String> {
+    let mut inserted: usize = 0;
+    let mut location_id: Option<String> = None;
+    for it in items.into_iter() {
         let date_ymd = it.date_ymd.trim().to_string();
         let shift = it.shift.trim().to_string();
         let employee = it.employee.trim().to_string();
         let raw_text = it.raw_text.trim().to_string();
-        let coupon_no = it.coupon_no.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        // Stored as bare digits so settlements, which list the number without
+        // spaces or dashes, match it.
+        let coupon_no = it.coupon_no.map(|s| s.chars().filter(|c| c.is_ascii_digit()).collect::<String>()).filter(|s| !s.is_empty());
+        let bundle_id = it.bundle_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
 
         if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() || raw_text.is_empty() { continue; }
+        if let Some(b) = bundle_id.as_deref() {
+            if load_bundle_components(conn, b)?.is_empty() {
+                return Err(format!("bundle_not_found: {b}"));
+            }
+        }
 
         let id = Uuid::new_v4().to_string();
-        let n = tx.execute(
-            "INSERT OR IGNORE INTO meituan_orders(id, date_ymd, shift, employee, coupon_no, raw_text, amount, discount, financial, bar_total, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-            params![id, date_ymd, shift, employee, coupon_no, raw_text, it.amount, it.discount, it.financial, it.bar_total, now],
+        let n = conn.execute(
+            "INSERT OR IGNORE INTO meituan_orders(id, date_ymd, shift, employee, coupon_no, raw_text, amount, discount, financial, bar_total, bundle_id, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![id, date_ymd, shift, employee, coupon_no, raw_text, it.amount, it.discount, it.financial, it.bar_total, bundle_id, now],
         ).map_err(|e| format!("insert: {e}"))?;
-        inserted += n as usize;
+        if n == 0 {
+            continue;
+        }
+        inserted += 1;
+        if let Some(b) = bundle_id.as_deref() {
+            if location_id.is_none() {
+                location_id = Some(default_front_location(conn)?);
+            }
+            let location_id = location_id.as_deref().unwrap_or_default();
+            for c in load_bundle_components(conn, b)?.iter() {
+                take_sold_stock(conn, &c.product, c.quantity, location_id, &id, None, now)?;
+            }
+        }
     }
-    tx.commit().map_err(|e| format!("commit: {e}"))?;
     Ok(inserted)
 }
 
@@ -634,21 +984,36 @@ pub fn wallet_balance(app: AppHandle, token: String) -> Result<WalletBalance, St
     let conn = open_db(&app)?;
     let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
 
-    let finance: f64 = conn.query_row("SELECT COALESCE(SUM(CASE WHEN entry_type = 'income' THEN amount ELSE -amount END), 0) FROM accounting_entries", [], |r| r.get(0)).unwrap_or(0.0);
-    let meituan: f64 = conn.query_row("SELECT COALESCE(SUM(financial), 0) FROM meituan_orders", [], |r| r.get(0)).unwrap_or(0.0);
+    let finance: Money = conn.query_row("SELECT COALESCE(SUM(CASE WHEN entry_type = 'income' THEN amount ELSE -amount END), 0) FROM accounting_entries", [], |r| r.get(0)).unwrap_or_default();
+    // What Meituan has paid out on its settlement statements.
+    let meituan: Money = conn.query_row("SELECT COALESCE(SUM(amount), 0) FROM meituan_settlements", [], |r| r.get(0)).unwrap_or_default();
+    let member_liability: Money = conn.query_row("SELECT COALESCE(SUM(balance), 0) FROM members", [], |r| r.get(0)).unwrap_or_default();
+    let points: i64 = conn.query_row("SELECT COALESCE(SUM(points), 0) FROM members", [], |r| r.get(0)).unwrap_or_default();
 
     Ok(WalletBalance {
         finance,
         meituan,
-        amap: 0.0,
+        amap: Money::ZERO,
+        member_liability,
+        points_liability: points_value(&conn, points)?,
     })
 }
 
+/// Verified vouchers Meituan has not settled yet, at what they should settle for.
 #[tauri::command]
-pub fn meituan_orders_pending(_app: AppHandle, token: String) -> Result<MeituanPending, String> {
+pub fn meituan_orders_pending(app: AppHandle, token: String) -> Result<MeituanPending, String> {
+    let conn = open_db(&app)?;
     let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
-    // Simplified: meituan pending orders can be those that are recently imported but not yet calculated in a shift
-    Ok(MeituanPending { pending: 0.0 })
+    let pending: Money = conn
+        .query_row(
+            "SELECT COALESCE(SUM(financial), 0) FROM meituan_orders o \
+             WHERE coupon_no IS NOT NULL AND coupon_no != '' \
+               AND NOT EXISTS (SELECT 1 FROM meituan_settlements s WHERE s.coupon_no = o.coupon_no)",
+            [],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("query pending: {e}"))?;
+    Ok(MeituanPending { pending })
 }
 
 #[tauri::command]
@@ -661,7 +1026,7 @@ pub fn shift_records_stats(app: AppHandle, token: String) -> Result<ShiftRecords
     let pending_count: i64 = conn.query_row("SELECT COUNT(1) FROM shift_records", [], |r| r.get(0)).unwrap_or(0);
     
     // Calculate total profit from sales_orders
-    let total_profit: f64 = conn.query_row("SELECT COALESCE(SUM(total_profit), 0) FROM sales_orders", [], |r| r.get(0)).unwrap_or(0.0);
+    let total_profit: Money = conn.query_row("SELECT COALESCE(SUM(total_profit), 0) FROM sales_orders", [], |r| r.get(0)).unwrap_or_default();
     
     // Month count
     let month_prefix = &today[0..7]; // YYYY-MM
@@ -677,14 +1042,18 @@ pub fn shift_records_stats(app: AppHandle, token: String) -> Result<ShiftRecords
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct WalletBalance {
-    pub finance: f64,
-    pub meituan: f64,
-    pub amap: f64,
+    pub finance: Money,
+    pub meituan: Money,
+    pub amap: Money,
+    /// Stored value members have yet to spend.
+    pub member_liability: Money,
+    /// What members' unspent loyalty points are worth.
+    pub points_liability: Money,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct MeituanPending {
-    pub pending: f64,
+    pub pending: Money,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -692,5 +1061,36 @@ pub struct ShiftRecordsStats {
     pub today: i64,
     pub pending: i64,
     pub month: i64,
-    pub profit: f64,
+    pub profit: Money,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_discount_is_spread_so_lines_add_up_to_the_total() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE sales_items(id TEXT PRIMARY KEY, revenue INTEGER, unit_price INTEGER, order_discount INTEGER NOT NULL DEFAULT 0);\
+             INSERT INTO sales_items VALUES('a', 1235, 1235, 0), ('b', 500, 250, 0), ('c', 0, 0, 0);",
+        )
+        .unwrap();
+        let mut lines = vec![
+            (String::from("a"), Money::from_fen(1235), 1.0),
+            (String::from("b"), Money::from_fen(500), 2.0),
+            (String::from("c"), Money::ZERO, 1.0),
+        ];
+        spread_order_discount(&conn, &mut lines, Money::from_fen(35)).unwrap();
+
+        let rows: Vec<(i64, i64, i64)> = conn
+            .prepare("SELECT revenue, unit_price, order_discount FROM sales_items ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows, [(1210, 1210, 25), (490, 245, 10), (0, 0, 0)]);
+        assert_eq!(rows.iter().map(|r| r.0).sum::<i64>(), 1700);
+    }
 }
@@ -0,0 +1,332 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::constants::SEAT_ORDER_STATUSES;
+use crate::models::*;
+use crate::commands::inventory::sale_location;
+use crate::commands::modifiers::{load_groups, resolve_modifiers};
+use crate::commands::pricing::price_at;
+use crate::commands::products::load_product;
+use crate::commands::payments::{settle_tenders, record_payments};
+use crate::commands::sales::ring_up_items;
+use crate::money::Money;
+
+// Customers order from the page served at /seat/<code>; the bar works the
+// queue pending → preparing → delivered → paid. Nothing is sold until the order
+// is delivered: that is when it is rung up as a regular sales order, priced
+// and promoted like a checkout, and its stock is taken. Payment is taken when
+// the customer settles up.
+
+pub fn seat_by_code(conn: &Connection, code: &str) -> Result<(String, String), String> {
+    conn.query_row(
+        "SELECT id, code FROM seats WHERE code = ?1 AND is_active = 1",
+        [code.trim()],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("query seat: {e}"))?
+    .ok_or_else(|| String::from("seat_not_found"))
+}
+
+fn can_advance(from: &str, to: &str) -> bool {
+    matches!(
+        (from, to),
+        ("pending", "preparing")
+            | ("preparing", "delivered")
+            | ("delivered", "paid")
+            | ("pending", "cancelled")
+            | ("preparing", "cancelled")
+    )
+}
+
+/// What a seat can order: active products at today's price with their options.
+pub fn load_seat_menu(conn: &Connection, now: i64) -> Result<Vec<SeatMenuItem>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, category, unit_price FROM products \
+             WHERE is_active = 1 AND deleted_at IS NULL ORDER BY category ASC, name ASC",
+        )
+        .map_err(|e| format!("prepare menu: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?, r.get::<_, Money>(3)?)))
+        .map_err(|e| format!("query menu: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (id, name, category, unit_price) = r.map_err(|e| format!("row menu: {e}"))?;
+        out.push(SeatMenuItem {
+            unit_price: price_at(conn, &id, now)?.unwrap_or(unit_price),
+            modifier_groups: load_groups(conn, Some(&id))?,
+            id,
+            name,
+            category,
+        });
+    }
+    Ok(out)
+}
+
+fn load_seat_orders(conn: &Connection, filter: &str, args: &[&dyn rusqlite::ToSql]) -> Result<Vec<SeatOrderRow>, String> {
+    let sql = format!(
+        "SELECT o.id, o.seat_code, o.status, o.note, o.sales_order_id, \
+                COALESCE((SELECT total_revenue FROM sales_orders WHERE id = o.sales_order_id), \
+                         (SELECT SUM(ROUND(quantity * unit_price)) FROM seat_order_items WHERE seat_order_id = o.id), 0), \
+                o.created_at, o.updated_at \
+         FROM seat_orders o WHERE {filter} ORDER BY o.created_at ASC"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("prepare seat_orders: {e}"))?;
+    let rows = stmt
+        .query_map(args, |r| {
+            Ok(SeatOrderRow {
+                id: r.get(0)?,
+                seat_code: r.get(1)?,
+                status: r.get(2)?,
+                note: r.get(3)?,
+                sales_order_id: r.get(4)?,
+                total: r.get(5)?,
+                items: Vec::new(),
+                created_at: r.get(6)?,
+                updated_at: r.get(7)?,
+            })
+        })
+        .map_err(|e| format!("query seat_orders: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row seat_orders: {e}"))?);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT product_id, product_name, quantity, unit_price, modifiers, modifier_names \
+             FROM seat_order_items WHERE seat_order_id = ?1 ORDER BY sort_order ASC",
+        )
+        .map_err(|e| format!("prepare seat_order_items: {e}"))?;
+    for o in out.iter_mut() {
+        let rows = stmt
+            .query_map([&o.id], |r| {
+                let modifiers: String = r.get(4)?;
+                Ok(SeatOrderItemRow {
+                    product_id: r.get(0)?,
+                    product_name: r.get(1)?,
+                    quantity: r.get(2)?,
+                    unit_price: r.get(3)?,
+                    modifiers: serde_json::from_str(&modifiers).unwrap_or_default(),
+                    modifier_names: r.get(5)?,
+                })
+            })
+            .map_err(|e| format!("query seat_order_items: {e}"))?;
+        for r in rows {
+            o.items.push(r.map_err(|e| format!("row seat_order_items: {e}"))?);
+        }
+    }
+    Ok(out)
+}
+
+fn load_seat_order(conn: &Connection, id: &str) -> Result<SeatOrderRow, String> {
+    load_seat_orders(conn, "o.id = ?1", &[&id])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| String::from("not_found"))
+}
+
+/// A seat's orders from the last day, for the customer page.
+pub fn seat_orders_for_seat(conn: &Connection, code: &str, now: i64) -> Result<Vec<SeatOrderRow>, String> {
+    let (seat_id, _) = seat_by_code(conn, code)?;
+    load_seat_orders(conn, "o.seat_id = ?1 AND o.created_at > ?2", &[&seat_id, &(now - 24 * 60 * 60)])
+}
+
+/// Places an order from a seat. Prices shown here are indicative; the order is
+/// priced for real when it is delivered.
+pub fn submit_seat_order(conn: &mut Connection, code: &str, input: SeatOrderSubmitInput, now: i64) -> Result<String, String> {
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let (seat_id, seat_code) = seat_by_code(&tx, code)?;
+    if input.items.is_empty() {
+        return Err(String::from("empty_order"));
+    }
+    let note = input.note.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO seat_orders(id, seat_id, seat_code, status, note, created_at, updated_at) VALUES(?1, ?2, ?3, 'pending', ?4, ?5, ?6)",
+        params![id, seat_id, seat_code, note, now, now],
+    )
+    .map_err(|e| format!("insert seat_orders: {e}"))?;
+
+    for (i, it) in input.items.iter().enumerate() {
+        if !it.quantity.is_finite() || it.quantity <= 0.0 || it.quantity > 99.0 {
+            return Err(String::from("invalid"));
+        }
+        let product = load_product(&tx, it.product_id.trim())?
+            .filter(|p| p.is_active)
+            .ok_or_else(|| format!("product_not_found: {}", it.product_id.trim()))?;
+        let option_ids = it.modifiers.clone().unwrap_or_default();
+        let modifiers = resolve_modifiers(&tx, &product.id, &option_ids)?;
+        let unit_price = price_at(&tx, &product.id, now)?.unwrap_or(product.unit_price)
+            + modifiers.iter().map(|m| m.price_delta).sum::<Money>();
+        let modifier_ids: Vec<&str> = modifiers.iter().map(|m| m.option_id.as_str()).collect();
+        let modifier_names: Vec<&str> = modifiers.iter().map(|m| m.option_name.as_str()).collect();
+        tx.execute(
+            "INSERT INTO seat_order_items(id, seat_order_id, product_id, product_name, quantity, unit_price, modifiers, modifier_names, sort_order) \
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                Uuid::new_v4().to_string(), id, product.id, product.name, it.quantity, unit_price,
+                serde_json::to_string(&modifier_ids).map_err(|e| format!("modifiers: {e}"))?,
+                modifier_names.join("、"), i as i64
+            ],
+        )
+        .map_err(|e| format!("insert seat_order_items: {e}"))?;
+    }
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn seats_list(app: AppHandle, token: String) -> Result<Vec<SeatRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.code, s.area, s.is_active, \
+                    (SELECT COUNT(1) FROM seat_orders o WHERE o.seat_id = s.id AND o.status IN ('pending', 'preparing', 'delivered')) \
+             FROM seats s ORDER BY s.area ASC, s.code ASC",
+        )
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(SeatRow {
+                id: r.get(0)?,
+                code: r.get(1)?,
+                area: r.get(2)?,
+                is_active: r.get::<_, i64>(3)? != 0,
+                open_orders: r.get(4)?,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn seat_upsert(app: AppHandle, input: SeatUpsertInput) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+
+    let code = input.code.trim().to_string();
+    if code.is_empty() {
+        return Err(String::from("invalid"));
+    }
+    let area = input.area.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let is_active = if input.is_active.unwrap_or(true) { 1i64 } else { 0i64 };
+    let now = now_ts()?;
+    let map_unique = |e: rusqlite::Error| {
+        if e.to_string().contains("seats.code") { String::from("duplicate_seat") } else { format!("save seat: {e}") }
+    };
+
+    let id = input.id.unwrap_or_default().trim().to_string();
+    if id.is_empty() {
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO seats(id, code, area, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, code, area, is_active, now, now],
+        )
+        .map_err(map_unique)?;
+        return Ok(id);
+    }
+
+    let n = conn
+        .execute(
+            "UPDATE seats SET code = ?2, area = ?3, is_active = ?4, updated_at = ?5 WHERE id = ?1",
+            params![id, code, area, is_active, now],
+        )
+        .map_err(map_unique)?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    Ok(id)
+}
+
+/// The bar's queue: open orders oldest first, or every order in `status`.
+#[tauri::command]
+pub fn seat_orders_queue(app: AppHandle, token: String, status: Option<String>) -> Result<Vec<SeatOrderRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    match status.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(s) => {
+            if !SEAT_ORDER_STATUSES.contains(&s) {
+                return Err(String::from("invalid"));
+            }
+            load_seat_orders(&conn, "o.status = ?1", &[&s])
+        }
+        None => load_seat_orders(&conn, "o.status IN ('pending', 'preparing', 'delivered')", &[]),
+    }
+}
+
+#[tauri::command]
+pub fn seat_order_advance(app: AppHandle, input: SeatOrderAdvanceInput) -> Result<SeatOrderRow, String> {
+    let mut conn = open_db(&app)?;
+    let actor_id = auth_resolve_account_id(&input.token).ok_or_else(|| String::from("unauthorized"))?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let order = load_seat_order(&tx, input.id.trim())?;
+    let to = input.status.trim();
+    if !can_advance(&order.status, to) {
+        return Err(format!("invalid_transition: {} -> {to}", order.status));
+    }
+
+    let mut sales_order_id = order.sales_order_id.clone();
+    match to {
+        "delivered" => {
+            let date_ymd = input.date_ymd.as_deref().map(str::trim).unwrap_or("");
+            let shift = input.shift.as_deref().map(str::trim).unwrap_or("");
+            let employee = input.employee.as_deref().map(str::trim).unwrap_or("");
+            if date_ymd.is_empty() || shift.is_empty() || employee.is_empty() {
+                return Err(String::from("missing fields"));
+            }
+            let location_id = sale_location(&tx, input.location_id.as_deref())?;
+            let items: Vec<PosItem> = order
+                .items
+                .iter()
+                .map(|it| PosItem {
+                    product_id: it.product_id.clone(),
+                    barcode: None,
+                    quantity: it.quantity,
+                    unit: None,
+                    modifiers: Some(it.modifiers.clone()),
+                    redeem: false,
+                })
+                .collect();
+            let id = Uuid::new_v4().to_string();
+            let rung = ring_up_items(&tx, &id, &items, &location_id, &actor_id, now)?;
+            tx.execute(
+                "INSERT INTO sales_orders(id, date_ymd, shift, employee, total_revenue, total_profit, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![id, date_ymd, shift, employee, rung.revenue, rung.profit, now, now],
+            )
+            .map_err(|e| format!("insert_order: {e}"))?;
+            sales_order_id = Some(id);
+        }
+        "paid" => {
+            let id = sales_order_id.as_deref().ok_or_else(|| String::from("not_delivered"))?;
+            let due: Money = tx
+                .query_row("SELECT total_revenue FROM sales_orders WHERE id = ?1", [id], |r| r.get(0))
+                .map_err(|e| format!("query order: {e}"))?;
+            let tenders = settle_tenders(due, input.payments.as_deref())?;
+            record_payments(&tx, id, &tenders, now)?;
+        }
+        _ => {}
+    }
+
+    tx.execute(
+        "UPDATE seat_orders SET status = ?2, sales_order_id = ?3, updated_at = ?4 WHERE id = ?1",
+        params![order.id, to, sales_order_id, now],
+    )
+    .map_err(|e| format!("update seat_orders: {e}"))?;
+    let updated = load_seat_order(&tx, &order.id)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(updated)
+}
@@ -0,0 +1,217 @@
+use tauri::AppHandle;
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::db::{open_db, now_ts};
+use crate::state::{auth_resolve_account_id, require_admin};
+use crate::models::{ProductUnitRow, ProductUnitUpsertInput};
+use crate::money::Money;
+
+// Stock, sales quantities and `unit_price` are always in the product's base
+// unit (瓶/包/个). `cost_price` is quoted per purchase unit, whose size in
+// base units is mirrored into the legacy `spec` column.
+
+/// Cost of one base unit in yuan, for display only since it is usually a
+/// fraction of a fen. A `spec` of 0 means the product is bought one by one.
+pub fn base_unit_cost(cost_price: Money, spec: f64) -> f64 {
+    if spec > 0.0 { cost_price.to_yuan() / spec } else { cost_price.to_yuan() }
+}
+
+/// Cost of `quantity` base units, rounded to the fen.
+pub fn base_units_cost(cost_price: Money, spec: f64, quantity: f64) -> Money {
+    if spec > 0.0 { cost_price.mul_f64(quantity / spec) } else { cost_price.mul_f64(quantity) }
+}
+
+/// Number of base units in `unit`; no unit or the base unit itself is 1.
+pub fn unit_factor(conn: &Connection, product_id: &str, base_unit: &str, unit: Option<&str>) -> Result<f64, String> {
+    let unit = match unit.map(str::trim).filter(|u| !u.is_empty()) {
+        Some(u) if u != base_unit => u,
+        _ => return Ok(1.0),
+    };
+    conn.query_row(
+        "SELECT factor FROM product_units WHERE product_id = ?1 AND unit = ?2",
+        params![product_id, unit],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("query unit: {e}"))?
+    .ok_or_else(|| format!("unit_not_found: {unit}"))
+}
+
+/// The unit purchases are recorded in by default, with its factor.
+pub fn purchase_unit(conn: &Connection, product_id: &str) -> Result<Option<(String, f64)>, String> {
+    conn.query_row(
+        "SELECT unit, factor FROM product_units WHERE product_id = ?1 AND is_purchase_default = 1",
+        [product_id],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("query purchase unit: {e}"))
+}
+
+/// `cost_price` re-quoted for a purchase unit of `new_spec` base units, so
+/// the cost of one base unit stays the same.
+pub fn rescale_cost(cost_price: Money, old_spec: f64, new_spec: f64) -> Money {
+    let per = |spec: f64| if spec > 0.0 { spec } else { 1.0 };
+    cost_price.mul_f64(per(new_spec) / per(old_spec))
+}
+
+/// `spec` must agree with the purchase unit once one is set; it is changed
+/// through the units instead. Returns the error for a conflicting `spec`.
+pub fn spec_conflict(conn: &Connection, product_id: &str, spec: f64) -> Result<Option<String>, String> {
+    Ok(purchase_unit(conn, product_id)?
+        .filter(|(_, factor)| (factor - spec).abs() > 1e-9)
+        .map(|(unit, factor)| format!("spec_conflict: {unit} = {factor}")))
+}
+
+/// Mirrors the purchase unit into `spec` and re-quotes `cost_price` for it.
+fn sync_purchase_spec(conn: &Connection, product_id: &str, now: i64) -> Result<(), String> {
+    let factor = purchase_unit(conn, product_id)?.map(|(_, f)| f).unwrap_or(1.0);
+    let (cost_price, spec): (Money, f64) = conn
+        .query_row("SELECT cost_price, spec FROM products WHERE id = ?1", [product_id], |r| Ok((r.get(0)?, r.get(1)?)))
+        .map_err(|e| format!("query product: {e}"))?;
+    conn.execute(
+        "UPDATE products SET spec = ?2, cost_price = ?3, updated_at = ?4 WHERE id = ?1",
+        params![product_id, factor, rescale_cost(cost_price, spec, factor), now],
+    )
+    .map_err(|e| format!("update spec: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn product_units_list(app: AppHandle, token: String, product_id: String) -> Result<Vec<ProductUnitRow>, String> {
+    let conn = open_db(&app)?;
+    let _ = auth_resolve_account_id(&token).ok_or_else(|| String::from("unauthorized"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT product_id, unit, factor, is_purchase_default FROM product_units WHERE product_id = ?1 ORDER BY factor ASC")
+        .map_err(|e| format!("prepare: {e}"))?;
+    let rows = stmt
+        .query_map([product_id.trim()], |r| {
+            Ok(ProductUnitRow {
+                product_id: r.get(0)?,
+                unit: r.get(1)?,
+                factor: r.get(2)?,
+                is_purchase_default: r.get::<_, i64>(3)? != 0,
+            })
+        })
+        .map_err(|e| format!("query_map: {e}"))?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r.map_err(|e| format!("row: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn product_unit_upsert(app: AppHandle, input: ProductUnitUpsertInput) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, input.token.trim())?;
+    let now = now_ts()?;
+
+    let product_id = input.product_id.trim().to_string();
+    let unit = input.unit.trim().to_string();
+    if product_id.is_empty() || unit.is_empty() || !input.factor.is_finite() || input.factor <= 0.0 {
+        return Err(String::from("invalid"));
+    }
+    let is_purchase_default = input.is_purchase_default.unwrap_or(false);
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let base_unit: String = tx
+        .query_row("SELECT base_unit FROM products WHERE id = ?1", [&product_id], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("query product: {e}"))?
+        .ok_or_else(|| format!("product_not_found: {product_id}"))?;
+    if unit == base_unit {
+        return Err(String::from("unit is the base unit"));
+    }
+
+    if is_purchase_default {
+        tx.execute("UPDATE product_units SET is_purchase_default = 0 WHERE product_id = ?1", [&product_id])
+            .map_err(|e| format!("clear purchase default: {e}"))?;
+    }
+    tx.execute(
+        "INSERT INTO product_units(product_id, unit, factor, is_purchase_default, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6) \
+         ON CONFLICT(product_id, unit) DO UPDATE SET factor = excluded.factor, is_purchase_default = excluded.is_purchase_default, updated_at = excluded.updated_at",
+        params![product_id, unit, input.factor, if is_purchase_default { 1i64 } else { 0i64 }, now, now],
+    )
+    .map_err(|e| format!("upsert product_units: {e}"))?;
+    sync_purchase_spec(&tx, &product_id, now)?;
+
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn product_unit_delete(app: AppHandle, token: String, product_id: String, unit: String) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    let _ = require_admin(&conn, token.trim())?;
+    let now = now_ts()?;
+
+    let tx = conn.transaction().map_err(|e| format!("tx: {e}"))?;
+    let n = tx
+        .execute(
+            "DELETE FROM product_units WHERE product_id = ?1 AND unit = ?2",
+            params![product_id.trim(), unit.trim()],
+        )
+        .map_err(|e| format!("delete product_units: {e}"))?;
+    if n == 0 {
+        return Err(String::from("not_found"));
+    }
+    sync_purchase_spec(&tx, product_id.trim(), now)?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+
+    #[test]
+    fn base_unit_costs() {
+        let case = Money::from_fen(4800);
+        assert_eq!(base_unit_cost(case, 24.0), 2.0);
+        assert_eq!(base_unit_cost(case, 0.0), 48.0);
+        assert_eq!(base_units_cost(case, 24.0, 3.0), Money::from_fen(600));
+        assert_eq!(base_units_cost(case, 24.0, 1.0 / 3.0), Money::from_fen(67));
+        assert_eq!(base_units_cost(Money::from_fen(250), 0.0, 2.0), Money::from_fen(500));
+    }
+
+    #[test]
+    fn rescaled_cost_keeps_the_base_unit_cost() {
+        let case = Money::from_fen(4800);
+        assert_eq!(rescale_cost(case, 24.0, 6.0), Money::from_fen(1200));
+        assert_eq!(rescale_cost(case, 24.0, 1.0), Money::from_fen(200));
+        assert_eq!(rescale_cost(Money::from_fen(200), 0.0, 24.0), Money::from_fen(4800));
+        assert_eq!(rescale_cost(Money::from_fen(1000), 3.0, 1.0), Money::from_fen(333));
+    }
+
+    #[test]
+    fn changing_the_purchase_unit_rescales_cost() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at) \
+             VALUES('cola', '可乐', '饮料', 300, 4800, 24, 0, 0, 1, 0, 0)",
+            [],
+        )
+        .unwrap();
+        let cost = || -> (Money, f64) {
+            conn.query_row("SELECT cost_price, spec FROM products WHERE id = 'cola'", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap()
+        };
+        conn.execute("INSERT INTO product_units(product_id, unit, factor, is_purchase_default, created_at, updated_at) VALUES('cola', '箱', 24, 1, 0, 0)", [])
+            .unwrap();
+        sync_purchase_spec(&conn, "cola", 0).unwrap();
+        assert_eq!(cost(), (Money::from_fen(4800), 24.0));
+        assert_eq!(spec_conflict(&conn, "cola", 24.0).unwrap(), None);
+        assert_eq!(spec_conflict(&conn, "cola", 6.0).unwrap().as_deref(), Some("spec_conflict: 箱 = 24"));
+
+        conn.execute("UPDATE product_units SET factor = 6 WHERE product_id = 'cola'", []).unwrap();
+        sync_purchase_spec(&conn, "cola", 0).unwrap();
+        assert_eq!(cost(), (Money::from_fen(1200), 6.0));
+        assert_eq!(base_units_cost(cost().0, cost().1, 1.0), Money::from_fen(200));
+
+        conn.execute("DELETE FROM product_units WHERE product_id = 'cola'", []).unwrap();
+        sync_purchase_spec(&conn, "cola", 0).unwrap();
+        assert_eq!(cost(), (Money::from_fen(200), 1.0));
+    }
+}
@@ -9,3 +9,79 @@ pub const DEFAULT_CATEGORY: &str = "饮品";
 /// Default shift names
 pub const SHIFT_DAY: &str = "白班";
 pub const SHIFT_NIGHT: &str = "晚班";
+
+/// Built-in stock locations
+pub const LOCATION_SHELF: &str = "shelf";
+pub const LOCATION_FRIDGE: &str = "fridge";
+pub const LOCATION_STOREROOM: &str = "storeroom";
+
+/// Default units of measure
+pub const DEFAULT_BASE_UNIT: &str = "个";
+pub const DEFAULT_CASE_UNIT: &str = "箱";
+
+/// Payment methods accepted at checkout; everything but cash is electronic
+pub const PAYMENT_CASH: &str = "cash";
+pub const PAYMENT_MEMBER: &str = "member";
+pub const PAYMENT_METHODS: [&str; 5] = ["cash", "wechat", "alipay", "member", "meituan"];
+
+/// Kinds of `sales_orders` rows; refunds and voids reverse an earlier sale
+pub const ORDER_KIND_SALE: &str = "sale";
+pub const ORDER_KIND_REFUND: &str = "refund";
+pub const ORDER_KIND_VOID: &str = "void";
+
+/// How long a retried request with the same Idempotency-Key gets the first result back
+pub const IDEMPOTENCY_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Seat-side order states, in the order the bar moves them through
+pub const SEAT_ORDER_STATUSES: [&str; 5] = ["pending", "preparing", "delivered", "paid", "cancelled"];
+
+/// Server-side cart states; only open carts take changes and only parked ones can be resumed
+pub const CART_STATUSES: [&str; 5] = ["open", "parked", "checked_out", "discarded", "expired"];
+
+/// An untouched open or parked cart expires this long after its last change
+pub const CART_EXPIRY_SECS: i64 = 8 * 60 * 60;
+
+/// Raw ESC/POS printers listen on the JetDirect port
+pub const PRINTER_PORT_DEFAULT: u16 = 9100;
+
+/// A print job is retried with a doubling delay and given up after this many tries
+pub const PRINT_MAX_ATTEMPTS: i64 = 5;
+pub const PRINT_RETRY_BASE_SECS: i64 = 5;
+
+/// Receipt links shown as a QR code stop working after this many hours unless configured otherwise
+pub const RECEIPT_LINK_TTL_HOURS_DEFAULT: i64 = 72;
+pub const RECEIPT_LINK_TTL_HOURS_MAX: i64 = 24 * 365;
+
+/// A customer display has this long to enter the pairing code shown at the till
+pub const DISPLAY_PAIR_CODE_TTL_SECS: i64 = 5 * 60;
+
+/// Wrong pairing codes allowed per window before pairing is refused for a while
+pub const DISPLAY_PAIR_MAX_FAILURES: usize = 10;
+pub const DISPLAY_PAIR_FAILURE_WINDOW_SECS: i64 = 60;
+
+/// Member tiers, lowest first
+pub const MEMBER_TIERS: [&str; 3] = ["standard", "silver", "gold"];
+
+/// Stored-value ledger entry kinds; spends and refunds follow sales, top-ups and adjustments are entered at the till
+pub const WALLET_ENTRY_KINDS: [&str; 4] = ["topup", "spend", "refund", "adjust"];
+
+/// Loyalty points ledger entry kinds; earn, return and positive adjustments open lots that later entries draw down
+pub const POINTS_ENTRY_KINDS: [&str; 6] = ["earn", "redeem", "return", "clawback", "expire", "adjust"];
+
+/// Longest loyalty points lifetime the settings accept
+pub const POINTS_EXPIRY_DAYS_MAX: i64 = 365 * 10;
+
+/// `sales_items.redeem_mode` of a line given away for loyalty points
+pub const REDEEM_MODE_POINTS: i64 = 1;
+
+/// Coupon statuses; a coupon is `used` once it reaches its `max_uses`
+pub const COUPON_STATUSES: [&str; 3] = ["active", "used", "void"];
+
+/// Characters of generated coupon codes, without look-alikes (0/O, 1/I/L) so printed codes read back cleanly
+pub const COUPON_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// Length of a generated coupon code, not counting the batch prefix
+pub const COUPON_CODE_LEN: usize = 10;
+
+/// Most coupons generated in one batch
+pub const COUPON_BATCH_MAX: i64 = 1000;
@@ -2,7 +2,8 @@ use rusqlite::{params, Connection};
 use tauri::{AppHandle, Manager};
 use std::path::PathBuf;
 use uuid::Uuid;
-use crate::constants::DEFAULT_CATEGORY;
+use crate::money::Money;
+use crate::constants::{DEFAULT_CATEGORY, DEFAULT_BASE_UNIT, DEFAULT_CASE_UNIT, LOCATION_SHELF, LOCATION_FRIDGE, LOCATION_STOREROOM};
 
 pub fn db_path(app: &AppHandle) -> Result<PathBuf, String> {
     let base = app
@@ -16,6 +17,11 @@ pub fn db_path(app: &AppHandle) -> Result<PathBuf, String> {
 pub fn open_db(app: &AppHandle) -> Result<Connection, String> {
     let path = db_path(app)?;
     let conn = Connection::open(path).map_err(|e| format!("sqlite open: {e}"))?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")
+        .map_err(|e| format!("sqlite pragma: {e}"))?;
+    // Tablets retrying over HTTP can race the desktop for the write lock.
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(|e| format!("sqlite busy_timeout: {e}"))?;
     
     init_db(&conn)?;
     
@@ -29,7 +35,7 @@ pub fn open_db(app: &AppHandle) -> Result<Connection, String> {
     Ok(conn)
 }
 
-fn init_db(conn: &Connection) -> Result<(), String> {
+pub fn init_db(conn: &Connection) -> Result<(), String> {
     conn.execute_batch(
         "PRAGMA journal_mode=WAL;\
          PRAGMA synchronous=NORMAL;\
@@ -44,7 +50,7 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            equity REAL NOT NULL DEFAULT 0,\
            proxy_host TEXT,\
            is_hidden INTEGER DEFAULT 0,\
-           salary_base REAL DEFAULT 0,\
+           salary_base INTEGER DEFAULT 0,\
            profile TEXT,\
            is_active INTEGER NOT NULL DEFAULT 1,\
            created_at INTEGER NOT NULL,\
@@ -69,30 +75,109 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            id TEXT PRIMARY KEY NOT NULL,\
            name TEXT NOT NULL UNIQUE,\
            category TEXT NOT NULL DEFAULT '饮品',\
-           unit_price REAL NOT NULL DEFAULT 0,\
-           cost_price REAL NOT NULL DEFAULT 0,\
+           unit_price INTEGER NOT NULL DEFAULT 0,\
+           cost_price INTEGER NOT NULL DEFAULT 0,\
            spec REAL NOT NULL DEFAULT 0,\
            on_shelf REAL NOT NULL DEFAULT 0,\
            stock REAL NOT NULL DEFAULT 0,\
+           base_unit TEXT NOT NULL DEFAULT '个',\
+           sku TEXT,\
+           kind TEXT NOT NULL DEFAULT 'item',\
            is_active INTEGER NOT NULL DEFAULT 1,\
+           deleted_at INTEGER,\
+           redeem_points INTEGER,\
            created_at INTEGER NOT NULL,\
            updated_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_products_name ON products(name);\
+         CREATE TABLE IF NOT EXISTS product_categories (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           name TEXT NOT NULL UNIQUE,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           color TEXT,\
+           icon TEXT,\
+           account_item TEXT,\
+           tax_rate REAL NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_products_category ON products(category);\
+         CREATE TABLE IF NOT EXISTS product_prices (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           product_id TEXT NOT NULL,\
+           unit_price INTEGER NOT NULL,\
+           effective_from INTEGER NOT NULL,\
+           note TEXT,\
+           actor_id TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_product_prices_product ON product_prices(product_id, effective_from);\
+         CREATE TABLE IF NOT EXISTS product_barcodes (\
+           code TEXT PRIMARY KEY NOT NULL,\
+           product_id TEXT NOT NULL,\
+           unit TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_product_barcodes_product ON product_barcodes(product_id);\
+         CREATE TABLE IF NOT EXISTS product_bundle_items (\
+           bundle_id TEXT NOT NULL,\
+           component_id TEXT NOT NULL,\
+           quantity REAL NOT NULL DEFAULT 1,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           PRIMARY KEY(bundle_id, component_id)\
+         );\
+         CREATE TABLE IF NOT EXISTS product_recipe_items (\
+           product_id TEXT NOT NULL,\
+           ingredient_id TEXT NOT NULL,\
+           quantity REAL NOT NULL DEFAULT 0,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           PRIMARY KEY(product_id, ingredient_id)\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_product_recipe_items_ingredient ON product_recipe_items(ingredient_id);\
+         CREATE TABLE IF NOT EXISTS product_units (\
+           product_id TEXT NOT NULL,\
+           unit TEXT NOT NULL,\
+           factor REAL NOT NULL,\
+           is_purchase_default INTEGER NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL,\
+           PRIMARY KEY(product_id, unit)\
+         );\
          CREATE TABLE IF NOT EXISTS sales_orders (\
            id TEXT PRIMARY KEY NOT NULL,\
            date_ymd TEXT NOT NULL,\
            shift TEXT NOT NULL,\
            employee TEXT NOT NULL,\
-           total_revenue REAL NOT NULL DEFAULT 0,\
-           total_profit REAL NOT NULL DEFAULT 0,\
+           total_revenue INTEGER NOT NULL DEFAULT 0,\
+           total_profit INTEGER NOT NULL DEFAULT 0,\
+           rounding INTEGER NOT NULL DEFAULT 0,\
+           kind TEXT NOT NULL DEFAULT 'sale',\
+           status TEXT NOT NULL DEFAULT 'completed',\
+           ref_order_id TEXT,\
+           reason TEXT,\
+           actor_id TEXT,\
+           member_id TEXT,\
+           coupon_discount INTEGER NOT NULL DEFAULT 0,\
            created_at INTEGER NOT NULL,\
            updated_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_sales_orders_date ON sales_orders(date_ymd);\
+         CREATE TABLE IF NOT EXISTS payments (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           order_id TEXT NOT NULL,\
+           method TEXT NOT NULL,\
+           amount INTEGER NOT NULL DEFAULT 0,\
+           tendered INTEGER NOT NULL DEFAULT 0,\
+           change_amount INTEGER NOT NULL DEFAULT 0,\
+           reference TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_payments_order ON payments(order_id);\
          CREATE TABLE IF NOT EXISTS sales_items (\
            id TEXT PRIMARY KEY NOT NULL,\
            order_id TEXT NOT NULL,\
+           product_id TEXT REFERENCES products(id),\
            product_name TEXT NOT NULL,\
            original REAL,\
            restock REAL,\
@@ -104,13 +189,89 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            stock_prev REAL,\
            stock REAL,\
            sales REAL,\
-           revenue REAL,\
-           unit_price REAL,\
-           cost_price REAL,\
+           revenue INTEGER,\
+           unit_price INTEGER,\
+           cost_price INTEGER,\
            spec REAL,\
+           bundle_id TEXT,\
+           ref_item_id TEXT,\
+           redeem_points INTEGER,\
+           order_discount INTEGER NOT NULL DEFAULT 0,\
            created_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_sales_items_order ON sales_items(order_id);\
+         CREATE TABLE IF NOT EXISTS modifier_groups (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           name TEXT NOT NULL UNIQUE,\
+           selection TEXT NOT NULL DEFAULT 'single',\
+           is_required INTEGER NOT NULL DEFAULT 0,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS modifier_options (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           group_id TEXT NOT NULL,\
+           name TEXT NOT NULL,\
+           price_delta INTEGER NOT NULL DEFAULT 0,\
+           stock_product_id TEXT,\
+           stock_quantity REAL NOT NULL DEFAULT 0,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL,\
+           UNIQUE(group_id, name)\
+         );\
+         CREATE TABLE IF NOT EXISTS product_modifier_groups (\
+           product_id TEXT NOT NULL,\
+           group_id TEXT NOT NULL,\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           PRIMARY KEY(product_id, group_id)\
+         );\
+         CREATE TABLE IF NOT EXISTS sales_item_modifiers (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           sales_item_id TEXT NOT NULL,\
+           order_id TEXT NOT NULL,\
+           option_id TEXT NOT NULL,\
+           group_name TEXT NOT NULL,\
+           option_name TEXT NOT NULL,\
+           price_delta INTEGER NOT NULL DEFAULT 0,\
+           stock_quantity REAL NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_sales_item_modifiers_order ON sales_item_modifiers(order_id);\
+         CREATE TABLE IF NOT EXISTS promotions (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           name TEXT NOT NULL,\
+           rule_type TEXT NOT NULL,\
+           scope TEXT NOT NULL DEFAULT 'all',\
+           target TEXT,\
+           value REAL NOT NULL DEFAULT 0,\
+           buy_qty REAL NOT NULL DEFAULT 0,\
+           get_qty REAL NOT NULL DEFAULT 0,\
+           weekdays TEXT,\
+           start_time TEXT,\
+           end_time TEXT,\
+           starts_at INTEGER,\
+           ends_at INTEGER,\
+           priority INTEGER NOT NULL DEFAULT 0,\
+           amount INTEGER NOT NULL DEFAULT 0,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS sales_item_promotions (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           sales_item_id TEXT NOT NULL,\
+           order_id TEXT NOT NULL,\
+           promotion_id TEXT NOT NULL,\
+           promotion_name TEXT NOT NULL,\
+           discount INTEGER NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_sales_item_promotions_order ON sales_item_promotions(order_id);\
+         CREATE INDEX IF NOT EXISTS idx_sales_item_promotions_promotion ON sales_item_promotions(promotion_id);\
          CREATE TABLE IF NOT EXISTS accounting_entries (\
            id TEXT PRIMARY KEY NOT NULL,\
            date_ymd TEXT NOT NULL,\
@@ -118,9 +279,9 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            employee TEXT NOT NULL,\
            entry_type TEXT NOT NULL,\
            item TEXT NOT NULL,\
-           amount REAL NOT NULL DEFAULT 0,\
-           bar_pay REAL NOT NULL DEFAULT 0,\
-           finance_pay REAL NOT NULL DEFAULT 0,\
+           amount INTEGER NOT NULL DEFAULT 0,\
+           bar_pay INTEGER NOT NULL DEFAULT 0,\
+           finance_pay INTEGER NOT NULL DEFAULT 0,\
            created_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_accounting_date ON accounting_entries(date_ymd);\
@@ -131,10 +292,11 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            employee TEXT NOT NULL,\
            coupon_no TEXT,\
            raw_text TEXT NOT NULL,\
-           amount REAL NOT NULL DEFAULT 0,\
-           discount REAL NOT NULL DEFAULT 0,\
-           financial REAL NOT NULL DEFAULT 0,\
-           bar_total REAL NOT NULL DEFAULT 0,\
+           amount INTEGER NOT NULL DEFAULT 0,\
+           discount INTEGER NOT NULL DEFAULT 0,\
+           financial INTEGER NOT NULL DEFAULT 0,\
+           bar_total INTEGER NOT NULL DEFAULT 0,\
+           bundle_id TEXT,\
            created_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_meituan_date ON meituan_orders(date_ymd);\
@@ -146,12 +308,12 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            date_ymd TEXT NOT NULL,\
            shift TEXT NOT NULL,\
            employee TEXT NOT NULL,\
-           wangfei REAL NOT NULL DEFAULT 0,\
-           shouhuo REAL NOT NULL DEFAULT 0,\
-           meituan REAL NOT NULL DEFAULT 0,\
-           zhichu REAL NOT NULL DEFAULT 0,\
-           income REAL NOT NULL DEFAULT 0,\
-           yingjiao REAL NOT NULL DEFAULT 0,\
+           wangfei INTEGER NOT NULL DEFAULT 0,\
+           shouhuo INTEGER NOT NULL DEFAULT 0,\
+           meituan INTEGER NOT NULL DEFAULT 0,\
+           zhichu INTEGER NOT NULL DEFAULT 0,\
+           income INTEGER NOT NULL DEFAULT 0,\
+           yingjiao INTEGER NOT NULL DEFAULT 0,\
            created_at INTEGER NOT NULL\
          );\
          CREATE INDEX IF NOT EXISTS idx_shift_records_date ON shift_records(date_ymd);\
@@ -165,22 +327,503 @@ fn init_db(conn: &Connection) -> Result<(), String> {
            sha256 TEXT NOT NULL,\
            created_at INTEGER NOT NULL\
          );\
-         CREATE INDEX IF NOT EXISTS idx_shift_snapshots_date ON shift_snapshots(date_ymd);",
+         CREATE INDEX IF NOT EXISTS idx_shift_snapshots_date ON shift_snapshots(date_ymd);\
+         CREATE TABLE IF NOT EXISTS stock_locations (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           name TEXT NOT NULL UNIQUE,\
+           kind TEXT NOT NULL DEFAULT 'back',\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS product_stock (\
+           product_id TEXT NOT NULL,\
+           location_id TEXT NOT NULL,\
+           quantity REAL NOT NULL DEFAULT 0,\
+           updated_at INTEGER NOT NULL,\
+           PRIMARY KEY(product_id, location_id)\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_product_stock_location ON product_stock(location_id);\
+         CREATE TABLE IF NOT EXISTS stock_movements (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           product_id TEXT NOT NULL,\
+           location_id TEXT NOT NULL,\
+           delta REAL NOT NULL,\
+           reason TEXT NOT NULL,\
+           ref_id TEXT,\
+           actor_id TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_stock_movements_product ON stock_movements(product_id, created_at);\
+         CREATE INDEX IF NOT EXISTS idx_stock_movements_ref ON stock_movements(ref_id);\
+         CREATE TABLE IF NOT EXISTS stock_transfers (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           date_ymd TEXT NOT NULL,\
+           shift TEXT NOT NULL,\
+           employee TEXT NOT NULL,\
+           actor_id TEXT NOT NULL,\
+           product_id TEXT NOT NULL,\
+           product_name TEXT NOT NULL,\
+           from_location TEXT NOT NULL,\
+           to_location TEXT NOT NULL,\
+           quantity REAL NOT NULL,\
+           note TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_stock_transfers_shift ON stock_transfers(date_ymd, shift);\
+         CREATE TABLE IF NOT EXISTS stock_receipts (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           date_ymd TEXT NOT NULL,\
+           shift TEXT NOT NULL,\
+           employee TEXT NOT NULL,\
+           actor_id TEXT NOT NULL,\
+           product_id TEXT NOT NULL,\
+           product_name TEXT NOT NULL,\
+           location_id TEXT NOT NULL,\
+           unit TEXT NOT NULL,\
+           quantity REAL NOT NULL,\
+           factor REAL NOT NULL,\
+           base_quantity REAL NOT NULL,\
+           unit_cost INTEGER NOT NULL DEFAULT 0,\
+           total_cost INTEGER NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_stock_receipts_date ON stock_receipts(date_ymd);\
+         CREATE TABLE IF NOT EXISTS idempotency_keys (\
+           scope TEXT NOT NULL,\
+           key TEXT NOT NULL,\
+           result TEXT NOT NULL,\
+           created_at INTEGER NOT NULL,\
+           PRIMARY KEY(scope, key)\
+         );\
+         CREATE TABLE IF NOT EXISTS seats (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           code TEXT NOT NULL UNIQUE,\
+           area TEXT,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS seat_orders (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           seat_id TEXT NOT NULL REFERENCES seats(id),\
+           seat_code TEXT NOT NULL,\
+           status TEXT NOT NULL DEFAULT 'pending',\
+           note TEXT,\
+           sales_order_id TEXT,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_seat_orders_status ON seat_orders(status, created_at);\
+         CREATE TABLE IF NOT EXISTS seat_order_items (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           seat_order_id TEXT NOT NULL REFERENCES seat_orders(id),\
+           product_id TEXT NOT NULL REFERENCES products(id),\
+           product_name TEXT NOT NULL,\
+           quantity REAL NOT NULL,\
+           unit_price INTEGER NOT NULL DEFAULT 0,\
+           modifiers TEXT NOT NULL DEFAULT '[]',\
+           modifier_names TEXT NOT NULL DEFAULT '',\
+           sort_order INTEGER NOT NULL DEFAULT 0\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_seat_order_items_order ON seat_order_items(seat_order_id);\
+         CREATE TABLE IF NOT EXISTS carts (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           label TEXT,\
+           status TEXT NOT NULL DEFAULT 'open',\
+           terminal TEXT,\
+           location_id TEXT,\
+           actor_id TEXT,\
+           order_id TEXT,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL,\
+           expires_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_carts_status ON carts(status, expires_at);\
+         CREATE TABLE IF NOT EXISTS cart_items (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           cart_id TEXT NOT NULL REFERENCES carts(id),\
+           product_id TEXT NOT NULL REFERENCES products(id),\
+           quantity REAL NOT NULL,\
+           unit TEXT,\
+           modifiers TEXT NOT NULL DEFAULT '[]',\
+           sort_order INTEGER NOT NULL DEFAULT 0,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_cart_items_cart ON cart_items(cart_id);\
+         CREATE TABLE IF NOT EXISTS print_jobs (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           order_id TEXT,\
+           kind TEXT NOT NULL DEFAULT 'receipt',\
+           payload BLOB NOT NULL,\
+           status TEXT NOT NULL DEFAULT 'queued',\
+           attempts INTEGER NOT NULL DEFAULT 0,\
+           last_error TEXT,\
+           next_attempt_at INTEGER NOT NULL,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_print_jobs_status ON print_jobs(status, next_attempt_at);\
+         CREATE INDEX IF NOT EXISTS idx_print_jobs_order ON print_jobs(order_id);\
+         CREATE TABLE IF NOT EXISTS app_secrets (\
+           name TEXT PRIMARY KEY NOT NULL,\
+           value TEXT NOT NULL,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS customer_displays (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           terminal TEXT NOT NULL,\
+           pair_code TEXT,\
+           pair_expires_at INTEGER,\
+           display_key TEXT UNIQUE,\
+           paired_at INTEGER,\
+           last_seen_at INTEGER,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_customer_displays_code ON customer_displays(pair_code);\
+         CREATE TABLE IF NOT EXISTS members (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           phone TEXT NOT NULL UNIQUE,\
+           name TEXT NOT NULL,\
+           tier TEXT NOT NULL DEFAULT 'standard',\
+           balance INTEGER NOT NULL DEFAULT 0,\
+           points INTEGER NOT NULL DEFAULT 0,\
+           note TEXT,\
+           is_active INTEGER NOT NULL DEFAULT 1,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL\
+         );\
+         CREATE TABLE IF NOT EXISTS member_wallet_entries (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           member_id TEXT NOT NULL REFERENCES members(id),\
+           kind TEXT NOT NULL,\
+           amount INTEGER NOT NULL,\
+           balance_after INTEGER NOT NULL,\
+           method TEXT,\
+           order_id TEXT,\
+           date_ymd TEXT NOT NULL,\
+           shift TEXT,\
+           note TEXT,\
+           actor_id TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_member_wallet_member ON member_wallet_entries(member_id, created_at);\
+         CREATE INDEX IF NOT EXISTS idx_member_wallet_date ON member_wallet_entries(date_ymd, shift);\
+         CREATE TABLE IF NOT EXISTS member_points_entries (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           member_id TEXT NOT NULL REFERENCES members(id),\
+           kind TEXT NOT NULL,\
+           points INTEGER NOT NULL,\
+           balance_after INTEGER NOT NULL,\
+           remaining INTEGER NOT NULL DEFAULT 0,\
+           expires_at INTEGER,\
+           order_id TEXT,\
+           date_ymd TEXT NOT NULL,\
+           note TEXT,\
+           actor_id TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_member_points_member ON member_points_entries(member_id, created_at);\
+         CREATE INDEX IF NOT EXISTS idx_member_points_lots ON member_points_entries(remaining, expires_at);\
+         CREATE INDEX IF NOT EXISTS idx_member_points_order ON member_points_entries(order_id);\
+         CREATE TABLE IF NOT EXISTS coupons (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           code TEXT NOT NULL UNIQUE,\
+           name TEXT NOT NULL,\
+           face_value INTEGER NOT NULL,\
+           min_spend INTEGER NOT NULL DEFAULT 0,\
+           valid_from INTEGER,\
+           valid_until INTEGER,\
+           max_uses INTEGER NOT NULL DEFAULT 1,\
+           use_count INTEGER NOT NULL DEFAULT 0,\
+           status TEXT NOT NULL DEFAULT 'active',\
+           batch_id TEXT,\
+           created_by TEXT,\
+           created_at INTEGER NOT NULL,\
+           updated_at INTEGER NOT NULL,\
+           used_at INTEGER\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_coupons_batch ON coupons(batch_id);\
+         CREATE TABLE IF NOT EXISTS coupon_redemptions (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           coupon_id TEXT NOT NULL REFERENCES coupons(id),\
+           order_id TEXT NOT NULL,\
+           discount INTEGER NOT NULL,\
+           date_ymd TEXT NOT NULL,\
+           actor_id TEXT,\
+           created_at INTEGER NOT NULL,\
+           UNIQUE(coupon_id, order_id)\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_order ON coupon_redemptions(order_id);\
+         CREATE INDEX IF NOT EXISTS idx_coupon_redemptions_date ON coupon_redemptions(date_ymd);\
+         CREATE TABLE IF NOT EXISTS meituan_settlements (\
+           id TEXT PRIMARY KEY NOT NULL,\
+           coupon_no TEXT NOT NULL UNIQUE,\
+           settle_date TEXT NOT NULL,\
+           amount INTEGER NOT NULL,\
+           statement_id TEXT NOT NULL,\
+           created_by TEXT,\
+           created_at INTEGER NOT NULL\
+         );\
+         CREATE INDEX IF NOT EXISTS idx_meituan_settlements_date ON meituan_settlements(settle_date);",
     )
     .map_err(|e| format!("init db: {e}"))?;
 
     // Schema upgrades
-    let _ = conn.execute_batch("ALTER TABLE shift_records ADD COLUMN income REAL NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE shift_records ADD COLUMN income INTEGER NOT NULL DEFAULT 0;");
     let _ = conn.execute_batch(&format!("ALTER TABLE products ADD COLUMN category TEXT NOT NULL DEFAULT '{}';", DEFAULT_CATEGORY));
     // New Auth Columns
     let _ = conn.execute_batch("ALTER TABLE auth_accounts ADD COLUMN proxy_host TEXT;");
     let _ = conn.execute_batch("ALTER TABLE auth_accounts ADD COLUMN is_hidden INTEGER DEFAULT 0;");
-    let _ = conn.execute_batch("ALTER TABLE auth_accounts ADD COLUMN salary_base REAL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE auth_accounts ADD COLUMN salary_base INTEGER DEFAULT 0;");
     let _ = conn.execute_batch("ALTER TABLE auth_accounts ADD COLUMN profile TEXT;");
+    let _ = conn.execute_batch(&format!("ALTER TABLE products ADD COLUMN base_unit TEXT NOT NULL DEFAULT '{}';", DEFAULT_BASE_UNIT));
+    let _ = conn.execute_batch("ALTER TABLE products ADD COLUMN sku TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE products ADD COLUMN kind TEXT NOT NULL DEFAULT 'item';");
+    let _ = conn.execute_batch("ALTER TABLE sales_items ADD COLUMN bundle_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE meituan_orders ADD COLUMN bundle_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE products ADD COLUMN deleted_at INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN rounding INTEGER NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE sales_items ADD COLUMN product_id TEXT REFERENCES products(id);");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN kind TEXT NOT NULL DEFAULT 'sale';");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN status TEXT NOT NULL DEFAULT 'completed';");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN ref_order_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN reason TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN actor_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE sales_items ADD COLUMN ref_item_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN member_id TEXT;");
+    let _ = conn.execute_batch("ALTER TABLE members ADD COLUMN points INTEGER NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE products ADD COLUMN redeem_points INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE sales_items ADD COLUMN redeem_points INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE sales_orders ADD COLUMN coupon_discount INTEGER NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE sales_item_modifiers ADD COLUMN stock_quantity REAL NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE sales_items ADD COLUMN order_discount INTEGER NOT NULL DEFAULT 0;");
+    let _ = conn.execute_batch("ALTER TABLE promotions ADD COLUMN amount INTEGER NOT NULL DEFAULT 0;");
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_sales_items_product ON sales_items(product_id);")
+        .map_err(|e| format!("init sales_items index: {e}"))?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_sales_orders_ref ON sales_orders(ref_order_id);\
+         CREATE INDEX IF NOT EXISTS idx_sales_items_ref ON sales_items(ref_item_id);",
+    )
+    .map_err(|e| format!("init refund indexes: {e}"))?;
+    conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS ux_products_sku ON products(sku) WHERE sku IS NOT NULL AND sku != '';")
+        .map_err(|e| format!("init sku index: {e}"))?;
+
+    migrate_money_to_fen(conn)?;
+    migrate_fixed_promotions(conn)?;
+
+    // Inventory locations
+    let now = now_ts()?;
+    ensure_stock_locations_seed(conn, now)?;
+    backfill_product_stock(conn, now)?;
+    backfill_product_units(conn, now)?;
+    backfill_product_categories(conn, now)?;
+    backfill_product_prices(conn)?;
+    apply_scheduled_prices(conn, now)?;
+    backfill_sales_item_products(conn)?;
+    backfill_meituan_coupon_nos(conn)?;
     
     Ok(())
 }
 
+/// Money columns, stored as INTEGER fen (see `crate::money::Money`).
+const MONEY_COLUMNS: &[(&str, &[&str])] = &[
+    ("auth_accounts", &["salary_base"]),
+    ("products", &["unit_price", "cost_price"]),
+    ("product_prices", &["unit_price"]),
+    ("sales_orders", &["total_revenue", "total_profit", "rounding"]),
+    ("payments", &["amount", "tendered", "change_amount"]),
+    ("sales_items", &["revenue", "unit_price", "cost_price"]),
+    ("modifier_options", &["price_delta"]),
+    ("sales_item_modifiers", &["price_delta"]),
+    ("sales_item_promotions", &["discount"]),
+    ("accounting_entries", &["amount", "bar_pay", "finance_pay"]),
+    ("meituan_orders", &["amount", "discount", "financial", "bar_total"]),
+    ("shift_records", &["wangfei", "shouhuo", "meituan", "zhichu", "income", "yingjiao"]),
+    ("stock_receipts", &["unit_cost", "total_cost"]),
+    ("seat_order_items", &["unit_price"]),
+    ("promotions", &["amount"]),
+];
+
+/// Databases from before `user_version` 1 kept amounts as REAL yuan. Rewrite
+/// them once as whole fen, rounded half away from zero like `Money`, in one
+/// transaction so a crash leaves either the old or the new values.
+fn migrate_money_to_fen(conn: &Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |r| r.get(0))
+        .map_err(|e| format!("read user_version: {e}"))?;
+    if version >= 1 {
+        return Ok(());
+    }
+    let tx = conn.unchecked_transaction().map_err(|e| format!("tx: {e}"))?;
+    for (table, columns) in MONEY_COLUMNS {
+        let sets: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{c} = CAST(ROUND({c} * 100) AS INTEGER)"))
+            .collect();
+        tx.execute(&format!("UPDATE {table} SET {}", sets.join(", ")), [])
+            .map_err(|e| format!("migrate {table} to fen: {e}"))?;
+    }
+    tx.execute_batch("PRAGMA user_version = 1;")
+        .map_err(|e| format!("set user_version: {e}"))?;
+    tx.commit().map_err(|e| format!("commit: {e}"))?;
+    Ok(())
+}
+
+/// "fixed" promotions used to keep their yuan amount in the REAL `value`
+/// column; it moves to `amount` as fen, leaving `value` for percentages.
+fn migrate_fixed_promotions(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "UPDATE promotions SET amount = CAST(ROUND(value * 100) AS INTEGER), value = 0 WHERE rule_type = 'fixed' AND value != 0",
+        [],
+    )
+    .map_err(|e| format!("migrate fixed promotions: {e}"))?;
+    Ok(())
+}
+
+/// Sales lines written before `product_id` existed are linked by name.
+fn backfill_sales_item_products(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "UPDATE sales_items SET product_id = (SELECT p.id FROM products p WHERE p.name = sales_items.product_name) \
+         WHERE product_id IS NULL AND product_name IN (SELECT name FROM products)",
+        [],
+    )
+    .map_err(|e| format!("backfill sales_items product_id: {e}"))?;
+    Ok(())
+}
+
+/// Meituan coupon numbers imported with spaces or dashes keep only their
+/// digits. A row whose digits already belong to another order is left as it
+/// was rather than breaking the unique index.
+fn backfill_meituan_coupon_nos(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT id, coupon_no FROM meituan_orders WHERE coupon_no GLOB '*[^0-9]*'")
+        .map_err(|e| format!("prepare meituan coupon_no: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))
+        .map_err(|e| format!("query meituan coupon_no: {e}"))?;
+    let mut fixes = Vec::new();
+    for r in rows {
+        let (id, raw) = r.map_err(|e| format!("row meituan coupon_no: {e}"))?;
+        let digits: String = raw.chars().filter(|c| c.is_ascii_digit()).collect();
+        fixes.push((id, if digits.is_empty() { None } else { Some(digits) }));
+    }
+    for (id, digits) in fixes {
+        conn.execute("UPDATE OR IGNORE meituan_orders SET coupon_no = ?2 WHERE id = ?1", params![id, digits])
+            .map_err(|e| format!("backfill meituan coupon_no: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Products without any price history start it with their current price.
+fn backfill_product_prices(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO product_prices(id, product_id, unit_price, effective_from, created_at) \
+         SELECT lower(hex(randomblob(16))), id, unit_price, created_at, created_at FROM products p \
+         WHERE NOT EXISTS (SELECT 1 FROM product_prices x WHERE x.product_id = p.id)",
+        [],
+    )
+    .map_err(|e| format!("backfill product_prices: {e}"))?;
+    Ok(())
+}
+
+/// Scheduled price changes take effect the first time the database is opened
+/// after their `effective_from`; `products.unit_price` mirrors the price in force.
+fn apply_scheduled_prices(conn: &Connection, now: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE products SET \
+            unit_price = (SELECT x.unit_price FROM product_prices x WHERE x.product_id = products.id AND x.effective_from <= ?1 \
+                          ORDER BY x.effective_from DESC, x.created_at DESC LIMIT 1), \
+            updated_at = ?1 \
+         WHERE unit_price != (SELECT x.unit_price FROM product_prices x WHERE x.product_id = products.id AND x.effective_from <= ?1 \
+                              ORDER BY x.effective_from DESC, x.created_at DESC LIMIT 1)",
+        [now],
+    )
+    .map_err(|e| format!("apply scheduled prices: {e}"))?;
+    Ok(())
+}
+
+/// Every free-text category already used by a product becomes a row in
+/// `product_categories`; an empty table starts with the default category.
+fn backfill_product_categories(conn: &Connection, now: i64) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT category FROM products \
+             WHERE category != '' AND category NOT IN (SELECT name FROM product_categories) ORDER BY category ASC",
+        )
+        .map_err(|e| format!("prepare categories: {e}"))?;
+    let mut names: Vec<String> = stmt
+        .query_map([], |r| r.get(0))
+        .map_err(|e| format!("query categories: {e}"))?
+        .filter_map(|r| r.ok())
+        .collect();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(1) FROM product_categories", [], |r| r.get(0))
+        .map_err(|e| format!("count categories: {e}"))?;
+    if count == 0 && !names.iter().any(|n| n == DEFAULT_CATEGORY) {
+        names.insert(0, DEFAULT_CATEGORY.to_string());
+    }
+    for (i, name) in names.into_iter().enumerate() {
+        conn.execute(
+            "INSERT OR IGNORE INTO product_categories(id, name, sort_order, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, 1, ?4, ?5)",
+            params![Uuid::new_v4().to_string(), name, count + i as i64, now, now],
+        )
+        .map_err(|e| format!("backfill product_categories: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Legacy `spec` values above 1 were "items per case"; turn them into an
+/// explicit case unit for products that have no units defined yet.
+fn backfill_product_units(conn: &Connection, now: i64) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO product_units(product_id, unit, factor, is_purchase_default, created_at, updated_at) \
+         SELECT id, ?1, spec, 1, ?2, ?2 FROM products p \
+         WHERE spec > 1 AND NOT EXISTS (SELECT 1 FROM product_units u WHERE u.product_id = p.id)",
+        params![DEFAULT_CASE_UNIT, now],
+    )
+    .map_err(|e| format!("backfill product_units: {e}"))?;
+    Ok(())
+}
+
+fn ensure_stock_locations_seed(conn: &Connection, now: i64) -> Result<(), String> {
+    let items = vec![
+        (LOCATION_SHELF, "吧台货架", "front", 0i64),
+        (LOCATION_FRIDGE, "冰柜", "front", 1i64),
+        (LOCATION_STOREROOM, "仓库", "back", 2i64),
+    ];
+    for (id, name, kind, sort_order) in items {
+        conn.execute(
+            "INSERT OR IGNORE INTO stock_locations(id, name, kind, sort_order, is_active, created_at, updated_at) VALUES(?1, ?2, ?3, ?4, 1, ?5, ?6)",
+            params![id, name, kind, sort_order, now, now],
+        )
+        .map_err(|e| format!("seed stock_locations: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Products that have no per-location rows yet (legacy data, cloud restore) get
+/// `on_shelf` placed on the bar shelf and the rest of `stock` in the storeroom.
+fn backfill_product_stock(conn: &Connection, now: i64) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO product_stock(product_id, location_id, quantity, updated_at) \
+         SELECT id, ?1, MAX(MIN(on_shelf, stock), 0), ?2 FROM products p \
+         WHERE NOT EXISTS (SELECT 1 FROM product_stock s WHERE s.product_id = p.id)",
+        params![LOCATION_SHELF, now],
+    )
+    .map_err(|e| format!("backfill shelf stock: {e}"))?;
+    conn.execute(
+        "INSERT INTO product_stock(product_id, location_id, quantity, updated_at) \
+         SELECT p.id, ?1, p.stock - s.quantity, ?2 FROM products p \
+         JOIN product_stock s ON s.product_id = p.id AND s.location_id = ?3 \
+         WHERE NOT EXISTS (SELECT 1 FROM product_stock x WHERE x.product_id = p.id AND x.location_id = ?1)",
+        params![LOCATION_STOREROOM, now, LOCATION_SHELF],
+    )
+    .map_err(|e| format!("backfill storeroom stock: {e}"))?;
+    Ok(())
+}
+
 #[allow(dead_code)]
 fn ensure_auth_seed(_conn: &Connection, _now: i64) -> Result<(), String> {
     Ok(())
@@ -227,7 +870,7 @@ fn ensure_products_seed(conn: &Connection, now: i64) -> Result<(), String> {
             let _ = conn.execute(
                 "INSERT INTO products(id, name, category, unit_price, cost_price, spec, on_shelf, stock, is_active, created_at, updated_at) 
                  VALUES(?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, 1, ?8, ?9)",
-                params![id, name, cat, up, cp, sp, st, now, now],
+                params![id, name, cat, Money::from_yuan(up), Money::from_yuan(cp), sp, st, now, now],
             );
         }
     }
@@ -3,21 +3,38 @@
 
 use axum::{
     extract::{Query, State, Path},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Json},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{delete, get, post},
     Router,
 };
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tauri::Emitter;
+use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
 use tower_http::cors::{Any, CorsLayer};
+use crate::money::Money;
 
 pub const API_HOST: [u8; 4] = [0, 0, 0, 0];
 pub const API_PORT: u16 = 32521;
 
+/// Base URL other devices on the LAN reach this server at. The outgoing
+/// interface is found by "connecting" a UDP socket, which sends nothing.
+pub fn lan_base_url() -> String {
+    let ip = std::net::UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| s.connect("8.8.8.8:80").map(|_| s))
+        .and_then(|s| s.local_addr())
+        .map(|a| a.ip().to_string())
+        .unwrap_or_else(|_| String::from("127.0.0.1"));
+    format!("http://{ip}:{API_PORT}")
+}
+
 // 共享的AppHandle
 pub struct AppState {
     pub app: tauri::AppHandle,
@@ -76,6 +93,58 @@ async fn api_products_list(
     }
 }
 
+// GET /api/products/lookup?code=xxx (扫码枪)
+#[derive(Deserialize)]
+struct ProductLookupQuery {
+    code: String,
+}
+
+async fn api_product_lookup(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ProductLookupQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    match crate::commands::products::product_lookup_by_code(state.app.clone(), params.code) {
+        Ok(Some(hit)) => Ok(ApiResponse::ok(serde_json::to_value(hit).unwrap_or(Value::Null))),
+        Ok(None) => Err(ApiResponse::err(String::from("not_found"))),
+        Err(e) => Err(ApiResponse::err(e)),
+    }
+}
+
+// GET /api/products/export?token=xxx&format=csv|xlsx
+#[derive(Deserialize)]
+struct ProductsExportQuery {
+    token: String,
+    format: Option<String>,
+}
+
+async fn api_products_export(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ProductsExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<Value>>)> {
+    let format = params.format.unwrap_or_else(|| String::from("csv")).trim().to_lowercase();
+    crate::state::auth_resolve_account_id(&params.token).ok_or_else(|| ApiResponse::err(String::from("unauthorized")))?;
+    let conn = crate::db::open_db(&state.app).map_err(ApiResponse::<Value>::err)?;
+    let bytes = crate::commands::catalog_io::export_products(&conn, &format).map_err(ApiResponse::<Value>::err)?;
+    let content_type = if format == "xlsx" {
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    } else {
+        "text/csv; charset=utf-8"
+    };
+    let disposition = format!("attachment; filename=\"products.{format}\"");
+    Ok(([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)], bytes))
+}
+
+// POST /api/products/import (dry_run 默认为 true，仅预览)
+async fn api_products_import(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::ProductImportInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    match crate::commands::catalog_io::products_import(state.app.clone(), input) {
+        Ok(report) => Ok(ApiResponse::ok(serde_json::to_value(report).unwrap_or(Value::Null))),
+        Err(e) => Err(ApiResponse::err(e)),
+    }
+}
+
 // GET /api/meituan/orders
 #[derive(Deserialize)]
 struct MeituanQuery {
@@ -99,10 +168,10 @@ async fn api_meituan_orders(
         .query_map([&date, &limit.to_string()], |row| {
             Ok(serde_json::json!({
                 "raw_text": row.get::<_, String>(0)?,
-                "amount": row.get::<_, f64>(1)?,
-                "discount": row.get::<_, f64>(2)?,
-                "financial": row.get::<_, f64>(3)?,
-                "barPrice": row.get::<_, f64>(4)?,
+                "amount": row.get::<_, Money>(1)?,
+                "discount": row.get::<_, Money>(2)?,
+                "financial": row.get::<_, Money>(3)?,
+                "barPrice": row.get::<_, Money>(4)?,
             }))
         })
         .map_err(|e| ApiResponse::<Value>::err(format!("query: {e}")))?;
@@ -131,7 +200,7 @@ async fn api_shift_calculation(
     let date = params.date.unwrap_or_else(|| crate::db::now_ymd().unwrap_or_default());
     let shift = params.shift.unwrap_or_else(|| String::from("白班"));
 
-    let sales_total: f64 = conn
+    let sales_total: Money = conn
         .query_row(
             "SELECT SUM(total_revenue) FROM sales_orders WHERE date_ymd = ?1 AND shift = ?2",
             [&date, &shift],
@@ -139,9 +208,17 @@ async fn api_shift_calculation(
         )
         .optional()
         .map_err(|e| ApiResponse::<Value>::err(format!("sales: {e}")))?
-        .unwrap_or(0.0);
+        .unwrap_or_default();
+
+    let refunds: Money = conn
+        .query_row(
+            "SELECT COALESCE(-SUM(total_revenue), 0) FROM sales_orders WHERE date_ymd = ?1 AND shift = ?2 AND kind != 'sale'",
+            [&date, &shift],
+            |r| r.get(0),
+        )
+        .map_err(|e| ApiResponse::<Value>::err(format!("refunds: {e}")))?;
 
-    let meituan_total: f64 = conn
+    let meituan_total: Money = conn
         .query_row(
             "SELECT SUM(bar_total) FROM meituan_orders WHERE date_ymd = ?1 AND shift = ?2",
             [&date, &shift],
@@ -149,9 +226,9 @@ async fn api_shift_calculation(
         )
         .optional()
         .map_err(|e| ApiResponse::<Value>::err(format!("meituan: {e}")))?
-        .unwrap_or(0.0);
+        .unwrap_or_default();
 
-    let bar_pay: f64 = conn
+    let bar_pay: Money = conn
         .query_row(
             "SELECT SUM(bar_pay) FROM accounting_entries WHERE date_ymd = ?1 AND shift = ?2 AND entry_type = '支出'",
             [&date, &shift],
@@ -159,9 +236,9 @@ async fn api_shift_calculation(
         )
         .optional()
         .map_err(|e| ApiResponse::<Value>::err(format!("bar_pay: {e}")))?
-        .unwrap_or(0.0);
+        .unwrap_or_default();
 
-    let income: f64 = conn
+    let income: Money = conn
         .query_row(
             "SELECT SUM(amount) FROM accounting_entries WHERE date_ymd = ?1 AND shift = ?2 AND entry_type = '入账'",
             [&date, &shift],
@@ -169,19 +246,30 @@ async fn api_shift_calculation(
         )
         .optional()
         .map_err(|e| ApiResponse::<Value>::err(format!("income: {e}")))?
-        .unwrap_or(0.0);
+        .unwrap_or_default();
+
+    let payments = crate::commands::payments::load_payment_summary(&conn, &date, &shift)
+        .map_err(ApiResponse::<Value>::err)?;
 
-    let internet_fee: f64 = 0.0; 
-    let amount_due: f64 = internet_fee + sales_total - meituan_total - bar_pay;
+    let internet_fee = Money::ZERO;
+    let amount_due = internet_fee + sales_total - meituan_total - bar_pay;
 
     let result = serde_json::json!({
         "internetFee": internet_fee,
         "salesRevenue": sales_total,
+        "refunds": refunds,
         "meituanRevenue": meituan_total,
         "expenditure": bar_pay,
         "income": income,
         "barPay": bar_pay,
-        "amountDue": amount_due
+        "amountDue": amount_due,
+        "cashPayments": payments.cash,
+        "electronicPayments": payments.electronic,
+        "rounding": payments.rounding,
+        "cashDue": payments.cash + payments.member_topups_cash - bar_pay,
+        "paymentsByMethod": payments.by_method,
+        "memberTopups": payments.member_topups,
+        "memberTopupsCash": payments.member_topups_cash
     });
 
     Ok(ApiResponse::ok(result))
@@ -203,9 +291,9 @@ async fn api_finance_accounting(
         .query_map([&date, &shift], |r| {
             Ok(serde_json::json!({
                 "item": r.get::<_, String>(0)?,
-                "amount": r.get::<_, f64>(1)?,
-                "barPay": r.get::<_, f64>(2)?,
-                "financePay": r.get::<_, f64>(3)?,
+                "amount": r.get::<_, Money>(1)?,
+                "barPay": r.get::<_, Money>(2)?,
+                "financePay": r.get::<_, Money>(3)?,
             }))
         })
         .map_err(|e| ApiResponse::<Value>::err(format!("query exp: {e}")))?
@@ -220,16 +308,19 @@ async fn api_finance_accounting(
         .query_map([&date, &shift], |r| {
             Ok(serde_json::json!({
                 "item": r.get::<_, String>(0)?,
-                "amount": r.get::<_, f64>(1)?,
+                "amount": r.get::<_, Money>(1)?,
             }))
         })
         .map_err(|e| ApiResponse::<Value>::err(format!("query inc: {e}")))?
         .filter_map(|r| r.ok())
         .collect();
 
-    let total_exp: f64 = expenses.iter().map(|v| v["amount"].as_f64().unwrap_or(0.0)).sum();
-    let total_inc: f64 = incomes.iter().map(|v| v["amount"].as_f64().unwrap_or(0.0)).sum();
-    let total_bar: f64 = expenses.iter().map(|v| v["barPay"].as_f64().unwrap_or(0.0)).sum();
+    let sum = |rows: &[Value], field: &str| -> Money {
+        rows.iter().map(|v| Money::from_yuan(v[field].as_f64().unwrap_or(0.0))).sum()
+    };
+    let total_exp = sum(&expenses, "amount");
+    let total_inc = sum(&incomes, "amount");
+    let total_bar = sum(&expenses, "barPay");
 
     let result = serde_json::json!({
         "expenses": expenses,
@@ -242,11 +333,23 @@ async fn api_finance_accounting(
     Ok(ApiResponse::ok(result))
 }
 
+// 移动端弱网重试时带上 Idempotency-Key，重复请求返回第一次的结果
+fn idempotency_key(headers: &HeaderMap, body_key: Option<String>) -> Option<String> {
+    body_key.or_else(|| {
+        headers
+            .get("Idempotency-Key")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+    })
+}
+
 // POST 接口：处理来自移动端的数据录入
 async fn api_pos_checkout(
     State(state): State<Arc<AppState>>,
-    Json(input): Json<crate::models::PosCheckoutInput>,
+    headers: HeaderMap,
+    Json(mut input): Json<crate::models::PosCheckoutInput>,
 ) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    input.idempotency_key = idempotency_key(&headers, input.idempotency_key.take());
     match crate::commands::sales::pos_checkout(state.app.clone(), input) {
         Ok(order_id) => Ok(ApiResponse::ok(order_id)),
         Err(e) => Err(ApiResponse::err(e)),
@@ -255,8 +358,10 @@ async fn api_pos_checkout(
 
 async fn api_shift_record_insert(
     State(state): State<Arc<AppState>>,
-    Json(input): Json<crate::models::ShiftRecordInsertInput>,
+    headers: HeaderMap,
+    Json(mut input): Json<crate::models::ShiftRecordInsertInput>,
 ) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    input.idempotency_key = idempotency_key(&headers, input.idempotency_key.take());
     match crate::commands::sales::shift_record_insert(state.app.clone(), input) {
         Ok(id) => Ok(ApiResponse::ok(id)),
         Err(e) => Err(ApiResponse::err(e)),
@@ -265,14 +370,656 @@ async fn api_shift_record_insert(
 
 async fn api_accounting_create(
     State(state): State<Arc<AppState>>,
-    Json(input): Json<crate::models::AccountingEntriesCreateFromShiftInput>,
+    headers: HeaderMap,
+    Json(mut input): Json<crate::models::AccountingEntriesCreateFromShiftInput>,
 ) -> Result<Json<ApiResponse<usize>>, (StatusCode, Json<ApiResponse<usize>>)> {
+    input.idempotency_key = idempotency_key(&headers, input.idempotency_key.take());
     match crate::commands::sales::accounting_entries_create_from_shift(state.app.clone(), input) {
         Ok(count) => Ok(ApiResponse::ok(count)),
         Err(e) => Err(ApiResponse::err(e)),
     }
 }
 
+// ==================== 小票 ====================
+
+#[derive(Deserialize)]
+struct ReceiptQuery {
+    token: String,
+    format: Option<String>,
+}
+
+// GET /api/sales/:id/receipt?token=xxx&format=json|text|html
+async fn api_receipt(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<ReceiptQuery>,
+) -> Result<axum::response::Response, (StatusCode, Json<ApiResponse<Value>>)> {
+    match params.format.as_deref().unwrap_or("json") {
+        "json" => {
+            let receipt = crate::commands::receipts::receipt_get(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+            Ok(ApiResponse::ok(serde_json::to_value(receipt).unwrap_or(Value::Null)).into_response())
+        }
+        "html" => {
+            let page = crate::commands::receipts::receipt_preview(state.app.clone(), params.token, id, params.format).map_err(ApiResponse::<Value>::err)?;
+            Ok(Html(page).into_response())
+        }
+        _ => {
+            let text = crate::commands::receipts::receipt_preview(state.app.clone(), params.token, id, params.format).map_err(ApiResponse::<Value>::err)?;
+            Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response())
+        }
+    }
+}
+
+// GET /api/sales/:id/payments?token=xxx 订单收款明细
+async fn api_payments_list(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let rows = crate::commands::payments::payments_list(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(rows).unwrap_or(Value::Null)))
+}
+
+// GET /api/sales/:id/modifiers?token=xxx 订单规格/加料明细
+async fn api_sales_item_modifiers_list(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let rows = crate::commands::modifiers::sales_item_modifiers_list(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(rows).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct ReceiptPrintPayload {
+    token: String,
+}
+
+// POST /api/sales/:id/print 打印或重印小票，返回打印任务 id
+async fn api_receipt_print(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<ReceiptPrintPayload>,
+) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    match crate::commands::receipts::receipt_print(state.app.clone(), payload.token, id) {
+        Ok(job_id) => Ok(ApiResponse::ok(job_id)),
+        Err(e) => Err(ApiResponse::err(e)),
+    }
+}
+
+// GET /api/sales/:id/receipt_link?token=xxx 电子小票链接和二维码
+async fn api_receipt_link(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let link = crate::commands::receipt_links::receipt_link_create(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(link).unwrap_or(Value::Null)))
+}
+
+// GET /api/sales/:id/receipt_qr.svg?token=xxx 直接作为 <img> 使用
+async fn api_receipt_qr(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<Value>>)> {
+    let link = crate::commands::receipt_links::receipt_link_create(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml"), (header::CACHE_CONTROL, "no-store")], link.qr_svg))
+}
+
+// GET /receipt/:token 顾客扫码查看电子小票（只读，链接签名且会过期）
+async fn receipt_link_page(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> (StatusCode, [(header::HeaderName, &'static str); 1], Html<String>) {
+    let no_store = [(header::CACHE_CONTROL, "no-store")];
+    let result = crate::db::open_db(&state.app).and_then(|conn| {
+        let now = crate::db::now_ts()?;
+        crate::commands::receipt_links::receipt_page(&conn, &token, now)
+    });
+    match result {
+        Ok(page) => (StatusCode::OK, no_store, Html(page)),
+        Err(e) => {
+            let (status, msg) = match e.as_str() {
+                "link_expired" => (StatusCode::GONE, "小票链接已过期，请向收银台索取。"),
+                "not_found" => (StatusCode::NOT_FOUND, "小票不存在或链接无效。"),
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, "暂时无法打开小票，请稍后再试。"),
+            };
+            let page = format!(
+                "<!DOCTYPE html><html lang=\"zh-CN\"><head><meta charset=\"utf-8\">\
+                 <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"><title>电子小票</title></head>\
+                 <body style=\"font-family:sans-serif;text-align:center;padding:3em 1em\"><p>{msg}</p></body></html>"
+            );
+            (status, no_store, Html(page))
+        }
+    }
+}
+
+// ==================== 座位点单（顾客端，无需登录） ====================
+
+// GET /seat/:code 顾客点单页面
+async fn seat_page() -> Html<&'static str> {
+    Html(include_str!("seat_page.html"))
+}
+
+// GET /api/seat/:code/menu
+async fn api_seat_menu(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let conn = crate::db::open_db(&state.app).map_err(ApiResponse::<Value>::err)?;
+    let now = crate::db::now_ts().map_err(ApiResponse::<Value>::err)?;
+    crate::commands::seats::seat_by_code(&conn, &code).map_err(ApiResponse::<Value>::err)?;
+    let menu = crate::commands::seats::load_seat_menu(&conn, now).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(menu).unwrap_or(Value::Null)))
+}
+
+// GET /api/seat/:code/orders
+async fn api_seat_orders(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let conn = crate::db::open_db(&state.app).map_err(ApiResponse::<Value>::err)?;
+    let now = crate::db::now_ts().map_err(ApiResponse::<Value>::err)?;
+    let orders = crate::commands::seats::seat_orders_for_seat(&conn, &code, now).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(orders).unwrap_or(Value::Null)))
+}
+
+// POST /api/seat/:code/orders 下单后通知吧台刷新队列
+async fn api_seat_order_submit(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Json(input): Json<crate::models::SeatOrderSubmitInput>,
+) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    let mut conn = crate::db::open_db(&state.app).map_err(ApiResponse::<String>::err)?;
+    let now = crate::db::now_ts().map_err(ApiResponse::<String>::err)?;
+    let id = crate::commands::seats::submit_seat_order(&mut conn, &code, input, now).map_err(ApiResponse::<String>::err)?;
+    let _ = state.app.emit("seat-orders-changed", id.clone());
+    Ok(ApiResponse::ok(id))
+}
+
+// ==================== 挂单 / 购物车（多终端共享） ====================
+
+// 购物车有变动时通知各终端刷新挂单列表
+fn carts_changed(state: &AppState, cart_id: &str) {
+    let _ = state.app.emit("carts-changed", cart_id.to_string());
+}
+
+#[derive(Deserialize)]
+struct CartsQuery {
+    token: String,
+    status: Option<String>,
+}
+
+// GET /api/carts?token=xxx&status=parked
+async fn api_carts_list(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CartsQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let carts = crate::commands::carts::carts_list(state.app.clone(), params.token, params.status).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(carts).unwrap_or(Value::Null)))
+}
+
+// POST /api/carts
+async fn api_cart_create(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::CartCreateInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let cart = crate::commands::carts::cart_create(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &cart.id);
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: String,
+}
+
+// GET /api/carts/:id?token=xxx
+async fn api_cart_get(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let cart = crate::commands::carts::cart_get(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+// DELETE /api/carts/:id?token=xxx
+async fn api_cart_discard(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    crate::commands::carts::cart_discard(state.app.clone(), params.token, id.clone()).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(Value::Null))
+}
+
+// POST /api/carts/:id/items
+async fn api_cart_item_add(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(mut input): Json<crate::models::CartItemAddInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    input.cart_id = id.clone();
+    let cart = crate::commands::carts::cart_item_add(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+// DELETE /api/carts/:id/items/:item_id?token=xxx
+async fn api_cart_item_remove(
+    State(state): State<Arc<AppState>>,
+    Path((id, item_id)): Path<(String, String)>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let cart = crate::commands::carts::cart_item_remove(state.app.clone(), params.token, id.clone(), item_id).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct CartParkPayload {
+    token: String,
+    label: Option<String>,
+}
+
+// POST /api/carts/:id/park
+async fn api_cart_park(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<CartParkPayload>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let cart = crate::commands::carts::cart_park(state.app.clone(), payload.token, id.clone(), payload.label).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct CartResumePayload {
+    token: String,
+    terminal: Option<String>,
+}
+
+// POST /api/carts/:id/resume 任意终端都可以取回挂单
+async fn api_cart_resume(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<CartResumePayload>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let cart = crate::commands::carts::cart_resume(state.app.clone(), payload.token, id.clone(), payload.terminal).map_err(ApiResponse::<Value>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(serde_json::to_value(cart).unwrap_or(Value::Null)))
+}
+
+// POST /api/carts/:id/checkout
+async fn api_cart_checkout(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(mut input): Json<crate::models::CartCheckoutInput>,
+) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    input.cart_id = id.clone();
+    input.idempotency_key = idempotency_key(&headers, input.idempotency_key.take());
+    let order_id = crate::commands::carts::cart_checkout(state.app.clone(), input).map_err(ApiResponse::<String>::err)?;
+    carts_changed(&state, &id);
+    Ok(ApiResponse::ok(order_id))
+}
+
+// ==================== 顾客屏（副屏 / 平板，配对后订阅收银台购物车） ====================
+
+// GET /display 顾客屏页面
+async fn display_page() -> Html<&'static str> {
+    Html(include_str!("display_page.html"))
+}
+
+// GET /api/displays?token=xxx
+async fn api_displays_list(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let displays = crate::commands::display::displays_list(state.app.clone(), params.token).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(displays).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct DisplayPairStartPayload {
+    token: String,
+    terminal: String,
+}
+
+// POST /api/displays 收银台生成配对码
+async fn api_display_pair_start(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DisplayPairStartPayload>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let pairing = crate::commands::display::display_pair_start(state.app.clone(), payload.token, payload.terminal).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(pairing).unwrap_or(Value::Null)))
+}
+
+// DELETE /api/displays/:id?token=xxx
+async fn api_display_unpair(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    crate::commands::display::display_unpair(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(Value::Null))
+}
+
+// POST /api/display/pair 顾客屏输入配对码换取密钥（无需登录）
+async fn api_display_pair(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::DisplayPairInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let conn = crate::db::open_db(&state.app).map_err(ApiResponse::<Value>::err)?;
+    let now = crate::db::now_ts().map_err(ApiResponse::<Value>::err)?;
+    let paired = crate::commands::display::pair_display(&conn, &input.code, now).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(paired).unwrap_or(Value::Null)))
+}
+
+// POST /api/display/cart 收银台本地购物车推送到顾客屏（每次变动推送一次）
+async fn api_display_cart_push(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::DisplayCartPushInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    crate::commands::display::display_cart_push(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(Value::Null))
+}
+
+#[derive(Deserialize)]
+struct DisplayStreamQuery {
+    key: String,
+}
+
+// GET /api/display/stream?key=xxx 顾客屏订阅（SSE），先推送当前画面，之后每次购物车变动推送一次
+async fn api_display_stream(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DisplayStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let conn = crate::db::open_db(&state.app).map_err(ApiResponse::<Value>::err)?;
+    let now = crate::db::now_ts().map_err(ApiResponse::<Value>::err)?;
+    let terminal = crate::commands::display::display_terminal(&conn, &params.key, now).map_err(ApiResponse::<Value>::err)?;
+    // 先订阅再取快照，避免两者之间的变动丢失
+    let rx = crate::commands::display::subscribe_display(&terminal)
+        .ok_or_else(|| ApiResponse::<Value>::err(String::from("display unavailable")))?;
+    let snapshot = crate::commands::display::display_snapshot(&conn, &terminal, now).map_err(ApiResponse::<Value>::err)?;
+    let first = serde_json::to_string(&snapshot).map_err(|e| ApiResponse::<Value>::err(format!("serialize: {e}")))?;
+    // 顾客屏跟不上时跳过积压的消息，下一条就是最新画面；每条消息前确认密钥仍有效，解除配对后断开
+    let app = state.app.clone();
+    let key = params.key.clone();
+    let updates = BroadcastStream::new(rx)
+        .filter_map(|m| m.ok())
+        .take_while(move |_| crate::db::open_db(&app).is_ok_and(|c| crate::commands::display::display_key_valid(&c, &key)));
+    let stream = tokio_stream::once(first).chain(updates).map(|data| Ok(Event::default().data(data)));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// ==================== 会员 / 储值 ====================
+
+#[derive(Deserialize)]
+struct MembersQuery {
+    token: String,
+    query: Option<String>,
+}
+
+// GET /api/members?token=xxx&query=138
+async fn api_members_list(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<MembersQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let members = crate::commands::members::members_list(state.app.clone(), params.token, params.query).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(members).unwrap_or(Value::Null)))
+}
+
+// POST /api/members 新建或修改会员（余额只能通过充值/调整变动）
+async fn api_member_upsert(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::MemberUpsertInput>,
+) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    let id = crate::commands::members::member_upsert(state.app.clone(), input).map_err(ApiResponse::<String>::err)?;
+    Ok(ApiResponse::ok(id))
+}
+
+#[derive(Deserialize)]
+struct MemberLookupQuery {
+    token: String,
+    phone: String,
+}
+
+// GET /api/members/lookup?token=xxx&phone=13800000000
+async fn api_member_lookup(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<MemberLookupQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let member = crate::commands::members::member_lookup(state.app.clone(), params.token, params.phone).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(member).unwrap_or(Value::Null)))
+}
+
+// GET /api/members/:id?token=xxx
+async fn api_member_get(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let member = crate::commands::members::member_get(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(member).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct WalletEntriesQuery {
+    token: String,
+    kind: Option<String>,
+    limit: Option<i64>,
+}
+
+// GET /api/members/:id/wallet?token=xxx&kind=topup&limit=50 储值流水
+async fn api_member_wallet_entries(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<WalletEntriesQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let entries = crate::commands::members::member_wallet_entries(state.app.clone(), params.token, id, params.kind, params.limit).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(entries).unwrap_or(Value::Null)))
+}
+
+// POST /api/members/:id/topup 充值（计入交班收款，不计入营业收入）
+async fn api_member_topup(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(mut input): Json<crate::models::MemberTopupInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    input.member_id = id;
+    input.idempotency_key = idempotency_key(&headers, input.idempotency_key.take());
+    let entry = crate::commands::members::member_topup(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(entry).unwrap_or(Value::Null)))
+}
+
+// POST /api/members/:id/adjust 管理员手工调整余额
+async fn api_member_adjust(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(mut input): Json<crate::models::MemberAdjustInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    input.member_id = id;
+    let entry = crate::commands::members::member_adjust(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(entry).unwrap_or(Value::Null)))
+}
+
+// ==================== 会员积分 ====================
+
+// GET /api/loyalty/settings?token=xxx 积分规则
+async fn api_loyalty_settings_get(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let settings = crate::commands::loyalty::loyalty_settings_get(state.app.clone(), params.token).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(settings).unwrap_or(Value::Null)))
+}
+
+// GET /api/members/:id/points?token=xxx&kind=earn&limit=50 积分流水
+async fn api_member_points_entries(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<WalletEntriesQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let entries = crate::commands::loyalty::member_points_entries(state.app.clone(), params.token, id, params.kind, params.limit).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(entries).unwrap_or(Value::Null)))
+}
+
+// POST /api/members/:id/points/adjust 管理员手工调整积分
+async fn api_member_points_adjust(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(mut input): Json<crate::models::MemberPointsAdjustInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    input.member_id = id;
+    let entry = crate::commands::loyalty::member_points_adjust(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(entry).unwrap_or(Value::Null)))
+}
+
+// GET /api/products/redeemable?token=xxx 可用积分兑换的商品
+async fn api_redeemable_products(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let products = crate::commands::loyalty::redeemable_products(state.app.clone(), params.token).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(products).unwrap_or(Value::Null)))
+}
+
+// ==================== 优惠券 ====================
+
+#[derive(Deserialize)]
+struct CouponsQuery {
+    token: String,
+    status: Option<String>,
+    batch_id: Option<String>,
+}
+
+// GET /api/coupons?token=xxx&status=active&batch_id=xxx
+async fn api_coupons_list(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CouponsQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let coupons = crate::commands::coupons::coupons_list(state.app.clone(), params.token, params.status, params.batch_id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(coupons).unwrap_or(Value::Null)))
+}
+
+// POST /api/coupons 新建单张优惠券（不填券码则自动生成）
+async fn api_coupon_create(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::CouponCreateInput>,
+) -> Result<Json<ApiResponse<String>>, (StatusCode, Json<ApiResponse<String>>)> {
+    let id = crate::commands::coupons::coupon_create(state.app.clone(), input).map_err(ApiResponse::<String>::err)?;
+    Ok(ApiResponse::ok(id))
+}
+
+// POST /api/coupons/batch 批量生成券码
+async fn api_coupon_batch_create(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::CouponBatchInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let batch = crate::commands::coupons::coupon_batch_create(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(batch).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct CouponLookupQuery {
+    token: String,
+    code: String,
+}
+
+// GET /api/coupons/lookup?token=xxx&code=ABCD2345 查券
+async fn api_coupon_lookup(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CouponLookupQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let coupon = crate::commands::coupons::coupon_lookup(state.app.clone(), params.token, params.code).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(coupon).unwrap_or(Value::Null)))
+}
+
+// POST /api/coupons/use 核销优惠券（收银台结账请用 coupon_code）
+async fn api_coupon_use(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::CouponUseInput>,
+) -> Result<Json<ApiResponse<bool>>, (StatusCode, Json<ApiResponse<bool>>)> {
+    let ok = crate::commands::coupons::coupon_use(state.app.clone(), input).map_err(ApiResponse::<bool>::err)?;
+    Ok(ApiResponse::ok(ok))
+}
+
+// POST /api/coupons/:id/void 作废优惠券
+async fn api_coupon_void(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<TokenQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    crate::commands::coupons::coupon_void(state.app.clone(), params.token, id).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(Value::Null))
+}
+
+#[derive(Deserialize)]
+struct CouponReportQuery {
+    token: String,
+    date_from: String,
+    date_to: String,
+}
+
+// GET /api/coupons/report?token=xxx&date_from=2025-01-01&date_to=2025-01-31 优惠券使用报表
+async fn api_coupon_usage_report(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CouponReportQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let report = crate::commands::coupons::coupon_usage_report(state.app.clone(), params.token, params.date_from, params.date_to).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(report).unwrap_or(Value::Null)))
+}
+
+// ==================== 美团验券 ====================
+
+// POST /api/meituan/parse 解析粘贴的验券记录（不保存，用于预览）
+async fn api_meituan_parse(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::MeituanTextInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let parsed = crate::commands::meituan::meituan_orders_parse(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(parsed).unwrap_or(Value::Null)))
+}
+
+// POST /api/meituan/import 解析并导入验券记录，返回新增数、重复数和解析失败的行
+async fn api_meituan_import_text(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::MeituanTextInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let result = crate::commands::meituan::meituan_orders_import_text(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(result).unwrap_or(Value::Null)))
+}
+
+// POST /api/meituan/settlements/import 导入美团结算单 (CSV/XLSX，dry_run 默认为 true，仅预览)
+async fn api_meituan_settlements_import(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<crate::models::MeituanSettlementImportInput>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let report = crate::commands::meituan::meituan_settlements_import(state.app.clone(), input).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(report).unwrap_or(Value::Null)))
+}
+
+#[derive(Deserialize)]
+struct MeituanReconcileQuery {
+    token: String,
+    date_from: String,
+    date_to: String,
+}
+
+// GET /api/meituan/reconciliation?token=xxx&date_from=2025-10-01&date_to=2025-10-31 美团对账：未结算、结算单多出和金额不符的券
+async fn api_meituan_reconciliation(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<MeituanReconcileQuery>,
+) -> Result<Json<ApiResponse<Value>>, (StatusCode, Json<ApiResponse<Value>>)> {
+    let report = crate::commands::meituan::meituan_reconciliation(state.app.clone(), params.token, params.date_from, params.date_to).map_err(ApiResponse::<Value>::err)?;
+    Ok(ApiResponse::ok(serde_json::to_value(report).unwrap_or(Value::Null)))
+}
+
 #[derive(Deserialize)]
 struct AuthLoginPayload {
     input: crate::models::AuthLoginInput,
@@ -481,18 +1228,64 @@ pub async fn start_http_server(app: tauri::AppHandle) -> Result<(), Box<dyn std:
 
     let api_routes = Router::new()
         .route("/products", get(api_products_list))
+        .route("/products/lookup", get(api_product_lookup))
+        .route("/products/export", get(api_products_export))
+        .route("/products/import", post(api_products_import))
         .route("/meituan/orders", get(api_meituan_orders))
+        .route("/meituan/parse", post(api_meituan_parse))
+        .route("/meituan/import", post(api_meituan_import_text))
+        .route("/meituan/settlements/import", post(api_meituan_settlements_import))
+        .route("/meituan/reconciliation", get(api_meituan_reconciliation))
         .route("/shift/calculation", get(api_shift_calculation))
         .route("/shift/record", post(api_shift_record_insert))
         .route("/finance/accounting", get(api_finance_accounting).post(api_accounting_create))
         .route("/pos/checkout", post(api_pos_checkout))
+        .route("/sales/:id/receipt", get(api_receipt))
+        .route("/sales/:id/payments", get(api_payments_list))
+        .route("/sales/:id/modifiers", get(api_sales_item_modifiers_list))
+        .route("/sales/:id/print", post(api_receipt_print))
+        .route("/sales/:id/receipt_link", get(api_receipt_link))
+        .route("/sales/:id/receipt_qr.svg", get(api_receipt_qr))
         .route("/auth/login", post(api_auth_login))
         .route("/auth/employee_login", post(api_auth_employee_login))
         .route("/auth/pick_list", get(api_auth_pick_list))
+        .route("/seat/:code/menu", get(api_seat_menu))
+        .route("/seat/:code/orders", get(api_seat_orders).post(api_seat_order_submit))
+        .route("/carts", get(api_carts_list).post(api_cart_create))
+        .route("/carts/:id", get(api_cart_get).delete(api_cart_discard))
+        .route("/carts/:id/items", post(api_cart_item_add))
+        .route("/carts/:id/items/:item_id", delete(api_cart_item_remove))
+        .route("/carts/:id/park", post(api_cart_park))
+        .route("/carts/:id/resume", post(api_cart_resume))
+        .route("/carts/:id/checkout", post(api_cart_checkout))
+        .route("/displays", get(api_displays_list).post(api_display_pair_start))
+        .route("/displays/:id", delete(api_display_unpair))
+        .route("/display/pair", post(api_display_pair))
+        .route("/display/cart", post(api_display_cart_push))
+        .route("/display/stream", get(api_display_stream))
+        .route("/members", get(api_members_list).post(api_member_upsert))
+        .route("/members/lookup", get(api_member_lookup))
+        .route("/members/:id", get(api_member_get))
+        .route("/members/:id/wallet", get(api_member_wallet_entries))
+        .route("/members/:id/topup", post(api_member_topup))
+        .route("/members/:id/adjust", post(api_member_adjust))
+        .route("/members/:id/points", get(api_member_points_entries))
+        .route("/members/:id/points/adjust", post(api_member_points_adjust))
+        .route("/loyalty/settings", get(api_loyalty_settings_get))
+        .route("/products/redeemable", get(api_redeemable_products))
+        .route("/coupons", get(api_coupons_list).post(api_coupon_create))
+        .route("/coupons/batch", post(api_coupon_batch_create))
+        .route("/coupons/lookup", get(api_coupon_lookup))
+        .route("/coupons/use", post(api_coupon_use))
+        .route("/coupons/report", get(api_coupon_usage_report))
+        .route("/coupons/:id/void", post(api_coupon_void))
         .route("/rpc/:cmd", post(api_rpc_handler));
 
     let app_router = Router::new()
         .route("/", get(|| async { "🚀 Smarticafe API Hub is running!" }))
+        .route("/seat/:code", get(seat_page))
+        .route("/receipt/:token", get(receipt_link_page))
+        .route("/display", get(display_page))
         .nest("/api", api_routes)
         .layer(cors)
         .with_state(state);
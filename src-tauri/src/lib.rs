@@ -8,6 +8,7 @@ pub mod commands;
 pub mod http_server;
 pub mod constants;
 pub mod cipher;
+pub mod money;
 
 use crate::commands::*;
 
@@ -39,6 +40,12 @@ pub fn run() {
                 }
             });
 
+            // 启动小票打印队列
+            commands::printing::start_print_worker(app.handle().clone());
+
+            // 启动积分过期处理（启动时一次，之后每小时）
+            commands::loyalty::start_points_expiry_worker(app.handle().clone());
+
             // 启动HTTP API服务器
             let app_handle = app.handle().clone();
             std::thread::spawn(move || {
@@ -90,12 +97,22 @@ pub fn run() {
             product_upsert,
             product_delete,
             migrate_products_from_kv,
+            product_lookup_by_code,
+            product_barcodes_list,
+            product_barcode_add,
+            product_barcode_remove,
+            products_export,
+            products_import,
 
             // Sales & Shift Commands
             shift_record_insert,
             sales_order_create_from_shift,
             accounting_entries_create_from_shift,
             meituan_orders_import,
+            meituan_orders_parse,
+            meituan_orders_import_text,
+            meituan_settlements_import,
+            meituan_reconciliation,
             shift_records_list,
             sales_orders_list,
             sales_items_list,
@@ -118,15 +135,128 @@ pub fn run() {
             coupons_list,
             coupon_use,
             coupon_create,
+            coupon_lookup,
+            coupon_batch_create,
+            coupon_void,
+            coupon_usage_report,
 
             // Finance Commands
             finance_daily_report,
             finance_weekly_report,
             finance_monthly_report,
             finance_dividend_report,
+            sales_category_report,
 
             // Cloud Sync Commands
             db_replace_from_cloud,
+
+            // Inventory Commands
+            stock_locations_list,
+            stock_location_upsert,
+            product_stock_list,
+            stock_transfer_create,
+            stock_transfers_list,
+            stock_adjust,
+            stock_restock_summary,
+            stock_receive,
+            stock_receipts_list,
+
+            // Unit Commands
+            product_units_list,
+            product_unit_upsert,
+            product_unit_delete,
+
+            // Modifier Commands
+            modifier_groups_list,
+            modifier_group_upsert,
+            modifier_option_upsert,
+            product_modifier_groups_set,
+            sales_item_modifiers_list,
+
+            // Bundle Commands
+            bundle_components_list,
+            bundle_components_set,
+
+            // Recipe Commands
+            recipe_get,
+            recipe_set,
+            recipe_usage_report,
+
+            // Category Commands
+            product_categories_list,
+            product_category_upsert,
+            product_category_rename,
+            product_category_merge,
+
+            // Pricing Commands
+            product_prices_list,
+            product_price_schedule,
+            product_price_cancel,
+
+            // Promotion Commands
+            promotions_list,
+            promotion_upsert,
+            sales_item_promotions_list,
+            promotions_report,
+
+            // Payment Commands
+            payments_list,
+            shift_payment_summary,
+
+            // Refund Commands
+            order_void,
+            order_refund,
+
+            // Seat Order Commands
+            seats_list,
+            seat_upsert,
+            seat_orders_queue,
+            seat_order_advance,
+
+            // Cart Commands
+            cart_create,
+            cart_get,
+            carts_list,
+            cart_item_add,
+            cart_item_remove,
+            cart_park,
+            cart_resume,
+            cart_discard,
+            cart_checkout,
+
+            // Receipt & Printing Commands
+            receipt_get,
+            receipt_preview,
+            receipt_print,
+            printer_settings_get,
+            settings_save_printer,
+            print_jobs_list,
+            print_job_retry,
+            receipt_link_create,
+            receipt_link_settings_get,
+            settings_save_receipt_links,
+
+            // Customer Display Commands
+            display_pair_start,
+            displays_list,
+            display_unpair,
+            display_cart_push,
+
+            // Member Commands
+            members_list,
+            member_get,
+            member_lookup,
+            member_upsert,
+            member_wallet_entries,
+            member_topup,
+            member_adjust,
+            // Loyalty Commands
+            loyalty_settings_get,
+            settings_save_loyalty,
+            member_points_entries,
+            member_points_adjust,
+            product_set_redeem_points,
+            redeemable_products,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+use crate::money::Money;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthSession {
@@ -115,12 +117,15 @@ pub struct ProductRow {
     pub id: String,
     pub name: String,
     pub category: String,
-    pub unit_price: f64,
-    pub cost_price: f64,
+    pub unit_price: Money,
+    pub cost_price: Money,
     pub spec: f64,
     pub on_shelf: f64,
     pub stock: f64,
     pub is_active: bool,
+    pub base_unit: String,
+    pub sku: Option<String>,
+    pub kind: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,12 +133,434 @@ pub struct ProductUpsertInput {
     pub id: Option<String>,
     pub name: String,
     pub category: Option<String>,
-    pub unit_price: f64,
-    pub cost_price: f64,
+    pub unit_price: Money,
+    pub cost_price: Money,
     pub spec: f64,
     pub on_shelf: f64,
     pub stock: f64,
     pub is_active: Option<bool>,
+    pub base_unit: Option<String>,
+    pub sku: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleComponentRow {
+    pub component_id: String,
+    pub component_name: String,
+    pub quantity: f64,
+    pub unit_price: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleComponentInput {
+    pub product_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleComponentsSetInput {
+    pub token: String,
+    pub bundle_id: String,
+    pub components: Vec<BundleComponentInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductPriceRow {
+    pub id: String,
+    pub product_id: String,
+    pub unit_price: Money,
+    pub effective_from: i64,
+    pub note: Option<String>,
+    pub actor_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductPriceScheduleInput {
+    pub token: String,
+    pub product_id: String,
+    pub unit_price: Money,
+    pub effective_from: i64,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionRow {
+    pub id: String,
+    pub name: String,
+    pub rule_type: String,
+    pub scope: String,
+    pub target: Option<String>,
+    pub value: f64,
+    pub buy_qty: f64,
+    pub get_qty: f64,
+    pub weekdays: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub priority: i64,
+    pub is_active: bool,
+    /// Off each unit for "fixed" promotions; zero for the others.
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub name: String,
+    pub rule_type: String,
+    pub scope: Option<String>,
+    pub target: Option<String>,
+    pub value: Option<f64>,
+    pub buy_qty: Option<f64>,
+    pub get_qty: Option<f64>,
+    pub weekdays: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub priority: Option<i64>,
+    pub is_active: Option<bool>,
+    pub amount: Option<Money>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesItemPromotionRow {
+    pub id: String,
+    pub sales_item_id: String,
+    pub order_id: String,
+    pub promotion_id: String,
+    pub promotion_name: String,
+    pub discount: Money,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionStat {
+    pub promotion_id: String,
+    pub promotion_name: String,
+    pub lines: i64,
+    pub discount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductCategoryRow {
+    pub id: String,
+    pub name: String,
+    pub sort_order: i64,
+    pub is_active: bool,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub account_item: Option<String>,
+    pub tax_rate: f64,
+    pub product_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductCategoryUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub name: String,
+    pub sort_order: Option<i64>,
+    pub is_active: Option<bool>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub account_item: Option<String>,
+    pub tax_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductCategoryMergeInput {
+    pub token: String,
+    pub source_id: String,
+    pub target_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesCategoryStat {
+    pub category: String,
+    pub quantity: f64,
+    pub revenue: Money,
+    pub cost: Money,
+    pub profit: Money,
+    pub account_item: Option<String>,
+    pub tax_rate: f64,
+    /// Output tax included in `revenue`.
+    pub tax: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeItemRow {
+    pub ingredient_id: String,
+    pub ingredient_name: String,
+    pub base_unit: String,
+    pub quantity: f64,
+    pub unit_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeItemInput {
+    pub ingredient_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeSetInput {
+    pub token: String,
+    pub product_id: String,
+    pub items: Vec<RecipeItemInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeUsageRow {
+    pub ingredient_id: String,
+    pub ingredient_name: String,
+    pub base_unit: String,
+    pub theoretical: f64,
+    pub actual: f64,
+    pub variance: f64,
+    pub variance_cost: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductImportInput {
+    pub token: String,
+    pub format: String,
+    pub content: Vec<u8>,
+    pub mapping: Option<HashMap<String, String>>,
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductImportRow {
+    pub row: usize,
+    pub action: String,
+    pub product_id: Option<String>,
+    pub name: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductImportReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub rows: Vec<ProductImportRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductLookup {
+    pub product: ProductRow,
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductBarcodeRow {
+    pub code: String,
+    pub product_id: String,
+    pub unit: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductBarcodeAddInput {
+    pub token: String,
+    pub product_id: String,
+    pub code: String,
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductUnitRow {
+    pub product_id: String,
+    pub unit: String,
+    pub factor: f64,
+    pub is_purchase_default: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductUnitUpsertInput {
+    pub token: String,
+    pub product_id: String,
+    pub unit: String,
+    pub factor: f64,
+    pub is_purchase_default: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockReceiveInput {
+    pub token: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub product_id: String,
+    pub location_id: Option<String>,
+    pub unit: Option<String>,
+    pub quantity: f64,
+    pub unit_cost: Option<Money>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockReceiptRow {
+    pub id: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub location_id: String,
+    pub unit: String,
+    pub quantity: f64,
+    pub factor: f64,
+    pub base_quantity: f64,
+    pub unit_cost: Money,
+    pub total_cost: Money,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifierGroupRow {
+    pub id: String,
+    pub name: String,
+    pub selection: String,
+    pub is_required: bool,
+    pub sort_order: i64,
+    pub is_active: bool,
+    pub options: Vec<ModifierOptionRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifierGroupUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub name: String,
+    pub selection: Option<String>,
+    pub is_required: Option<bool>,
+    pub sort_order: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifierOptionRow {
+    pub id: String,
+    pub group_id: String,
+    pub name: String,
+    pub price_delta: Money,
+    pub stock_product_id: Option<String>,
+    pub stock_quantity: f64,
+    pub sort_order: i64,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifierOptionUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub group_id: String,
+    pub name: String,
+    pub price_delta: Money,
+    pub stock_product_id: Option<String>,
+    pub stock_quantity: Option<f64>,
+    pub sort_order: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductModifierGroupsSetInput {
+    pub token: String,
+    pub product_id: String,
+    pub group_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesItemModifierRow {
+    pub id: String,
+    pub sales_item_id: String,
+    pub order_id: String,
+    pub option_id: String,
+    pub group_name: String,
+    pub option_name: String,
+    pub price_delta: Money,
+    /// Base units of the option's stock product the line used.
+    pub stock_quantity: f64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockLocationRow {
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+    pub sort_order: i64,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockLocationUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub name: String,
+    pub kind: String,
+    pub sort_order: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductStockRow {
+    pub product_id: String,
+    pub product_name: String,
+    pub location_id: String,
+    pub location_name: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransferInput {
+    pub token: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub product_id: String,
+    pub from_location: String,
+    pub to_location: String,
+    pub quantity: f64,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransferRow {
+    pub id: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub actor_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub from_location: String,
+    pub to_location: String,
+    pub quantity: f64,
+    pub note: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockAdjustInput {
+    pub token: String,
+    pub product_id: String,
+    pub location_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestockSummaryRow {
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -141,12 +568,13 @@ pub struct ShiftRecordInsertInput {
     pub date_ymd: String,
     pub shift: String,
     pub employee: String,
-    pub wangfei: f64,
-    pub shouhuo: f64,
-    pub meituan: f64,
-    pub zhichu: f64,
-    pub income: f64,
-    pub yingjiao: f64,
+    pub wangfei: Money,
+    pub shouhuo: Money,
+    pub meituan: Money,
+    pub zhichu: Money,
+    pub income: Money,
+    pub yingjiao: Money,
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -155,12 +583,12 @@ pub struct ShiftRecordRow {
     pub date_ymd: String,
     pub shift: String,
     pub employee: String,
-    pub wangfei: f64,
-    pub shouhuo: f64,
-    pub meituan: f64,
-    pub zhichu: f64,
-    pub income: f64,
-    pub yingjiao: f64,
+    pub wangfei: Money,
+    pub shouhuo: Money,
+    pub meituan: Money,
+    pub zhichu: Money,
+    pub income: Money,
+    pub yingjiao: Money,
     pub created_at: i64,
 }
 
@@ -192,8 +620,14 @@ pub struct SalesOrderRow {
     pub date_ymd: String,
     pub shift: String,
     pub employee: String,
-    pub total_revenue: f64,
-    pub total_profit: f64,
+    pub total_revenue: Money,
+    pub total_profit: Money,
+    pub kind: String,
+    pub status: String,
+    pub ref_order_id: Option<String>,
+    pub reason: Option<String>,
+    /// Receipts printed for the order so far.
+    pub print_count: i64,
     pub created_at: i64,
 }
 
@@ -201,6 +635,7 @@ pub struct SalesOrderRow {
 pub struct SalesItemRow {
     pub id: String,
     pub order_id: String,
+    pub product_id: Option<String>,
     pub product_name: String,
     pub original: Option<f64>,
     pub restock: Option<f64>,
@@ -212,10 +647,12 @@ pub struct SalesItemRow {
     pub stock_prev: Option<f64>,
     pub stock: Option<f64>,
     pub sales: Option<f64>,
-    pub revenue: Option<f64>,
-    pub unit_price: Option<f64>,
-    pub cost_price: Option<f64>,
+    pub revenue: Option<Money>,
+    pub unit_price: Option<Money>,
+    pub cost_price: Option<Money>,
     pub spec: Option<f64>,
+    pub bundle_id: Option<String>,
+    pub ref_item_id: Option<String>,
     pub created_at: i64,
 }
 
@@ -227,9 +664,9 @@ pub struct AccountingEntryRow {
     pub employee: String,
     pub entry_type: String,
     pub item: String,
-    pub amount: f64,
-    pub bar_pay: f64,
-    pub finance_pay: f64,
+    pub amount: Money,
+    pub bar_pay: Money,
+    pub finance_pay: Money,
     pub created_at: i64,
 }
 
@@ -241,10 +678,11 @@ pub struct MeituanOrderRow {
     pub employee: String,
     pub coupon_no: Option<String>,
     pub raw_text: String,
-    pub amount: f64,
-    pub discount: f64,
-    pub financial: f64,
-    pub bar_total: f64,
+    pub amount: Money,
+    pub discount: Money,
+    pub financial: Money,
+    pub bar_total: Money,
+    pub bundle_id: Option<String>,
     pub created_at: i64,
 }
 
@@ -260,10 +698,119 @@ pub struct MeituanOrderImportItem {
     pub employee: String,
     pub coupon_no: Option<String>,
     pub raw_text: String,
-    pub amount: f64,
-    pub discount: f64,
-    pub financial: f64,
-    pub bar_total: f64,
+    pub amount: Money,
+    pub discount: Money,
+    pub financial: Money,
+    pub bar_total: Money,
+    pub bundle_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanTextInput {
+    pub token: String,
+    /// Verification records as pasted from 开店宝, one per line.
+    pub text: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub bundle_id: Option<String>,
+}
+
+/// A pasted line the parser could not read; `line` counts from 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanParseError {
+    pub line: usize,
+    pub text: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanParseResult {
+    pub items: Vec<MeituanOrderImportItem>,
+    pub errors: Vec<MeituanParseError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanTextImportResult {
+    pub inserted: usize,
+    /// Parsed records that were already imported.
+    pub duplicates: usize,
+    pub errors: Vec<MeituanParseError>,
+}
+
+/// A Meituan settlement statement export. `mapping` points a field
+/// (coupon_no, amount, settle_date) at a header the defaults don't know;
+/// `settle_date` is used for rows without a date of their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanSettlementImportInput {
+    pub token: String,
+    pub format: String,
+    pub content: Vec<u8>,
+    pub mapping: Option<HashMap<String, String>>,
+    pub settle_date: Option<String>,
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanSettlementImportRow {
+    pub row: usize,
+    /// create, skip (already imported) or error.
+    pub action: String,
+    pub coupon_no: Option<String>,
+    pub amount: Option<Money>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanSettlementImportReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub statement_id: String,
+    pub created: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    /// Sum of the rows created, i.e. what this statement adds to the Meituan wallet.
+    pub total: Money,
+    pub rows: Vec<MeituanSettlementImportRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanSettlementRow {
+    pub id: String,
+    pub coupon_no: String,
+    pub settle_date: String,
+    pub amount: Money,
+    pub statement_id: String,
+    pub created_at: i64,
+}
+
+/// A voucher Meituan settled for a different amount than we recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanSettlementMismatch {
+    pub coupon_no: String,
+    pub date_ymd: String,
+    pub settle_date: String,
+    pub expected: Money,
+    pub settled: Money,
+    /// settled - expected.
+    pub difference: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeituanReconciliation {
+    pub date_from: String,
+    pub date_to: String,
+    /// Verified in the range, not on any statement yet.
+    pub unsettled: Vec<MeituanOrderRow>,
+    /// Settled in the range, never verified here.
+    pub unrecorded: Vec<MeituanSettlementRow>,
+    pub mismatched: Vec<MeituanSettlementMismatch>,
+    /// What the vouchers verified in the range should settle for.
+    pub expected_total: Money,
+    /// What has been settled for those vouchers so far.
+    pub settled_total: Money,
+    pub unsettled_total: Money,
+    pub unrecorded_total: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -273,14 +820,18 @@ pub struct AccountingEntriesCreateFromShiftInput {
     pub employee: String,
     pub expenses: Vec<AccountingEntryInput>,
     pub incomes: Vec<AccountingEntryInput>,
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccountingEntryInput {
     pub item: String,
-    pub amount: f64,
-    pub bar_pay: f64,
-    pub finance_pay: f64,
+    /// A product category; an entry without an item is booked under the
+    /// category's account item.
+    pub category: Option<String>,
+    pub amount: Money,
+    pub bar_pay: Money,
+    pub finance_pay: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -294,6 +845,8 @@ pub struct SalesOrderCreateFromShiftInput {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SalesItemInput {
     pub product_name: String,
+    /// Sent by newer clients; older ones are matched to a product by name.
+    pub product_id: Option<String>,
     pub original: Option<f64>,
     pub restock: Option<f64>,
     pub remaining: Option<f64>,
@@ -304,9 +857,9 @@ pub struct SalesItemInput {
     pub stock_prev: Option<f64>,
     pub stock: Option<f64>,
     pub sales: Option<f64>,
-    pub revenue: Option<f64>,
-    pub unit_price: Option<f64>,
-    pub cost_price: Option<f64>,
+    pub revenue: Option<Money>,
+    pub unit_price: Option<Money>,
+    pub cost_price: Option<Money>,
     pub spec: Option<f64>,
 }
 
@@ -317,12 +870,90 @@ pub struct PosCheckoutInput {
     pub shift: String,
     pub employee: String,
     pub items: Vec<PosItem>,
+    pub location_id: Option<String>,
+    pub payments: Option<Vec<PosPayment>>,
+    pub rounding: Option<String>,
+    /// Client-generated id for the order; a retry with the same id returns it.
+    pub order_id: Option<String>,
+    pub idempotency_key: Option<String>,
+    /// Member the sale is for; required when paying from a member balance.
+    pub member_id: Option<String>,
+    /// Coupon taken off the order before rounding.
+    pub coupon_code: Option<String>,
+    /// Till rung up on; its customer display shows the amount paid.
+    pub terminal: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRefundInput {
+    pub token: String,
+    pub order_id: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    pub reason: String,
+    /// Lines to return; `None` refunds whatever is left of the order.
+    pub lines: Option<Vec<OrderRefundLine>>,
+    pub method: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRefundLine {
+    pub item_id: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PosPayment {
+    pub method: String,
+    pub amount: Money,
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentRow {
+    pub id: String,
+    pub order_id: String,
+    pub method: String,
+    pub amount: Money,
+    pub tendered: Money,
+    pub change_amount: Money,
+    pub reference: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentMethodStat {
+    pub method: String,
+    pub amount: Money,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShiftPaymentSummary {
+    pub date_ymd: String,
+    pub shift: String,
+    pub cash: Money,
+    pub electronic: Money,
+    pub rounding: Money,
+    pub by_method: Vec<PaymentMethodStat>,
+    /// Member balance sold this shift: money taken in, but not sales.
+    pub member_topups: Money,
+    /// The part of `member_topups` paid in cash, which is in the drawer.
+    pub member_topups_cash: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PosItem {
+    #[serde(default)]
     pub product_id: String,
+    pub barcode: Option<String>,
     pub quantity: f64,
+    pub unit: Option<String>,
+    pub modifiers: Option<Vec<String>>,
+    /// Given for the member's loyalty points instead of paid for.
+    #[serde(default)]
+    pub redeem: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -336,17 +967,25 @@ pub struct MigrationStats {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinanceDailyReport {
     pub date_ymd: String,
-    pub total_income: f64,
-    pub total_expense: f64,
-    pub net_profit: f64,
+    pub total_income: Money,
+    pub total_expense: Money,
+    pub net_profit: Money,
+    pub refunds: Money,
     pub shift_count: i64,
     pub details: Vec<FinanceEntry>,
+    /// Member balance sold that day; deferred until it is spent, so not income.
+    pub member_topups: Money,
+    /// Member balances outstanding at the end of the day.
+    pub member_liability: Money,
+    /// Loyalty points outstanding at the end of the day, and what they are worth.
+    pub points_outstanding: i64,
+    pub points_liability: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinanceEntry {
     pub item: String,
-    pub amount: f64,
+    pub amount: Money,
     pub type_: String,
 }
 
@@ -354,40 +993,56 @@ pub struct FinanceEntry {
 pub struct FinanceWeeklyReport {
     pub week_start: String,
     pub week_end: String,
-    pub total_income: f64,
-    pub total_expense: f64,
-    pub net_profit: f64,
+    pub total_income: Money,
+    pub total_expense: Money,
+    pub net_profit: Money,
+    pub refunds: Money,
     pub daily_stats: Vec<DailyStat>,
+    /// Member balance sold in the week; not income until it is spent.
+    pub member_topups: Money,
+    /// Member balances outstanding at the end of the week.
+    pub member_liability: Money,
+    /// Loyalty points outstanding at the end of the week, and what they are worth.
+    pub points_outstanding: i64,
+    pub points_liability: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FinanceMonthlyReport {
     pub month: String,
-    pub total_income: f64,
-    pub total_expense: f64,
-    pub net_profit: f64,
+    pub total_income: Money,
+    pub total_expense: Money,
+    pub net_profit: Money,
+    pub refunds: Money,
     pub daily_stats: Vec<DailyStat>,
     pub category_stats: Vec<CategoryStat>,
+    /// Member balance sold in the month; deferred until it is spent, so not income.
+    pub member_topups: Money,
+    /// Member balances outstanding at the end of the month.
+    pub member_liability: Money,
+    /// Loyalty points outstanding at the end of the month, and what they are worth.
+    pub points_outstanding: i64,
+    pub points_liability: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DailyStat {
     pub date_ymd: String,
-    pub income: f64,
-    pub expense: f64,
+    pub income: Money,
+    pub expense: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CategoryStat {
     pub category: String,
-    pub income: f64,
-    pub expense: f64,
+    pub income: Money,
+    pub expense: Money,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DividendReport {
     pub month: String,
-    pub total_profit: f64,
+    pub total_profit: Money,
     pub shareholders: Vec<ShareholderDividend>,
 }
 
@@ -395,7 +1050,7 @@ pub struct DividendReport {
 pub struct ShareholderDividend {
     pub name: String,
     pub equity: f64,
-    pub dividend: f64,
+    pub dividend: Money,
     pub pick_name: String,
     pub proxy_host: Option<String>,
 }
@@ -406,7 +1061,7 @@ pub struct AuthAccountUpdateInput {
     pub display_name: Option<String>,
     pub equity: Option<f64>,
     pub proxy_host: Option<String>,
-    pub salary_base: Option<f64>,
+    pub salary_base: Option<Money>,
     pub is_hidden: Option<bool>,
     pub profile: Option<String>,
 }
@@ -416,9 +1071,17 @@ pub struct CouponRow {
     pub id: String,
     pub code: String,
     pub name: String,
-    pub face_value: f64,
+    pub face_value: Money,
+    /// The order must come to at least this much before the coupon.
+    pub min_spend: Money,
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
+    pub max_uses: i64,
+    pub use_count: i64,
     pub status: String,
+    pub batch_id: Option<String>,
     pub created_at: i64,
+    /// When it was last redeemed.
     pub used_at: Option<i64>,
 }
 
@@ -433,8 +1096,74 @@ pub struct CouponUseInput {
 pub struct CouponCreateInput {
     pub token: String,
     pub name: String,
-    pub face_value: f64,
+    pub face_value: Money,
+    /// Generated when empty.
+    #[serde(default)]
+    pub code: String,
+    pub min_spend: Option<Money>,
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
+    /// Times the coupon can be redeemed; once if unset.
+    pub max_uses: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponBatchInput {
+    pub token: String,
+    pub name: String,
+    pub face_value: Money,
+    pub count: i64,
+    /// Printed before every code of the batch, e.g. `XMAS`.
+    pub prefix: Option<String>,
+    pub min_spend: Option<Money>,
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
+    pub max_uses: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponBatch {
+    pub batch_id: String,
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponRedemptionRow {
+    pub id: String,
+    pub coupon_id: String,
     pub code: String,
+    pub name: String,
+    pub order_id: String,
+    pub discount: Money,
+    pub date_ymd: String,
+    pub actor_id: Option<String>,
+    pub created_at: i64,
+}
+
+/// Coupons of one batch, or one coupon created on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponUsageStat {
+    pub batch_id: Option<String>,
+    pub name: String,
+    pub issued: i64,
+    /// Coupons redeemed at least once.
+    pub used: i64,
+    pub voided: i64,
+    /// Coupons past their window with uses left.
+    pub expired: i64,
+    /// Redemptions and discount given within the report's dates.
+    pub redemptions: i64,
+    pub discount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CouponUsageReport {
+    pub date_from: String,
+    pub date_to: String,
+    pub redemptions: i64,
+    pub discount: Money,
+    pub stats: Vec<CouponUsageStat>,
+    pub details: Vec<CouponRedemptionRow>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -469,8 +1198,8 @@ pub struct ProductRowCloud {
     pub id: String,
     pub name: String,
     pub category: String,
-    pub unit_price: f64,
-    pub cost_price: f64,
+    pub unit_price: Money,
+    pub cost_price: Money,
     pub spec: f64,
     pub on_shelf: f64,
     pub stock: f64,
@@ -485,8 +1214,452 @@ pub struct SalesOrderRowCloud {
     pub date_ymd: String,
     pub shift: String,
     pub employee: String,
-    pub total_revenue: f64,
-    pub total_profit: f64,
+    pub total_revenue: Money,
+    pub total_profit: Money,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatRow {
+    pub id: String,
+    pub code: String,
+    pub area: Option<String>,
+    pub is_active: bool,
+    pub open_orders: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub code: String,
+    pub area: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatMenuItem {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub unit_price: Money,
+    pub modifier_groups: Vec<ModifierGroupRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatOrderSubmitInput {
+    pub items: Vec<SeatOrderItemInput>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatOrderItemInput {
+    pub product_id: String,
+    pub quantity: f64,
+    pub modifiers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatOrderRow {
+    pub id: String,
+    pub seat_code: String,
+    pub status: String,
+    pub note: Option<String>,
+    pub sales_order_id: Option<String>,
+    pub total: Money,
+    pub items: Vec<SeatOrderItemRow>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatOrderItemRow {
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: f64,
+    pub unit_price: Money,
+    pub modifiers: Vec<String>,
+    pub modifier_names: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatOrderAdvanceInput {
+    pub token: String,
+    pub id: String,
+    pub status: String,
+    /// Shift the sale is booked in when the order is delivered.
+    pub date_ymd: Option<String>,
+    pub shift: Option<String>,
+    pub employee: Option<String>,
+    pub location_id: Option<String>,
+    /// Tenders taken when the order is marked paid.
+    pub payments: Option<Vec<PosPayment>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartView {
+    pub id: String,
+    pub label: Option<String>,
+    pub status: String,
+    pub terminal: Option<String>,
+    pub location_id: Option<String>,
+    pub order_id: Option<String>,
+    pub lines: Vec<CartLineRow>,
+    /// Sum of the lines before promotions.
+    pub subtotal: Money,
+    pub discount: Money,
+    /// What a checkout would charge before 抹零.
+    pub total: Money,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartLineRow {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub modifiers: Vec<String>,
+    pub modifier_names: String,
+    /// Price per entered unit, modifiers included.
+    pub unit_price: Money,
+    pub gross: Money,
+    pub discount: Money,
+    pub promotion_name: Option<String>,
+    pub net: Money,
+    /// Why the line can no longer be sold (product, unit or option gone);
+    /// such lines are priced at zero and block checkout until removed.
+    pub unavailable: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartCreateInput {
+    pub token: String,
+    pub terminal: Option<String>,
+    pub label: Option<String>,
+    pub location_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartItemAddInput {
+    pub token: String,
+    #[serde(default)]
+    pub cart_id: String,
+    #[serde(default)]
+    pub product_id: String,
+    pub barcode: Option<String>,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub modifiers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartCheckoutInput {
+    pub token: String,
+    #[serde(default)]
+    pub cart_id: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    /// Overrides the location the cart was created with.
+    pub location_id: Option<String>,
+    pub payments: Option<Vec<PosPayment>>,
+    pub rounding: Option<String>,
+    pub idempotency_key: Option<String>,
+    pub member_id: Option<String>,
+    pub coupon_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptView {
+    pub brand_name: String,
+    pub store_name: String,
+    pub order_id: String,
+    pub kind: String,
+    pub ref_order_id: Option<String>,
+    pub date_ymd: String,
+    pub shift: String,
+    pub employee: String,
+    /// Local time of the order, `YYYY-MM-DD HH:MM`.
+    pub ordered_at: String,
+    pub lines: Vec<ReceiptLine>,
+    /// Sum of the lines before promotions.
+    pub subtotal: Money,
+    pub discount: Money,
+    pub coupon: Money,
+    pub rounding: Money,
+    pub total: Money,
+    pub payments: Vec<ReceiptPayment>,
+    /// True when a receipt for the order was printed before.
+    pub reprint: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLine {
+    pub product_name: String,
+    /// Set on the components of a bundle.
+    pub bundle_name: Option<String>,
+    pub quantity: f64,
+    pub unit_price: Money,
+    pub amount: Money,
+    pub modifiers: Vec<String>,
+    pub promotion_name: Option<String>,
+    pub discount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptPayment {
+    pub method: String,
+    pub label: String,
+    pub amount: Money,
+    pub tendered: Money,
+    pub change_amount: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Paper width in mm: 58 or 80.
+    pub paper_width: Option<u32>,
+    /// Print a receipt for every checkout without being asked.
+    pub auto_print: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterSettingsInput {
+    pub token: String,
+    pub settings: PrinterSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintJobRow {
+    pub id: String,
+    pub order_id: Option<String>,
+    pub kind: String,
+    pub status: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLink {
+    pub url: String,
+    /// The link as an SVG QR code, ready to show at the till.
+    pub qr_svg: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLinkSettings {
+    pub ttl_hours: Option<i64>,
+    /// Address customers' phones reach the till at, e.g. `http://192.168.1.20:32521`;
+    /// the till's LAN address when unset.
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLinkSettingsInput {
+    pub token: String,
+    pub settings: ReceiptLinkSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayPairing {
+    pub id: String,
+    pub terminal: String,
+    /// Six digits to type on the display.
+    pub code: String,
+    pub expires_at: i64,
+    /// Page to open on the display device.
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayRow {
+    pub id: String,
+    pub terminal: String,
+    pub paired: bool,
+    pub paired_at: Option<i64>,
+    pub last_seen_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// A cart kept by the till itself rather than on the server, shown on the
+/// till's customer display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayCartPushInput {
+    pub token: String,
+    pub terminal: String,
+    pub items: Vec<PosItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayPairInput {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayPaired {
+    /// Secret the display keeps and streams with.
+    pub key: String,
+    pub terminal: String,
+}
+
+/// What the customer display should show: the till's open cart, an idle
+/// screen, or the total just paid with a link to the electronic receipt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayMessage {
+    pub kind: String,
+    pub cart: Option<CartView>,
+    pub total: Option<Money>,
+    pub receipt_url: Option<String>,
+    pub qr_svg: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberRow {
+    pub id: String,
+    pub phone: String,
+    pub name: String,
+    pub tier: String,
+    /// Stored value left to spend.
+    pub balance: Money,
+    /// Loyalty points left to redeem.
+    pub points: i64,
+    pub note: Option<String>,
+    pub is_active: bool,
     pub created_at: i64,
     pub updated_at: i64,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberUpsertInput {
+    pub token: String,
+    pub id: Option<String>,
+    pub phone: String,
+    pub name: String,
+    pub tier: Option<String>,
+    pub note: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+/// One movement of a member's stored value; `amount` is signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletEntryRow {
+    pub id: String,
+    pub member_id: String,
+    pub kind: String,
+    pub amount: Money,
+    pub balance_after: Money,
+    pub method: Option<String>,
+    pub order_id: Option<String>,
+    pub date_ymd: String,
+    pub shift: Option<String>,
+    pub note: Option<String>,
+    pub actor_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberTopupInput {
+    pub token: String,
+    #[serde(default)]
+    pub member_id: String,
+    pub amount: Money,
+    /// How the customer paid for the top-up.
+    pub method: String,
+    pub date_ymd: String,
+    pub shift: String,
+    pub note: Option<String>,
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberAdjustInput {
+    pub token: String,
+    #[serde(default)]
+    pub member_id: String,
+    /// Added to the balance; negative to take off.
+    pub amount: Money,
+    pub reason: String,
+}
+
+/// Points per yuan for one product category, instead of the default rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryPointsRate {
+    pub category: String,
+    pub points_per_yuan: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoyaltySettings {
+    pub enabled: bool,
+    /// Points per yuan spent.
+    pub points_per_yuan: f64,
+    #[serde(default)]
+    pub category_rates: Vec<CategoryPointsRate>,
+    /// Days earning `bonus_multiplier` times the points: weekdays 1 (Monday)
+    /// to 7, and single dates as YYYY-MM-DD.
+    #[serde(default)]
+    pub bonus_weekdays: Vec<i64>,
+    #[serde(default)]
+    pub bonus_dates: Vec<String>,
+    pub bonus_multiplier: Option<f64>,
+    /// Points expire this many days after they were earned; never if unset.
+    pub expiry_days: Option<i64>,
+    /// What one point is worth, for the liability in reports.
+    pub point_value: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoyaltySettingsInput {
+    pub token: String,
+    pub settings: LoyaltySettings,
+}
+
+/// One movement of a member's points; `points` is signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointsEntryRow {
+    pub id: String,
+    pub member_id: String,
+    pub kind: String,
+    pub points: i64,
+    pub balance_after: i64,
+    /// Points of this entry not yet redeemed or expired.
+    pub remaining: i64,
+    pub expires_at: Option<i64>,
+    pub order_id: Option<String>,
+    pub date_ymd: String,
+    pub note: Option<String>,
+    pub actor_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberPointsAdjustInput {
+    pub token: String,
+    #[serde(default)]
+    pub member_id: String,
+    /// Added to the member's points; negative to take off.
+    pub points: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedeemableProduct {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub base_unit: String,
+    /// Points for one base unit.
+    pub redeem_points: i64,
+}
@@ -0,0 +1,317 @@
+// Money for Smarticafe
+// 所有金额以“分”为单位的整数保存和计算，避免 f64 累加误差
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An amount of money in fen (0.01 yuan).
+///
+/// Stored in SQLite as an INTEGER count of fen. On the wire it stays a JSON
+/// number of yuan (`12.5`), so clients keep sending and showing yuan; a
+/// decimal string (`"12.50"`) is accepted too.
+///
+/// Rounding: whenever an amount is scaled (quantities, ratios, percentages) or
+/// parsed with more than two decimals, it is rounded to the nearest fen with
+/// halves going away from zero (四舍五入). Splits use [`Money::allocate`], which
+/// never loses or invents a fen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_fen(fen: i64) -> Money {
+        Money(fen)
+    }
+
+    pub const fn fen(self) -> i64 {
+        self.0
+    }
+
+    /// Parses a yuan amount such as `"12.345"` or `"-3"` exactly.
+    pub fn parse_yuan(s: &str) -> Option<Money> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, frac) = match digits.split_once('.') {
+            Some((w, f)) => (w, f),
+            None => (digits, ""),
+        };
+        if (whole.is_empty() && frac.is_empty())
+            || !whole.chars().all(|c| c.is_ascii_digit())
+            || !frac.chars().all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let yuan: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+        let mut frac_digits = frac.bytes().map(|b| (b - b'0') as i64);
+        let jiao = frac_digits.next().unwrap_or(0);
+        let fen = frac_digits.next().unwrap_or(0);
+        let round_up = frac_digits.next().unwrap_or(0) >= 5;
+        let total = yuan.checked_mul(100)?.checked_add(jiao * 10 + fen + i64::from(round_up))?;
+        Some(Money(if negative { -total } else { total }))
+    }
+
+    /// Converts a yuan amount coming from an f64, rounding to the fen. The
+    /// shortest decimal form of the float is used, so `1.005` becomes 1.01.
+    pub fn from_yuan(yuan: f64) -> Money {
+        if !yuan.is_finite() {
+            return Money::ZERO;
+        }
+        Money::parse_yuan(&yuan.to_string()).unwrap_or_else(|| Money((yuan * 100.0).round() as i64))
+    }
+
+    /// The amount in yuan, for display and JSON only; never compute with it.
+    pub fn to_yuan(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    /// `self × factor`, rounded to the fen. Used for quantities and rates.
+    pub fn mul_f64(self, factor: f64) -> Money {
+        Money((self.0 as f64 * factor).round() as i64)
+    }
+
+    pub fn abs(self) -> Money {
+        Money(self.0.abs())
+    }
+
+    pub fn min(self, other: Money) -> Money {
+        Money(self.0.min(other.0))
+    }
+
+    pub fn max(self, other: Money) -> Money {
+        Money(self.0.max(other.0))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Splits the amount in proportion to `weights` so that the parts add up
+    /// to it exactly (largest remainder method). Leftover fen go to the parts
+    /// with the largest fractional share, ties to the earlier part, so the
+    /// same inputs always give the same split. Without any positive weight the
+    /// amount is split evenly.
+    pub fn allocate(self, weights: &[f64]) -> Vec<Money> {
+        if weights.is_empty() {
+            return Vec::new();
+        }
+        let clean: Vec<f64> = weights.iter().map(|w| if w.is_finite() && *w > 0.0 { *w } else { 0.0 }).collect();
+        let total_weight: f64 = clean.iter().sum();
+        let clean = if total_weight > 0.0 { clean } else { vec![1.0; weights.len()] };
+        let total_weight: f64 = clean.iter().sum();
+
+        let amount = self.0.abs();
+        let mut parts: Vec<i64> = Vec::with_capacity(clean.len());
+        let mut remainders: Vec<(usize, f64)> = Vec::with_capacity(clean.len());
+        for (i, w) in clean.iter().enumerate() {
+            let exact = amount as f64 * w / total_weight;
+            let floor = exact.floor();
+            parts.push(floor as i64);
+            remainders.push((i, exact - floor));
+        }
+        let mut left = amount - parts.iter().sum::<i64>();
+        remainders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        for (i, _) in remainders.iter() {
+            if left <= 0 {
+                break;
+            }
+            parts[*i] += 1;
+            left -= 1;
+        }
+        parts
+            .into_iter()
+            .map(|p| Money(if self.0 < 0 { -p } else { p }))
+            .collect()
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{sign}{}.{:02}", abs / 100, abs % 100)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_yuan())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        struct YuanVisitor;
+
+        impl de::Visitor<'_> for YuanVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an amount in yuan")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+                v.checked_mul(100).map(Money).ok_or_else(|| E::custom("amount out of range"))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+                i64::try_from(v).map_err(|_| E::custom("amount out of range")).and_then(|v| self.visit_i64(v))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Money, E> {
+                if !v.is_finite() {
+                    return Err(E::custom("amount is not a number"));
+                }
+                Ok(Money::from_yuan(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Money, E> {
+                Money::parse_yuan(v).ok_or_else(|| E::custom(format!("invalid amount: {v}")))
+            }
+        }
+
+        deserializer.deserialize_any(YuanVisitor)
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0))
+    }
+}
+
+// Columns created before the switch to fen keep REAL affinity; the migration
+// rewrote their values as whole numbers of fen, so a REAL is read as fen too.
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Money> {
+        match value {
+            ValueRef::Integer(v) => Ok(Money(v)),
+            ValueRef::Real(v) => Ok(Money(v.round() as i64)),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fen(parts: &[Money]) -> Vec<i64> {
+        parts.iter().map(|m| m.fen()).collect()
+    }
+
+    #[test]
+    fn parse_yuan_rounds_halves_away_from_zero() {
+        assert_eq!(Money::parse_yuan("1.005"), Some(Money(101)));
+        assert_eq!(Money::parse_yuan("-0.005"), Some(Money(-1)));
+        assert_eq!(Money::parse_yuan("0.995"), Some(Money(100)));
+        assert_eq!(Money::parse_yuan("12.3449"), Some(Money(1234)));
+        assert_eq!(Money::parse_yuan("12.34999"), Some(Money(1235)));
+        assert_eq!(Money::parse_yuan(" +7 "), Some(Money(700)));
+        assert_eq!(Money::parse_yuan(".5"), Some(Money(50)));
+        for bad in ["", "-", ".", "1.2.3", "1,000", "¥3", "1e3", "99999999999999999999"] {
+            assert_eq!(Money::parse_yuan(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn from_yuan_uses_the_shortest_decimal() {
+        assert_eq!(Money::from_yuan(1.005), Money(101));
+        assert_eq!(Money::from_yuan(-0.005), Money(-1));
+        assert_eq!(Money::from_yuan(2.675), Money(268));
+        assert_eq!(Money::from_yuan(0.1 + 0.2), Money(30));
+        assert_eq!(Money::from_yuan(f64::NAN), Money::ZERO);
+        assert_eq!(Money::from_yuan(f64::INFINITY), Money::ZERO);
+    }
+
+    #[test]
+    fn mul_f64_rounds_halves_away_from_zero() {
+        assert_eq!(Money(5).mul_f64(0.5), Money(3));
+        assert_eq!(Money(-5).mul_f64(0.5), Money(-3));
+        assert_eq!(Money(1250).mul_f64(0.15), Money(188));
+    }
+
+    #[test]
+    fn allocate_adds_up_exactly() {
+        assert_eq!(fen(&Money(100).allocate(&[1.0, 1.0, 1.0])), vec![34, 33, 33]);
+        assert_eq!(fen(&Money(-100).allocate(&[1.0, 1.0, 1.0])), vec![-34, -33, -33]);
+        assert_eq!(fen(&Money(1000).allocate(&[0.3, 0.3, 0.4])), vec![300, 300, 400]);
+        for (amount, weights) in [(9_999, vec![1.0, 2.0, 3.0, 7.0]), (1, vec![0.1, 0.1]), (123_457, vec![1.0 / 3.0; 7])] {
+            let parts = Money(amount).allocate(&weights);
+            assert_eq!(parts.len(), weights.len());
+            assert_eq!(parts.iter().copied().sum::<Money>(), Money(amount));
+        }
+        assert!(Money(100).allocate(&[]).is_empty());
+    }
+
+    #[test]
+    fn allocate_breaks_ties_towards_earlier_parts() {
+        // Both remainders are .5; the earlier part gets the fen, every time.
+        assert_eq!(fen(&Money(3).allocate(&[1.0, 1.0])), vec![2, 1]);
+        assert_eq!(fen(&Money(5).allocate(&[2.0, 1.0, 1.0, 2.0])), vec![2, 1, 1, 1]);
+        let first = Money(1001).allocate(&[3.0, 3.0, 3.0]);
+        for _ in 0..10 {
+            assert_eq!(Money(1001).allocate(&[3.0, 3.0, 3.0]), first);
+        }
+    }
+
+    #[test]
+    fn allocate_without_positive_weights_splits_evenly() {
+        assert_eq!(fen(&Money(5).allocate(&[0.0, 0.0])), vec![3, 2]);
+        assert_eq!(fen(&Money(7).allocate(&[-1.0, f64::NAN, 0.0])), vec![3, 2, 2]);
+        // Bad weights count as zero next to a good one.
+        assert_eq!(fen(&Money(7).allocate(&[-1.0, f64::NAN, 2.0])), vec![0, 0, 7]);
+    }
+}
@@ -1,11 +1,16 @@
 use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
 use rusqlite::{Connection, OptionalExtension};
 use crate::models::SystemLog;
 use crate::db::now_ymd;
 
 pub static AUTH_SESSIONS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
 pub static SYSTEM_LOGS: OnceLock<Mutex<Vec<SystemLog>>> = OnceLock::new();
+/// Live customer-display feeds, one channel per till.
+pub static DISPLAY_CHANNELS: OnceLock<Mutex<HashMap<String, broadcast::Sender<String>>>> = OnceLock::new();
+/// When recent wrong display pairing codes were entered.
+pub static DISPLAY_PAIR_FAILURES: OnceLock<Mutex<Vec<i64>>> = OnceLock::new();
 
 pub fn auth_sessions() -> &'static Mutex<HashMap<String, String>> {
     AUTH_SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
@@ -15,6 +20,14 @@ pub fn system_logs() -> &'static Mutex<Vec<SystemLog>> {
     SYSTEM_LOGS.get_or_init(|| Mutex::new(Vec::new()))
 }
 
+pub fn display_channels() -> &'static Mutex<HashMap<String, broadcast::Sender<String>>> {
+    DISPLAY_CHANNELS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn display_pair_failures() -> &'static Mutex<Vec<i64>> {
+    DISPLAY_PAIR_FAILURES.get_or_init(|| Mutex::new(Vec::new()))
+}
+
 pub fn auth_resolve_account_id(token: &str) -> Option<String> {
     if token.trim().is_empty() {
         return None;
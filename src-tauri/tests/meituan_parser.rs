@@ -0,0 +1,76 @@
+// Runs the Meituan verification-record parser over every fixture in
+// tests/fixtures/meituan: each NAME.txt is pasted text and NAME.json the
+// items and errors it must produce. Only the keys a fixture lists are
+// compared, so fixtures can leave out raw_text where it adds nothing.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::{json, Map, Value};
+use smarticafe_lib::commands::meituan::parse_meituan_text;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/meituan")
+}
+
+fn project(expected: &Value, actual: &Value) -> Value {
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            let picked: Map<String, Value> = e
+                .keys()
+                .map(|k| (k.clone(), project(&e[k], a.get(k).unwrap_or(&Value::Null))))
+                .collect();
+            Value::Object(picked)
+        }
+        (Value::Array(e), Value::Array(a)) => Value::Array(
+            a.iter()
+                .enumerate()
+                .map(|(i, v)| e.get(i).map(|ev| project(ev, v)).unwrap_or_else(|| v.clone()))
+                .collect(),
+        ),
+        _ => actual.clone(),
+    }
+}
+
+#[test]
+fn meituan_fixtures() {
+    let mut names: Vec<String> = fs::read_dir(fixtures_dir())
+        .expect("fixtures dir")
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|x| x == "txt"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty(), "no fixtures found");
+
+    for name in names {
+        let text = fs::read_to_string(fixtures_dir().join(format!("{name}.txt"))).expect("fixture text");
+        let expected: Value = serde_json::from_str(
+            &fs::read_to_string(fixtures_dir().join(format!("{name}.json"))).expect("fixture json"),
+        )
+        .expect("fixture json parses");
+
+        let parsed = parse_meituan_text(&text, "2025-10-09", "白班", "test", None);
+        for it in parsed.items.iter() {
+            assert_eq!(it.date_ymd, "2025-10-09", "{name}");
+            assert_eq!(it.bar_total, it.amount, "{name}");
+        }
+        let actual = json!({
+            "items": parsed.items.iter().map(|it| json!({
+                "coupon_no": it.coupon_no,
+                "raw_text": it.raw_text,
+                "amount": it.amount.to_string(),
+                "discount": it.discount.to_string(),
+                "financial": it.financial.to_string(),
+            })).collect::<Vec<_>>(),
+            "errors": parsed.errors.iter().map(|e| json!({
+                "line": e.line,
+                "text": e.text,
+                "error": e.error,
+            })).collect::<Vec<_>>(),
+        });
+
+        assert_eq!(project(&expected, &actual), expected, "fixture {name}");
+    }
+}
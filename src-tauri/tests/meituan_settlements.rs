@@ -0,0 +1,113 @@
+// Runs the Meituan settlement importer against an in-memory database. The
+// statement in tests/fixtures/meituan_settlements is GBK-encoded, opens with
+// title lines and dates one row by Excel serial, the way Meituan exports it.
+
+use std::fs;
+use std::path::PathBuf;
+
+use rusqlite::Connection;
+use smarticafe_lib::commands::meituan::import_settlements;
+use smarticafe_lib::db::init_db;
+use smarticafe_lib::models::MeituanSettlementImportInput;
+use smarticafe_lib::money::Money;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/meituan_settlements").join(name);
+    fs::read(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+fn open() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    init_db(&conn).unwrap();
+    conn
+}
+
+fn input(format: &str, content: Vec<u8>, dry_run: bool) -> MeituanSettlementImportInput {
+    MeituanSettlementImportInput {
+        token: String::new(),
+        format: format.to_string(),
+        content,
+        mapping: None,
+        settle_date: None,
+        dry_run: Some(dry_run),
+    }
+}
+
+fn settlements(conn: &Connection) -> Vec<(String, String, Money)> {
+    let mut stmt = conn
+        .prepare("SELECT coupon_no, settle_date, amount FROM meituan_settlements ORDER BY coupon_no")
+        .unwrap();
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap();
+    rows.map(Result::unwrap).collect()
+}
+
+#[test]
+fn gbk_statement_with_title_rows() {
+    let mut conn = open();
+    let report = import_settlements(&mut conn, &input("csv", fixture("gbk_titled.csv"), false), "admin").unwrap();
+
+    assert!(report.applied, "{:?}", report.rows);
+    assert_eq!((report.created, report.skipped, report.errors), (2, 0, 0));
+    assert_eq!(report.total, Money::from_fen(101850));
+    // Rows are numbered as in the file, after the two title lines and the header.
+    assert_eq!(report.rows.iter().map(|r| r.row).collect::<Vec<_>>(), vec![4, 5]);
+    assert_eq!(
+        settlements(&conn),
+        vec![
+            (String::from("123456789012"), String::from("2025-10-10"), Money::from_fen(1850)),
+            (String::from("234567890123"), String::from("2025-10-11"), Money::from_fen(100000)),
+        ]
+    );
+}
+
+#[test]
+fn dry_run_leaves_nothing_behind() {
+    let mut conn = open();
+    let report = import_settlements(&mut conn, &input("csv", fixture("gbk_titled.csv"), true), "admin").unwrap();
+
+    assert!(!report.applied);
+    assert_eq!(report.created, 2);
+    assert!(settlements(&conn).is_empty());
+}
+
+#[test]
+fn reimport_skips_and_changed_amount_is_already_settled() {
+    let mut conn = open();
+    import_settlements(&mut conn, &input("csv", fixture("gbk_titled.csv"), false), "admin").unwrap();
+
+    let again = import_settlements(&mut conn, &input("csv", fixture("gbk_titled.csv"), false), "admin").unwrap();
+    assert!(again.applied);
+    assert_eq!((again.created, again.skipped, again.errors), (0, 2, 0));
+
+    let changed = "券码,结算金额,结算日期\n123456789012,20.00,2025-10-12\n345678901234,5.00,2025-10-12\n";
+    let report = import_settlements(&mut conn, &input("csv", changed.as_bytes().to_vec(), false), "admin").unwrap();
+    assert!(!report.applied);
+    assert_eq!((report.created, report.errors), (1, 1));
+    assert_eq!(report.rows[0].errors, vec![String::from("already_settled")]);
+    // One bad row rolls the whole statement back.
+    assert_eq!(settlements(&conn).len(), 2);
+}
+
+#[test]
+fn xlsx_date_cells_are_excel_serials() {
+    let mut wb = rust_xlsxwriter::Workbook::new();
+    let date = rust_xlsxwriter::Format::new().set_num_format("yyyy-mm-dd");
+    let ws = wb.add_worksheet();
+    ws.write_string(0, 0, "美团团购结算账单").unwrap();
+    ws.write_string(2, 0, "券码").unwrap();
+    ws.write_string(2, 1, "结算金额").unwrap();
+    ws.write_string(2, 2, "结算日期").unwrap();
+    ws.write_string(3, 0, "456789012345").unwrap();
+    ws.write_number(3, 1, 12.5).unwrap();
+    ws.write_number_with_format(3, 2, 45941.0, &date).unwrap();
+    let content = wb.save_to_buffer().unwrap();
+
+    let mut conn = open();
+    let report = import_settlements(&mut conn, &input("xlsx", content, false), "admin").unwrap();
+
+    assert!(report.applied, "{:?}", report.rows);
+    assert_eq!(
+        settlements(&conn),
+        vec![(String::from("456789012345"), String::from("2025-10-11"), Money::from_fen(1250))]
+    );
+}